@@ -0,0 +1,3 @@
+DROP TABLE cosigner;
+DROP TABLE wallet;
+DROP TABLE psbt;
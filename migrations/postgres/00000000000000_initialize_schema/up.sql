@@ -0,0 +1,45 @@
+CREATE TABLE cosigner (
+  id SERIAL PRIMARY KEY,
+  uuid TEXT NOT NULL UNIQUE,
+  "type" SMALLINT NOT NULL,
+  email_address TEXT DEFAULT NULL,
+  xpub TEXT NOT NULL,
+  xprv TEXT DEFAULT NULL,
+  creation_time TIMESTAMP NOT NULL,
+  wallet_uuid TEXT DEFAULT NULL UNIQUE
+);
+
+CREATE TABLE wallet (
+  id SERIAL PRIMARY KEY,
+  uuid TEXT NOT NULL UNIQUE,
+  address_type SMALLINT NOT NULL,
+  network SMALLINT NOT NULL,
+  receive_descriptor TEXT NOT NULL,
+  receive_descriptor_watch_only TEXT NOT NULL,
+  receive_address_index BIGINT NOT NULL,
+  receive_address TEXT NOT NULL DEFAULT '',
+  change_descriptor TEXT NOT NULL,
+  change_descriptor_watch_only TEXT NOT NULL,
+  change_address_index BIGINT NOT NULL,
+  change_address TEXT NOT NULL DEFAULT '',
+  required_signatures SMALLINT NOT NULL,
+  balance TEXT NOT NULL,
+  creation_time TIMESTAMP NOT NULL
+);
+
+CREATE TABLE psbt (
+  id SERIAL PRIMARY KEY,
+  uuid TEXT NOT NULL,
+  base64 TEXT NOT NULL,
+  creation_time TIMESTAMP NOT NULL,
+  wallet_uuid TEXT NOT NULL UNIQUE
+);
+
+CREATE INDEX cosigner_uuid_idx ON cosigner (uuid);
+CREATE INDEX cosigner_wallet_uuid_idx ON cosigner (wallet_uuid);
+CREATE INDEX email_address_idx ON cosigner (email_address);
+CREATE INDEX xpub_idx ON cosigner (xpub);
+CREATE INDEX wallet_uuid_idx ON wallet (uuid);
+CREATE INDEX wallet_receive_descriptor_watch_only_idx ON wallet (receive_descriptor_watch_only);
+CREATE INDEX psbt_wallet_uuid_idx ON psbt (wallet_uuid);
+CREATE INDEX psbt_uuid_idx ON psbt (uuid);
@@ -0,0 +1,2 @@
+DROP TABLE bsms_key_record;
+DROP TABLE bsms_session;
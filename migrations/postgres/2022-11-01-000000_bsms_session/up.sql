@@ -0,0 +1,22 @@
+CREATE TABLE bsms_session (
+  id SERIAL PRIMARY KEY,
+  uuid TEXT NOT NULL UNIQUE,
+  token TEXT NOT NULL,
+  address_type SMALLINT NOT NULL,
+  network SMALLINT NOT NULL,
+  required_signatures SMALLINT NOT NULL,
+  signers SMALLINT NOT NULL,
+  wallet_uuid TEXT DEFAULT NULL,
+  creation_time TIMESTAMP NOT NULL
+);
+
+CREATE TABLE bsms_key_record (
+  id SERIAL PRIMARY KEY,
+  session_uuid TEXT NOT NULL,
+  cosigner_uuid TEXT NOT NULL,
+  record TEXT NOT NULL,
+  creation_time TIMESTAMP NOT NULL
+);
+
+CREATE INDEX bsms_session_uuid_idx ON bsms_session (uuid);
+CREATE INDEX bsms_key_record_session_uuid_idx ON bsms_key_record (session_uuid);
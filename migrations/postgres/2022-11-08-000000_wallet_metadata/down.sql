@@ -0,0 +1,6 @@
+DROP INDEX cosigner_name_idx;
+DROP INDEX wallet_name_idx;
+DROP TABLE wallet_tag;
+ALTER TABLE cosigner DROP COLUMN name;
+ALTER TABLE wallet DROP COLUMN description;
+ALTER TABLE wallet DROP COLUMN name;
@@ -0,0 +1,14 @@
+ALTER TABLE wallet ADD COLUMN name TEXT DEFAULT NULL;
+ALTER TABLE wallet ADD COLUMN description TEXT DEFAULT NULL;
+ALTER TABLE cosigner ADD COLUMN name TEXT DEFAULT NULL;
+
+CREATE TABLE wallet_tag (
+  id SERIAL PRIMARY KEY,
+  wallet_uuid TEXT NOT NULL,
+  tag TEXT NOT NULL,
+  UNIQUE (wallet_uuid, tag)
+);
+
+CREATE INDEX wallet_name_idx ON wallet (name);
+CREATE INDEX cosigner_name_idx ON cosigner (name);
+CREATE INDEX wallet_tag_tag_idx ON wallet_tag (tag);
@@ -0,0 +1,14 @@
+CREATE TABLE wallet_label (
+  id SERIAL PRIMARY KEY,
+  wallet_uuid TEXT NOT NULL,
+  "type" SMALLINT NOT NULL,
+  "ref" TEXT NOT NULL,
+  label TEXT NOT NULL,
+  origin TEXT DEFAULT NULL,
+  spendable BOOLEAN DEFAULT NULL,
+  creation_time TIMESTAMP NOT NULL,
+  UNIQUE (wallet_uuid, "type", "ref")
+);
+
+CREATE INDEX wallet_label_wallet_uuid_idx ON wallet_label (wallet_uuid);
+CREATE INDEX wallet_label_ref_idx ON wallet_label ("ref");
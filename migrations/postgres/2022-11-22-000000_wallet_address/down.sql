@@ -0,0 +1,2 @@
+DROP INDEX wallet_address_address_idx;
+DROP TABLE wallet_address;
@@ -0,0 +1,11 @@
+CREATE TABLE wallet_address (
+  id SERIAL PRIMARY KEY,
+  wallet_uuid TEXT NOT NULL,
+  address_index BIGINT NOT NULL,
+  address TEXT NOT NULL,
+  used BOOLEAN NOT NULL DEFAULT FALSE,
+  creation_time TIMESTAMP NOT NULL,
+  UNIQUE (wallet_uuid, address_index)
+);
+
+CREATE INDEX wallet_address_address_idx ON wallet_address (address);
@@ -0,0 +1,24 @@
+DROP TABLE wallet_cosigner;
+
+ALTER TABLE bsms_key_record DROP CONSTRAINT bsms_key_record_cosigner_uuid_fkey;
+ALTER TABLE bsms_key_record DROP CONSTRAINT bsms_key_record_session_uuid_fkey;
+ALTER TABLE bsms_session DROP CONSTRAINT bsms_session_wallet_uuid_fkey;
+ALTER TABLE wallet_address DROP CONSTRAINT wallet_address_wallet_uuid_fkey;
+ALTER TABLE wallet_label DROP CONSTRAINT wallet_label_wallet_uuid_fkey;
+ALTER TABLE wallet_tag DROP CONSTRAINT wallet_tag_wallet_uuid_fkey;
+ALTER TABLE psbt DROP CONSTRAINT psbt_wallet_uuid_fkey;
+ALTER TABLE cosigner DROP CONSTRAINT cosigner_wallet_uuid_fkey;
+
+-- Without the references the orphaned rows fit in again.
+INSERT INTO cosigner SELECT * FROM orphaned_cosigner;
+INSERT INTO psbt SELECT * FROM orphaned_psbt;
+INSERT INTO wallet_tag SELECT * FROM orphaned_wallet_tag;
+INSERT INTO wallet_label SELECT * FROM orphaned_wallet_label;
+INSERT INTO wallet_address SELECT * FROM orphaned_wallet_address;
+INSERT INTO bsms_key_record SELECT * FROM orphaned_bsms_key_record;
+DROP TABLE orphaned_bsms_key_record;
+DROP TABLE orphaned_wallet_address;
+DROP TABLE orphaned_wallet_label;
+DROP TABLE orphaned_wallet_tag;
+DROP TABLE orphaned_psbt;
+DROP TABLE orphaned_cosigner;
@@ -0,0 +1,69 @@
+-- Rows left behind by forgotten wallets can't satisfy the new references. As
+-- they may hold internal keys, they are moved into orphaned_* tables for the
+-- operator to review instead of being dropped.
+CREATE TABLE IF NOT EXISTS orphaned_cosigner AS SELECT * FROM cosigner WHERE false;
+INSERT INTO orphaned_cosigner
+  SELECT * FROM cosigner
+  WHERE wallet_uuid IS NOT NULL AND wallet_uuid NOT IN (SELECT uuid FROM wallet);
+DELETE FROM cosigner WHERE uuid IN (SELECT uuid FROM orphaned_cosigner);
+
+CREATE TABLE IF NOT EXISTS orphaned_psbt AS SELECT * FROM psbt WHERE false;
+INSERT INTO orphaned_psbt SELECT * FROM psbt WHERE wallet_uuid NOT IN (SELECT uuid FROM wallet);
+DELETE FROM psbt WHERE wallet_uuid NOT IN (SELECT uuid FROM wallet);
+
+CREATE TABLE IF NOT EXISTS orphaned_wallet_tag AS SELECT * FROM wallet_tag WHERE false;
+INSERT INTO orphaned_wallet_tag
+  SELECT * FROM wallet_tag WHERE wallet_uuid NOT IN (SELECT uuid FROM wallet);
+DELETE FROM wallet_tag WHERE wallet_uuid NOT IN (SELECT uuid FROM wallet);
+
+CREATE TABLE IF NOT EXISTS orphaned_wallet_label AS SELECT * FROM wallet_label WHERE false;
+INSERT INTO orphaned_wallet_label
+  SELECT * FROM wallet_label WHERE wallet_uuid NOT IN (SELECT uuid FROM wallet);
+DELETE FROM wallet_label WHERE wallet_uuid NOT IN (SELECT uuid FROM wallet);
+
+CREATE TABLE IF NOT EXISTS orphaned_wallet_address AS SELECT * FROM wallet_address WHERE false;
+INSERT INTO orphaned_wallet_address
+  SELECT * FROM wallet_address WHERE wallet_uuid NOT IN (SELECT uuid FROM wallet);
+DELETE FROM wallet_address WHERE wallet_uuid NOT IN (SELECT uuid FROM wallet);
+
+UPDATE bsms_session SET wallet_uuid = NULL WHERE wallet_uuid NOT IN (SELECT uuid FROM wallet);
+
+CREATE TABLE IF NOT EXISTS orphaned_bsms_key_record AS SELECT * FROM bsms_key_record WHERE false;
+INSERT INTO orphaned_bsms_key_record
+  SELECT * FROM bsms_key_record
+  WHERE session_uuid NOT IN (SELECT uuid FROM bsms_session)
+    OR cosigner_uuid NOT IN (SELECT uuid FROM cosigner);
+DELETE FROM bsms_key_record WHERE id IN (SELECT id FROM orphaned_bsms_key_record);
+
+ALTER TABLE cosigner ADD CONSTRAINT cosigner_wallet_uuid_fkey
+  FOREIGN KEY (wallet_uuid) REFERENCES wallet (uuid) ON DELETE CASCADE;
+ALTER TABLE psbt ADD CONSTRAINT psbt_wallet_uuid_fkey
+  FOREIGN KEY (wallet_uuid) REFERENCES wallet (uuid) ON DELETE CASCADE;
+ALTER TABLE wallet_tag ADD CONSTRAINT wallet_tag_wallet_uuid_fkey
+  FOREIGN KEY (wallet_uuid) REFERENCES wallet (uuid) ON DELETE CASCADE;
+ALTER TABLE wallet_label ADD CONSTRAINT wallet_label_wallet_uuid_fkey
+  FOREIGN KEY (wallet_uuid) REFERENCES wallet (uuid) ON DELETE CASCADE;
+ALTER TABLE wallet_address ADD CONSTRAINT wallet_address_wallet_uuid_fkey
+  FOREIGN KEY (wallet_uuid) REFERENCES wallet (uuid) ON DELETE CASCADE;
+ALTER TABLE bsms_session ADD CONSTRAINT bsms_session_wallet_uuid_fkey
+  FOREIGN KEY (wallet_uuid) REFERENCES wallet (uuid) ON DELETE SET NULL;
+ALTER TABLE bsms_key_record ADD CONSTRAINT bsms_key_record_session_uuid_fkey
+  FOREIGN KEY (session_uuid) REFERENCES bsms_session (uuid) ON DELETE CASCADE;
+ALTER TABLE bsms_key_record ADD CONSTRAINT bsms_key_record_cosigner_uuid_fkey
+  FOREIGN KEY (cosigner_uuid) REFERENCES cosigner (uuid) ON DELETE CASCADE;
+
+-- External cosigners each wallet was created with. Existing wallets are
+-- linked to the cosigners whose xpub appears in their descriptor.
+CREATE TABLE wallet_cosigner (
+  id SERIAL PRIMARY KEY,
+  wallet_uuid TEXT NOT NULL REFERENCES wallet (uuid) ON DELETE CASCADE,
+  cosigner_uuid TEXT NOT NULL REFERENCES cosigner (uuid) ON DELETE RESTRICT,
+  UNIQUE (wallet_uuid, cosigner_uuid)
+);
+
+INSERT INTO wallet_cosigner (wallet_uuid, cosigner_uuid)
+  SELECT w.uuid, c.uuid
+  FROM wallet w JOIN cosigner c ON strpos(w.receive_descriptor_watch_only, c.xpub) > 0
+  WHERE c.wallet_uuid IS NULL;
+
+CREATE INDEX wallet_cosigner_cosigner_uuid_idx ON wallet_cosigner (cosigner_uuid);
@@ -0,0 +1,6 @@
+ALTER TABLE psbt DROP CONSTRAINT psbt_uuid_key;
+
+ALTER TABLE wallet_label DROP COLUMN archived_by, DROP COLUMN archived_at;
+ALTER TABLE psbt DROP COLUMN archived_by, DROP COLUMN archived_at;
+ALTER TABLE cosigner DROP COLUMN archived_by, DROP COLUMN archived_at;
+ALTER TABLE wallet DROP COLUMN archived_by, DROP COLUMN archived_at;
@@ -0,0 +1,9 @@
+ALTER TABLE wallet ADD COLUMN archived_at TIMESTAMP, ADD COLUMN archived_by TEXT;
+ALTER TABLE cosigner ADD COLUMN archived_at TIMESTAMP, ADD COLUMN archived_by TEXT;
+ALTER TABLE psbt ADD COLUMN archived_at TIMESTAMP, ADD COLUMN archived_by TEXT;
+ALTER TABLE wallet_label ADD COLUMN archived_at TIMESTAMP, ADD COLUMN archived_by TEXT;
+
+-- Archived PSBTs stay around, so a wallet has any number of them and they
+-- are told apart by their uuid.
+ALTER TABLE psbt DROP CONSTRAINT psbt_wallet_uuid_key;
+ALTER TABLE psbt ADD CONSTRAINT psbt_uuid_key UNIQUE (uuid);
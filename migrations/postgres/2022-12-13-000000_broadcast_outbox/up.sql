@@ -0,0 +1,16 @@
+CREATE TABLE broadcast (
+  id SERIAL PRIMARY KEY,
+  txid TEXT NOT NULL UNIQUE,
+  wallet_uuid TEXT NOT NULL REFERENCES wallet (uuid) ON DELETE CASCADE,
+  -- The PSBT is deleted once its transaction was broadcast.
+  psbt_uuid TEXT NOT NULL,
+  raw_transaction TEXT NOT NULL,
+  status SMALLINT NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  last_error TEXT,
+  creation_time TIMESTAMP NOT NULL,
+  broadcast_time TIMESTAMP
+);
+
+CREATE INDEX broadcast_wallet_uuid_idx ON broadcast (wallet_uuid);
+CREATE INDEX broadcast_status_idx ON broadcast (status);
@@ -0,0 +1,6 @@
+DROP TABLE key_export;
+
+ALTER TABLE cosigner
+  DROP COLUMN mnemonic_exported_at,
+  DROP COLUMN mnemonic_has_passphrase,
+  DROP COLUMN mnemonic;
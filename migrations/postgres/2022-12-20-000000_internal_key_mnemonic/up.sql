@@ -0,0 +1,17 @@
+-- The mnemonic of an internal key, encrypted, until it has been exported.
+ALTER TABLE cosigner
+  ADD COLUMN mnemonic TEXT,
+  ADD COLUMN mnemonic_has_passphrase BOOLEAN NOT NULL DEFAULT FALSE,
+  ADD COLUMN mnemonic_exported_at TIMESTAMP;
+
+-- An audit trail of key exports, kept after the cosigner is purged, hence
+-- without a reference to it. Backups, which take all keys, have no cosigner.
+CREATE TABLE key_export (
+  id SERIAL PRIMARY KEY,
+  cosigner_uuid TEXT,
+  actor TEXT NOT NULL,
+  outcome TEXT NOT NULL,
+  creation_time TIMESTAMP NOT NULL
+);
+
+CREATE INDEX key_export_cosigner_uuid_idx ON key_export (cosigner_uuid);
@@ -0,0 +1,3 @@
+DROP INDEX wallet_successor_uuid_idx;
+
+ALTER TABLE wallet DROP COLUMN successor_uuid;
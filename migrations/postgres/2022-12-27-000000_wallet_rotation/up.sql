@@ -0,0 +1,5 @@
+-- The wallet that took over from this one when its internal key was rotated.
+ALTER TABLE wallet
+  ADD COLUMN successor_uuid TEXT REFERENCES wallet (uuid) ON DELETE SET NULL;
+
+CREATE INDEX wallet_successor_uuid_idx ON wallet (successor_uuid);
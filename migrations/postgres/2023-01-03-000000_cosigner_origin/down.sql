@@ -0,0 +1 @@
+ALTER TABLE cosigner DROP COLUMN origin;
@@ -0,0 +1,3 @@
+-- Key records are only accepted unencrypted, which BIP129 signals with the
+-- token 00. Open sessions are switched over so they can still complete.
+UPDATE bsms_session SET token = '00' WHERE wallet_uuid IS NULL;
@@ -0,0 +1,22 @@
+CREATE TABLE 'bsms_session' (
+  'id' INTEGER NOT NULL  DEFAULT NULL PRIMARY KEY AUTOINCREMENT,
+  'uuid' MEDIUMTEXT NOT NULL  DEFAULT 'NULL' UNIQUE,
+  'token' MEDIUMTEXT NOT NULL  DEFAULT 'NULL',
+  'address_type' SMALLINT NOT NULL  DEFAULT NULL,
+  'network' SMALLINT NOT NULL  DEFAULT NULL,
+  'required_signatures' SMALLINT NOT NULL  DEFAULT NULL,
+  'signers' SMALLINT NOT NULL  DEFAULT NULL,
+  'wallet_uuid' MEDIUMTEXT DEFAULT NULL,
+  'creation_time' DATETIME NOT NULL  DEFAULT 'NULL'
+);
+
+CREATE TABLE 'bsms_key_record' (
+  'id' INTEGER NOT NULL  DEFAULT NULL PRIMARY KEY AUTOINCREMENT,
+  'session_uuid' MEDIUMTEXT NOT NULL  DEFAULT 'NULL',
+  'cosigner_uuid' MEDIUMTEXT NOT NULL  DEFAULT 'NULL',
+  'record' MEDIUMTEXT NOT NULL  DEFAULT 'NULL',
+  'creation_time' DATETIME NOT NULL  DEFAULT 'NULL'
+);
+
+CREATE INDEX 'bsms_session_uuid_idx' ON 'bsms_session' ('uuid');
+CREATE INDEX 'bsms_key_record_session_uuid_idx' ON 'bsms_key_record' ('session_uuid');
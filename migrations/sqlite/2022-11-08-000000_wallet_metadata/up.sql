@@ -0,0 +1,14 @@
+ALTER TABLE 'wallet' ADD COLUMN 'name' MEDIUMTEXT DEFAULT NULL;
+ALTER TABLE 'wallet' ADD COLUMN 'description' MEDIUMTEXT DEFAULT NULL;
+ALTER TABLE 'cosigner' ADD COLUMN 'name' MEDIUMTEXT DEFAULT NULL;
+
+CREATE TABLE 'wallet_tag' (
+  'id' INTEGER NOT NULL  DEFAULT NULL PRIMARY KEY AUTOINCREMENT,
+  'wallet_uuid' MEDIUMTEXT NOT NULL  DEFAULT 'NULL',
+  'tag' MEDIUMTEXT NOT NULL  DEFAULT 'NULL',
+  UNIQUE ('wallet_uuid', 'tag')
+);
+
+CREATE INDEX 'wallet_name_idx' ON 'wallet' ('name');
+CREATE INDEX 'cosigner_name_idx' ON 'cosigner' ('name');
+CREATE INDEX 'wallet_tag_tag_idx' ON 'wallet_tag' ('tag');
@@ -0,0 +1,14 @@
+CREATE TABLE 'wallet_label' (
+  'id' INTEGER NOT NULL  DEFAULT NULL PRIMARY KEY AUTOINCREMENT,
+  'wallet_uuid' MEDIUMTEXT NOT NULL  DEFAULT 'NULL',
+  'type' SMALLINT NOT NULL  DEFAULT NULL,
+  'ref' MEDIUMTEXT NOT NULL  DEFAULT 'NULL',
+  'label' MEDIUMTEXT NOT NULL  DEFAULT 'NULL',
+  'origin' MEDIUMTEXT DEFAULT NULL,
+  'spendable' BOOLEAN DEFAULT NULL,
+  'creation_time' DATETIME NOT NULL  DEFAULT 'NULL',
+  UNIQUE ('wallet_uuid', 'type', 'ref')
+);
+
+CREATE INDEX 'wallet_label_wallet_uuid_idx' ON 'wallet_label' ('wallet_uuid');
+CREATE INDEX 'wallet_label_ref_idx' ON 'wallet_label' ('ref');
@@ -0,0 +1,11 @@
+CREATE TABLE 'wallet_address' (
+  'id' INTEGER NOT NULL  DEFAULT NULL PRIMARY KEY AUTOINCREMENT,
+  'wallet_uuid' MEDIUMTEXT NOT NULL  DEFAULT 'NULL',
+  'address_index' BIGINT NOT NULL  DEFAULT NULL,
+  'address' MEDIUMTEXT NOT NULL  DEFAULT 'NULL',
+  'used' BOOLEAN NOT NULL  DEFAULT 0,
+  'creation_time' DATETIME NOT NULL  DEFAULT 'NULL',
+  UNIQUE ('wallet_uuid', 'address_index')
+);
+
+CREATE INDEX 'wallet_address_address_idx' ON 'wallet_address' ('address');
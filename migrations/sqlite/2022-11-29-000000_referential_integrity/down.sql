@@ -0,0 +1,5 @@
+-- Foreign keys stay in place; only the cosigner links are dropped. The
+-- orphaned_* tables are kept as well, as their rows can't go back without the
+-- wallets they belonged to.
+DROP INDEX 'wallet_cosigner_cosigner_uuid_idx';
+DROP TABLE 'wallet_cosigner';
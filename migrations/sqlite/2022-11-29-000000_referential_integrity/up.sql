@@ -0,0 +1,198 @@
+-- SQLite can't add foreign keys to existing tables, so every table pointing
+-- at a wallet, session or cosigner is rebuilt. Rows left behind by forgotten
+-- wallets can't satisfy the new references; as they may hold internal keys,
+-- they are moved into orphaned_* tables for the operator to review instead of
+-- being dropped. The literal 'NULL' email addresses of internal cosigners
+-- become real NULLs on the way.
+
+CREATE TABLE IF NOT EXISTS 'orphaned_cosigner' AS SELECT * FROM 'cosigner' WHERE 0;
+INSERT INTO 'orphaned_cosigner'
+  SELECT * FROM 'cosigner'
+  WHERE wallet_uuid IS NOT NULL AND wallet_uuid NOT IN (SELECT uuid FROM 'wallet');
+
+CREATE TABLE IF NOT EXISTS 'orphaned_psbt' AS SELECT * FROM 'psbt' WHERE 0;
+INSERT INTO 'orphaned_psbt'
+  SELECT * FROM 'psbt' WHERE wallet_uuid NOT IN (SELECT uuid FROM 'wallet');
+
+CREATE TABLE IF NOT EXISTS 'orphaned_wallet_tag' AS SELECT * FROM 'wallet_tag' WHERE 0;
+INSERT INTO 'orphaned_wallet_tag'
+  SELECT * FROM 'wallet_tag' WHERE wallet_uuid NOT IN (SELECT uuid FROM 'wallet');
+
+CREATE TABLE IF NOT EXISTS 'orphaned_wallet_label' AS SELECT * FROM 'wallet_label' WHERE 0;
+INSERT INTO 'orphaned_wallet_label'
+  SELECT * FROM 'wallet_label' WHERE wallet_uuid NOT IN (SELECT uuid FROM 'wallet');
+
+CREATE TABLE IF NOT EXISTS 'orphaned_wallet_address' AS SELECT * FROM 'wallet_address' WHERE 0;
+INSERT INTO 'orphaned_wallet_address'
+  SELECT * FROM 'wallet_address' WHERE wallet_uuid NOT IN (SELECT uuid FROM 'wallet');
+
+CREATE TABLE IF NOT EXISTS 'orphaned_bsms_key_record' AS SELECT * FROM 'bsms_key_record' WHERE 0;
+INSERT INTO 'orphaned_bsms_key_record'
+  SELECT * FROM 'bsms_key_record'
+  WHERE session_uuid NOT IN (SELECT uuid FROM 'bsms_session')
+    OR cosigner_uuid NOT IN (
+      SELECT uuid FROM 'cosigner'
+      WHERE wallet_uuid IS NULL OR wallet_uuid IN (SELECT uuid FROM 'wallet')
+    );
+
+CREATE TABLE 'cosigner_new' (
+  'id' INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  'uuid' MEDIUMTEXT NOT NULL UNIQUE,
+  'type' SMALLINT NOT NULL,
+  'email_address' VARCHAR(50) DEFAULT NULL,
+  'xpub' MEDIUMTEXT(120) NOT NULL,
+  'xprv' MEDIUMTEXT DEFAULT NULL,
+  'creation_time' DATETIME NOT NULL,
+  'wallet_uuid' MEDIUMTEXT DEFAULT NULL UNIQUE REFERENCES 'wallet' ('uuid') ON DELETE CASCADE,
+  'name' MEDIUMTEXT DEFAULT NULL
+);
+
+INSERT INTO 'cosigner_new'
+  SELECT id, uuid, type, NULLIF(email_address, 'NULL'), xpub, xprv, creation_time, wallet_uuid, name
+  FROM 'cosigner'
+  WHERE wallet_uuid IS NULL OR wallet_uuid IN (SELECT uuid FROM 'wallet');
+
+DROP TABLE 'cosigner';
+ALTER TABLE 'cosigner_new' RENAME TO 'cosigner';
+
+CREATE INDEX 'cosigner_uuid_idx' ON 'cosigner' ('uuid');
+CREATE INDEX 'cosigner_wallet_uuid_idx' ON 'cosigner' ('wallet_uuid');
+CREATE INDEX 'email_address_idx' ON 'cosigner' ('email_address');
+CREATE INDEX 'xpub_idx' ON 'cosigner' ('xpub');
+CREATE INDEX 'cosigner_name_idx' ON 'cosigner' ('name');
+
+CREATE TABLE 'psbt_new' (
+  'id' INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  'uuid' MEDIUMTEXT NOT NULL,
+  'base64' MEDIUMTEXT NOT NULL,
+  'creation_time' DATETIME NOT NULL,
+  'wallet_uuid' MEDIUMTEXT NOT NULL UNIQUE REFERENCES 'wallet' ('uuid') ON DELETE CASCADE
+);
+
+INSERT INTO 'psbt_new'
+  SELECT id, uuid, base64, creation_time, wallet_uuid
+  FROM 'psbt'
+  WHERE wallet_uuid IN (SELECT uuid FROM 'wallet');
+
+DROP TABLE 'psbt';
+ALTER TABLE 'psbt_new' RENAME TO 'psbt';
+
+CREATE INDEX 'psbt_wallet_uuid_idx' ON 'psbt' ('wallet_uuid');
+CREATE INDEX 'psbt_uuid_idx' ON 'psbt' ('uuid');
+
+CREATE TABLE 'wallet_tag_new' (
+  'id' INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  'wallet_uuid' MEDIUMTEXT NOT NULL REFERENCES 'wallet' ('uuid') ON DELETE CASCADE,
+  'tag' MEDIUMTEXT NOT NULL,
+  UNIQUE ('wallet_uuid', 'tag')
+);
+
+INSERT INTO 'wallet_tag_new'
+  SELECT id, wallet_uuid, tag
+  FROM 'wallet_tag'
+  WHERE wallet_uuid IN (SELECT uuid FROM 'wallet');
+
+DROP TABLE 'wallet_tag';
+ALTER TABLE 'wallet_tag_new' RENAME TO 'wallet_tag';
+
+CREATE INDEX 'wallet_tag_tag_idx' ON 'wallet_tag' ('tag');
+
+CREATE TABLE 'wallet_label_new' (
+  'id' INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  'wallet_uuid' MEDIUMTEXT NOT NULL REFERENCES 'wallet' ('uuid') ON DELETE CASCADE,
+  'type' SMALLINT NOT NULL,
+  'ref' MEDIUMTEXT NOT NULL,
+  'label' MEDIUMTEXT NOT NULL,
+  'origin' MEDIUMTEXT DEFAULT NULL,
+  'spendable' BOOLEAN DEFAULT NULL,
+  'creation_time' DATETIME NOT NULL,
+  UNIQUE ('wallet_uuid', 'type', 'ref')
+);
+
+INSERT INTO 'wallet_label_new'
+  SELECT id, wallet_uuid, type, ref, label, origin, spendable, creation_time
+  FROM 'wallet_label'
+  WHERE wallet_uuid IN (SELECT uuid FROM 'wallet');
+
+DROP TABLE 'wallet_label';
+ALTER TABLE 'wallet_label_new' RENAME TO 'wallet_label';
+
+CREATE INDEX 'wallet_label_wallet_uuid_idx' ON 'wallet_label' ('wallet_uuid');
+CREATE INDEX 'wallet_label_ref_idx' ON 'wallet_label' ('ref');
+
+CREATE TABLE 'wallet_address_new' (
+  'id' INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  'wallet_uuid' MEDIUMTEXT NOT NULL REFERENCES 'wallet' ('uuid') ON DELETE CASCADE,
+  'address_index' BIGINT NOT NULL,
+  'address' MEDIUMTEXT NOT NULL,
+  'used' BOOLEAN NOT NULL DEFAULT 0,
+  'creation_time' DATETIME NOT NULL,
+  UNIQUE ('wallet_uuid', 'address_index')
+);
+
+INSERT INTO 'wallet_address_new'
+  SELECT id, wallet_uuid, address_index, address, used, creation_time
+  FROM 'wallet_address'
+  WHERE wallet_uuid IN (SELECT uuid FROM 'wallet');
+
+DROP TABLE 'wallet_address';
+ALTER TABLE 'wallet_address_new' RENAME TO 'wallet_address';
+
+CREATE INDEX 'wallet_address_address_idx' ON 'wallet_address' ('address');
+
+CREATE TABLE 'bsms_session_new' (
+  'id' INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  'uuid' MEDIUMTEXT NOT NULL UNIQUE,
+  'token' MEDIUMTEXT NOT NULL,
+  'address_type' SMALLINT NOT NULL,
+  'network' SMALLINT NOT NULL,
+  'required_signatures' SMALLINT NOT NULL,
+  'signers' SMALLINT NOT NULL,
+  'wallet_uuid' MEDIUMTEXT DEFAULT NULL REFERENCES 'wallet' ('uuid') ON DELETE SET NULL,
+  'creation_time' DATETIME NOT NULL
+);
+
+INSERT INTO 'bsms_session_new'
+  SELECT id, uuid, token, address_type, network, required_signatures, signers,
+    CASE WHEN wallet_uuid IN (SELECT uuid FROM 'wallet') THEN wallet_uuid END,
+    creation_time
+  FROM 'bsms_session';
+
+CREATE TABLE 'bsms_key_record_new' (
+  'id' INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  'session_uuid' MEDIUMTEXT NOT NULL REFERENCES 'bsms_session_new' ('uuid') ON DELETE CASCADE,
+  'cosigner_uuid' MEDIUMTEXT NOT NULL REFERENCES 'cosigner' ('uuid') ON DELETE CASCADE,
+  'record' MEDIUMTEXT NOT NULL,
+  'creation_time' DATETIME NOT NULL
+);
+
+INSERT INTO 'bsms_key_record_new'
+  SELECT id, session_uuid, cosigner_uuid, record, creation_time
+  FROM 'bsms_key_record'
+  WHERE session_uuid IN (SELECT uuid FROM 'bsms_session_new')
+    AND cosigner_uuid IN (SELECT uuid FROM 'cosigner');
+
+DROP TABLE 'bsms_key_record';
+DROP TABLE 'bsms_session';
+-- Renaming also updates the key records' reference to the new session table.
+ALTER TABLE 'bsms_session_new' RENAME TO 'bsms_session';
+ALTER TABLE 'bsms_key_record_new' RENAME TO 'bsms_key_record';
+
+CREATE INDEX 'bsms_session_uuid_idx' ON 'bsms_session' ('uuid');
+CREATE INDEX 'bsms_key_record_session_uuid_idx' ON 'bsms_key_record' ('session_uuid');
+
+-- External cosigners each wallet was created with. Existing wallets are
+-- linked to the cosigners whose xpub appears in their descriptor.
+CREATE TABLE 'wallet_cosigner' (
+  'id' INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  'wallet_uuid' MEDIUMTEXT NOT NULL REFERENCES 'wallet' ('uuid') ON DELETE CASCADE,
+  'cosigner_uuid' MEDIUMTEXT NOT NULL REFERENCES 'cosigner' ('uuid') ON DELETE RESTRICT,
+  UNIQUE ('wallet_uuid', 'cosigner_uuid')
+);
+
+INSERT INTO 'wallet_cosigner' ('wallet_uuid', 'cosigner_uuid')
+  SELECT w.uuid, c.uuid
+  FROM 'wallet' w JOIN 'cosigner' c ON instr(w.receive_descriptor_watch_only, c.xpub) > 0
+  WHERE c.wallet_uuid IS NULL;
+
+CREATE INDEX 'wallet_cosigner_cosigner_uuid_idx' ON 'wallet_cosigner' ('cosigner_uuid');
@@ -0,0 +1,11 @@
+ALTER TABLE 'psbt' DROP COLUMN 'archived_by';
+ALTER TABLE 'psbt' DROP COLUMN 'archived_at';
+
+ALTER TABLE 'wallet_label' DROP COLUMN 'archived_by';
+ALTER TABLE 'wallet_label' DROP COLUMN 'archived_at';
+
+ALTER TABLE 'cosigner' DROP COLUMN 'archived_by';
+ALTER TABLE 'cosigner' DROP COLUMN 'archived_at';
+
+ALTER TABLE 'wallet' DROP COLUMN 'archived_by';
+ALTER TABLE 'wallet' DROP COLUMN 'archived_at';
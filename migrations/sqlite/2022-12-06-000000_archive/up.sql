@@ -0,0 +1,30 @@
+ALTER TABLE 'wallet' ADD COLUMN 'archived_at' DATETIME DEFAULT NULL;
+ALTER TABLE 'wallet' ADD COLUMN 'archived_by' MEDIUMTEXT DEFAULT NULL;
+
+ALTER TABLE 'cosigner' ADD COLUMN 'archived_at' DATETIME DEFAULT NULL;
+ALTER TABLE 'cosigner' ADD COLUMN 'archived_by' MEDIUMTEXT DEFAULT NULL;
+
+ALTER TABLE 'wallet_label' ADD COLUMN 'archived_at' DATETIME DEFAULT NULL;
+ALTER TABLE 'wallet_label' ADD COLUMN 'archived_by' MEDIUMTEXT DEFAULT NULL;
+
+-- Archived PSBTs stay around, so a wallet has any number of them and they
+-- are told apart by their uuid.
+CREATE TABLE 'psbt_new' (
+  'id' INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  'uuid' MEDIUMTEXT NOT NULL UNIQUE,
+  'base64' MEDIUMTEXT NOT NULL,
+  'creation_time' DATETIME NOT NULL,
+  'wallet_uuid' MEDIUMTEXT NOT NULL REFERENCES 'wallet' ('uuid') ON DELETE CASCADE,
+  'archived_at' DATETIME DEFAULT NULL,
+  'archived_by' MEDIUMTEXT DEFAULT NULL
+);
+
+INSERT INTO 'psbt_new'
+  SELECT id, uuid, base64, creation_time, wallet_uuid, NULL, NULL
+  FROM 'psbt';
+
+DROP TABLE 'psbt';
+ALTER TABLE 'psbt_new' RENAME TO 'psbt';
+
+CREATE INDEX 'psbt_wallet_uuid_idx' ON 'psbt' ('wallet_uuid');
+CREATE INDEX 'psbt_uuid_idx' ON 'psbt' ('uuid');
@@ -0,0 +1,3 @@
+DROP INDEX 'broadcast_status_idx';
+DROP INDEX 'broadcast_wallet_uuid_idx';
+DROP TABLE 'broadcast';
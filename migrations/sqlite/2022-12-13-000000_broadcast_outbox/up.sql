@@ -0,0 +1,16 @@
+CREATE TABLE 'broadcast' (
+  'id' INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  'txid' MEDIUMTEXT NOT NULL UNIQUE,
+  'wallet_uuid' MEDIUMTEXT NOT NULL REFERENCES 'wallet' ('uuid') ON DELETE CASCADE,
+  -- The PSBT is deleted once its transaction was broadcast.
+  'psbt_uuid' MEDIUMTEXT NOT NULL,
+  'raw_transaction' MEDIUMTEXT NOT NULL,
+  'status' SMALLINT NOT NULL,
+  'attempts' INTEGER NOT NULL DEFAULT 0,
+  'last_error' MEDIUMTEXT DEFAULT NULL,
+  'creation_time' DATETIME NOT NULL,
+  'broadcast_time' DATETIME DEFAULT NULL
+);
+
+CREATE INDEX 'broadcast_wallet_uuid_idx' ON 'broadcast' ('wallet_uuid');
+CREATE INDEX 'broadcast_status_idx' ON 'broadcast' ('status');
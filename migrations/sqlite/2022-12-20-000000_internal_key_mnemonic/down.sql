@@ -0,0 +1,6 @@
+DROP INDEX 'key_export_cosigner_uuid_idx';
+DROP TABLE 'key_export';
+
+ALTER TABLE 'cosigner' DROP COLUMN 'mnemonic_exported_at';
+ALTER TABLE 'cosigner' DROP COLUMN 'mnemonic_has_passphrase';
+ALTER TABLE 'cosigner' DROP COLUMN 'mnemonic';
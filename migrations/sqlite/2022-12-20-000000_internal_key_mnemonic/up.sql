@@ -0,0 +1,16 @@
+-- The mnemonic of an internal key, encrypted, until it has been exported.
+ALTER TABLE 'cosigner' ADD COLUMN 'mnemonic' MEDIUMTEXT DEFAULT NULL;
+ALTER TABLE 'cosigner' ADD COLUMN 'mnemonic_has_passphrase' BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE 'cosigner' ADD COLUMN 'mnemonic_exported_at' DATETIME DEFAULT NULL;
+
+-- An audit trail of key exports, kept after the cosigner is purged, hence
+-- without a reference to it. Backups, which take all keys, have no cosigner.
+CREATE TABLE 'key_export' (
+  'id' INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  'cosigner_uuid' MEDIUMTEXT DEFAULT NULL,
+  'actor' MEDIUMTEXT NOT NULL,
+  'outcome' MEDIUMTEXT NOT NULL,
+  'creation_time' DATETIME NOT NULL
+);
+
+CREATE INDEX 'key_export_cosigner_uuid_idx' ON 'key_export' ('cosigner_uuid');
@@ -0,0 +1,4 @@
+-- The wallet that took over from this one when its internal key was rotated.
+ALTER TABLE 'wallet' ADD COLUMN 'successor_uuid' MEDIUMTEXT DEFAULT NULL REFERENCES 'wallet' ('uuid') ON DELETE SET NULL;
+
+CREATE INDEX 'wallet_successor_uuid_idx' ON 'wallet' ('successor_uuid');
@@ -0,0 +1,3 @@
+-- Master fingerprint and derivation path of the key, as `d34db33f/48'/0'/0'/2'`,
+-- which hardware signers need to recognise it.
+ALTER TABLE 'cosigner' ADD COLUMN 'origin' MEDIUMTEXT DEFAULT NULL;
@@ -0,0 +1,2 @@
+-- The previous tokens are gone; open sessions keep the token 00.
+SELECT 1;
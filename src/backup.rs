@@ -0,0 +1,232 @@
+use std::{collections::HashSet, error::Error, str::FromStr};
+
+use bdk::{
+    bitcoin::{psbt::PartiallySignedTransaction, secp256k1::Secp256k1, util::bip32},
+    descriptor::{Descriptor, DescriptorPublicKey},
+};
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crypto::{self, Kdf},
+    db,
+};
+
+const FORMAT: &str = "ohm-backup";
+const VERSION: u32 = 1;
+
+/// What ends up on disk: a versioned header in the clear, and the backup
+/// itself encrypted with a key derived from the passphrase. The header is
+/// authenticated along with the contents.
+#[derive(Deserialize, Serialize)]
+struct Archive {
+    format: String,
+    version: u32,
+    kdf: Kdf,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl Archive {
+    fn associated_data(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(serde_json::to_vec(&(
+            &self.format,
+            self.version,
+            &self.kdf,
+        ))?)
+    }
+}
+
+/// A copy of all cosigners, wallets, PSBTs and labels, internal keys
+/// included, that can recreate them in an empty database.
+#[derive(Deserialize, Serialize)]
+pub struct Backup {
+    creation_time: NaiveDateTime,
+    snapshot: db::Snapshot,
+}
+
+impl Backup {
+    /// Takes a copy of the database, recording it on behalf of `actor` like
+    /// an export of every key.
+    pub fn create(connection: &mut db::DbConnection, actor: &str) -> Result<Self, Box<dyn Error>> {
+        let backup = Self {
+            creation_time: Utc::now().naive_local(),
+            snapshot: db::Snapshot::take(connection)?,
+        };
+        Self::record(connection, actor, "backed up")?;
+
+        Ok(backup)
+    }
+
+    /// Records an attempt to take a backup, e.g. one that was refused before
+    /// reaching `create`.
+    pub fn record(
+        connection: &mut db::DbConnection,
+        actor: &str,
+        outcome: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        db::KeyExport::new(None, actor, outcome).insert(connection)?;
+
+        Ok(())
+    }
+
+    pub fn encrypt(&self, passphrase: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        if passphrase.is_empty() {
+            return Err("a passphrase is required".into());
+        }
+
+        let mut archive = Archive {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            kdf: Kdf::generate(),
+            nonce: String::new(),
+            ciphertext: String::new(),
+        };
+        (archive.nonce, archive.ciphertext) = crypto::encrypt(
+            &archive.kdf.cipher(passphrase)?,
+            &serde_json::to_vec(self)?,
+            &archive.associated_data()?,
+        )?;
+
+        Ok(serde_json::to_vec_pretty(&archive)?)
+    }
+
+    /// Decrypts an archive and checks that its contents are complete and
+    /// consistent before anything is restored from it.
+    pub fn decrypt(archive: &[u8], passphrase: &str) -> Result<Self, Box<dyn Error>> {
+        let archive: Archive =
+            serde_json::from_slice(archive).map_err(|_| "not an ohm backup archive")?;
+        if archive.format != FORMAT {
+            return Err("not an ohm backup archive".into());
+        }
+        if archive.version > VERSION {
+            return Err(format!(
+                "backup format version {} is newer than this server supports",
+                archive.version
+            )
+            .into());
+        }
+
+        let plaintext = crypto::decrypt(
+            &archive.kdf.cipher(passphrase)?,
+            &archive.nonce,
+            &archive.ciphertext,
+            &archive.associated_data()?,
+        )
+        .map_err(|_| "wrong passphrase or corrupted backup")?;
+
+        let backup: Self = serde_json::from_slice(&plaintext)?;
+        backup.validate()?;
+
+        Ok(backup)
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let snapshot = &self.snapshot;
+        let secp = Secp256k1::new();
+
+        let mut wallets = HashSet::new();
+        for wallet in &snapshot.wallets {
+            if !wallets.insert(wallet.uuid.as_str()) {
+                return Err(format!("wallet {} appears twice", wallet.uuid).into());
+            }
+
+            for descriptor in [
+                wallet.receive_descriptor.expose(),
+                &wallet.receive_descriptor_watch_only,
+                wallet.change_descriptor.expose(),
+                &wallet.change_descriptor_watch_only,
+            ] {
+                Descriptor::<DescriptorPublicKey>::parse_descriptor(&secp, descriptor)
+                    .map_err(|err| format!("wallet {} is invalid: {}", wallet.uuid, err))?;
+            }
+        }
+
+        for wallet in &snapshot.wallets {
+            if let Some(successor_uuid) = &wallet.successor_uuid {
+                if !wallets.contains(successor_uuid.as_str()) {
+                    return Err(format!("wallet {} has a missing successor", wallet.uuid).into());
+                }
+            }
+        }
+
+        let mut cosigners = HashSet::new();
+        let mut internal_cosigners = HashSet::new();
+        for cosigner in &snapshot.cosigners {
+            if !cosigners.insert(cosigner.uuid.as_str()) {
+                return Err(format!("cosigner {} appears twice", cosigner.uuid).into());
+            }
+
+            bip32::ExtendedPubKey::from_str(&cosigner.xpub)
+                .map_err(|err| format!("cosigner {} is invalid: {}", cosigner.uuid, err))?;
+            if let Some(xprv) = cosigner.xprv.expose() {
+                bip32::ExtendedPrivKey::from_str(xprv)
+                    .map_err(|err| format!("cosigner {} is invalid: {}", cosigner.uuid, err))?;
+            }
+
+            if let Some(wallet_uuid) = &cosigner.wallet_uuid {
+                if !wallets.contains(wallet_uuid.as_str()) {
+                    return Err(
+                        format!("cosigner {} belongs to a missing wallet", cosigner.uuid).into(),
+                    );
+                }
+                internal_cosigners.insert(wallet_uuid.as_str());
+            }
+        }
+
+        if let Some(wallet) = wallets.difference(&internal_cosigners).next() {
+            return Err(format!("wallet {} is missing its internal cosigner", wallet).into());
+        }
+
+        for link in &snapshot.wallet_cosigners {
+            if !wallets.contains(link.wallet_uuid.as_str())
+                || !cosigners.contains(link.cosigner_uuid.as_str())
+            {
+                return Err(
+                    format!("wallet {} refers to a missing cosigner", link.wallet_uuid).into(),
+                );
+            }
+        }
+
+        for psbt in &snapshot.psbts {
+            PartiallySignedTransaction::from_str(&psbt.base64)
+                .map_err(|err| format!("PSBT {} is invalid: {}", psbt.uuid, err))?;
+        }
+
+        let wallet_uuids = snapshot
+            .wallet_tags
+            .iter()
+            .map(|tag| &tag.wallet_uuid)
+            .chain(
+                snapshot
+                    .wallet_addresses
+                    .iter()
+                    .map(|address| &address.wallet_uuid),
+            )
+            .chain(snapshot.psbts.iter().map(|psbt| &psbt.wallet_uuid))
+            .chain(snapshot.labels.iter().map(|label| &label.wallet_uuid));
+        for wallet_uuid in wallet_uuids {
+            if !wallets.contains(wallet_uuid.as_str()) {
+                return Err(format!("backup refers to a missing wallet {}", wallet_uuid).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn restore(&self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
+        self.snapshot.restore(connection)
+    }
+
+    pub fn creation_time(&self) -> &NaiveDateTime {
+        &self.creation_time
+    }
+
+    pub fn wallet_count(&self) -> usize {
+        self.snapshot.wallets.len()
+    }
+
+    pub fn cosigner_count(&self) -> usize {
+        self.snapshot.cosigners.len()
+    }
+}
@@ -1,6 +1,18 @@
-use std::{error::Error, fmt::Debug};
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
 
-use bdk::bitcoin::{util::bip32::ExtendedPubKey, Address, Network};
+use bdk::bitcoin::{
+    psbt::PartiallySignedTransaction,
+    util::bip32::{ExtendedPubKey, Fingerprint},
+    Address, Network,
+};
 use email_address::EmailAddress;
 use structopt::{clap::AppSettings, StructOpt};
 use tonic::Request;
@@ -13,7 +25,10 @@ use ohm::{proto, Client, Response};
 enum CosignerOptions {
     Register {
         email_address: EmailAddress,
-        xpub: ExtendedPubKey,
+        /// Optionally preceded by its origin, e.g. [d34db33f/48'/0'/0'/2']xpub...
+        xpub: String,
+        #[structopt(long)]
+        name: Option<String>,
     },
     Info {
         cosigner_id: Uuid,
@@ -21,9 +36,28 @@ enum CosignerOptions {
     Find {
         email_address: Option<EmailAddress>,
         xpub: Option<ExtendedPubKey>,
+        #[structopt(long)]
+        name: Option<String>,
+        #[structopt(long)]
+        include_archived: bool,
+    },
+    Update {
+        cosigner_id: Uuid,
+        #[structopt(long)]
+        name: Option<String>,
+        /// Master fingerprint and derivation path of the key, e.g. d34db33f/48'/0'/0'/2'
+        #[structopt(long)]
+        origin: Option<String>,
     },
     Forget {
         cosigner_id: Uuid,
+        #[structopt(long)]
+        force: bool,
+        #[structopt(long, env = "OHM_ACTOR")]
+        actor: Option<String>,
+    },
+    Restore {
+        cosigner_id: Uuid,
     },
 }
 
@@ -35,6 +69,21 @@ enum WalletOptions {
         required_sigs: u64,
         #[structopt(required = true)]
         cosigner_ids: Vec<Uuid>,
+        #[structopt(long)]
+        name: Option<String>,
+        #[structopt(long)]
+        description: Option<String>,
+        #[structopt(long = "tag")]
+        tags: Vec<String>,
+        #[structopt(long, env = "OHM_MNEMONIC", hide_env_values = true)]
+        mnemonic: Option<String>,
+        #[structopt(
+            long,
+            env = "OHM_MNEMONIC_PASSPHRASE",
+            hide_env_values = true,
+            requires = "mnemonic"
+        )]
+        passphrase: Option<String>,
     },
     Info {
         wallet_id: Uuid,
@@ -43,9 +92,67 @@ enum WalletOptions {
         address_type: Option<String>, // TODO use AddressType
         network: Option<Network>,
         descriptor: Option<String>,
+        #[structopt(long)]
+        name: Option<String>,
+        #[structopt(long)]
+        tag: Option<String>,
+        #[structopt(long)]
+        include_archived: bool,
+    },
+    Update {
+        wallet_id: Uuid,
+        #[structopt(long)]
+        name: Option<String>,
+        #[structopt(long)]
+        description: Option<String>,
+        #[structopt(long = "tag")]
+        tags: Vec<String>,
+        #[structopt(long, conflicts_with = "tags")]
+        clear_tags: bool,
+    },
+    NewAddress {
+        wallet_id: Uuid,
+        #[structopt(long)]
+        label: Option<String>,
+    },
+    Addresses {
+        wallet_id: Uuid,
+        #[structopt(long, conflicts_with = "unused")]
+        used: bool,
+        #[structopt(long)]
+        unused: bool,
+    },
+    VerifyAddress {
+        wallet_id: Uuid,
+        address: Address,
     },
     Forget {
         wallet_id: Uuid,
+        #[structopt(long, env = "OHM_ACTOR")]
+        actor: Option<String>,
+    },
+    Restore {
+        wallet_id: Uuid,
+    },
+    Export {
+        wallet_id: Uuid,
+        #[structopt(short, long, default_value = "descriptor")]
+        format: proto::ExportFormat,
+    },
+    Rotate {
+        wallet_id: Uuid,
+        /// Replace an external cosigner, given as <old-cosigner-id>:<new-cosigner-id>
+        #[structopt(long = "swap", parse(try_from_str = parse_swap))]
+        swaps: Vec<proto::CosignerSwap>,
+        /// Fee rate of the sweep in sat/vB, estimated by the backend if omitted
+        #[structopt(long)]
+        fee_rate: Option<f32>,
+    },
+    Transactions {
+        wallet_id: Uuid,
+    },
+    Utxos {
+        wallet_id: Uuid,
     },
 }
 
@@ -55,6 +162,9 @@ enum PsbtOptions {
         wallet_id: Uuid,
         amount: String,
         recipient: Address,
+        /// Fee rate in sat/vB, estimated by the backend if omitted
+        #[structopt(long)]
+        fee_rate: Option<f32>,
     },
     Register {
         wallet_id: Uuid,
@@ -65,10 +175,26 @@ enum PsbtOptions {
     },
     Find {
         wallet_id: Uuid,
+        #[structopt(long)]
+        include_archived: bool,
     },
     Sign {
         psbt_id: Uuid,
     },
+    /// Signs with a hardware wallet through HWI, then combines the result
+    SignWithDevice {
+        psbt_id: Uuid,
+        /// Master fingerprint of the device to sign with, if several are
+        /// connected
+        #[structopt(long)]
+        fingerprint: Option<Fingerprint>,
+        /// Taken from the wallet's descriptor unless given
+        #[structopt(long)]
+        network: Option<Network>,
+        /// HWI, or a signer with the same command line interface
+        #[structopt(long, env = "OHM_HWI", default_value = "hwi")]
+        hwi: PathBuf,
+    },
     Combine {
         psbt_id: Uuid,
         psbt: String,
@@ -76,8 +202,107 @@ enum PsbtOptions {
     Broadcast {
         psbt_id: Uuid,
     },
+    Decode {
+        psbt_id: Uuid,
+    },
     Forget {
         psbt_id: Uuid,
+        #[structopt(long, env = "OHM_ACTOR")]
+        actor: Option<String>,
+    },
+    Restore {
+        psbt_id: Uuid,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum BsmsOptions {
+    Create {
+        address_type: String, // TODO use AddressType
+        network: Network,
+        required_sigs: u64,
+        signers: u64,
+    },
+    Submit {
+        session_id: Uuid,
+        key_record: PathBuf,
+    },
+    Info {
+        session_id: Uuid,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum LabelOptions {
+    Set {
+        wallet_id: Uuid,
+        label_type: proto::LabelType,
+        reference: String,
+        label: String,
+        #[structopt(long)]
+        origin: Option<String>,
+        #[structopt(long)]
+        spendable: Option<bool>,
+    },
+    Find {
+        wallet_id: Uuid,
+        #[structopt(long = "type")]
+        label_type: Option<proto::LabelType>,
+        #[structopt(long)]
+        reference: Option<String>,
+        #[structopt(long)]
+        include_archived: bool,
+    },
+    Forget {
+        wallet_id: Uuid,
+        label_type: proto::LabelType,
+        reference: String,
+        #[structopt(long, env = "OHM_ACTOR")]
+        actor: Option<String>,
+    },
+    Restore {
+        wallet_id: Uuid,
+        label_type: proto::LabelType,
+        reference: String,
+    },
+    Import {
+        wallet_id: Uuid,
+        labels: PathBuf,
+    },
+    Export {
+        wallet_id: Uuid,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum AdminOptions {
+    Backup {
+        output: PathBuf,
+        #[structopt(long, env = "OHM_BACKUP_PASSPHRASE", hide_env_values = true)]
+        passphrase: String,
+        #[structopt(long, env = "OHM_ADMIN_TOKEN", hide_env_values = true)]
+        admin_token: String,
+        #[structopt(long, env = "OHM_ACTOR")]
+        actor: Option<String>,
+    },
+    ExportKey {
+        wallet_id: Uuid,
+        #[structopt(long, env = "OHM_ADMIN_TOKEN", hide_env_values = true)]
+        admin_token: String,
+        #[structopt(long, env = "OHM_ACTOR")]
+        actor: Option<String>,
+    },
+    /// Splits the internal key into SLIP-39 shares, which only ohm can recombine
+    SplitKey {
+        wallet_id: Uuid,
+        threshold: u32,
+        share_count: u32,
+        #[structopt(long, env = "OHM_ADMIN_TOKEN", hide_env_values = true)]
+        admin_token: String,
+        #[structopt(long, env = "OHM_SHARE_PASSPHRASE", hide_env_values = true)]
+        passphrase: Option<String>,
+        #[structopt(long, env = "OHM_ACTOR")]
+        actor: Option<String>,
     },
 }
 
@@ -91,6 +316,15 @@ enum Command {
 
     #[structopt(display_order = 2)]
     Psbt(PsbtOptions),
+
+    #[structopt(display_order = 3)]
+    Bsms(BsmsOptions),
+
+    #[structopt(display_order = 4)]
+    Label(LabelOptions),
+
+    #[structopt(display_order = 5)]
+    Admin(AdminOptions),
 }
 
 #[derive(StructOpt, Debug)]
@@ -103,6 +337,112 @@ struct Options {
     command: Command,
 }
 
+fn parse_swap(swap: &str) -> Result<proto::CosignerSwap, Box<dyn Error>> {
+    let (old, new) = swap
+        .split_once(':')
+        .ok_or("expected <old-cosigner-id>:<new-cosigner-id>")?;
+
+    Ok(proto::CosignerSwap {
+        old_cosigner_id: Uuid::from_str(old)?.to_string(),
+        new_cosigner_id: Uuid::from_str(new)?.to_string(),
+    })
+}
+
+/// Runs an HWI command, returning its JSON output.
+fn run_hwi(hwi: &Path, args: &[&str]) -> Result<serde_json::Value, Box<dyn Error>> {
+    let output = process::Command::new(hwi)
+        .args(args)
+        .output()
+        .map_err(|err| format!("failed to run {}: {}", hwi.display(), err))?;
+
+    // HWI reports errors as JSON, with or without a non-zero exit status.
+    let value: Option<serde_json::Value> = serde_json::from_slice(&output.stdout).ok();
+    if let Some(error) = value.as_ref().and_then(|value| value.get("error")) {
+        return Err(format!("{} failed: {}", hwi.display(), error).into());
+    }
+
+    match value {
+        Some(value) if output.status.success() => Ok(value),
+        _ => Err(format!(
+            "{} failed ({}): {}",
+            hwi.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into()),
+    }
+}
+
+/// Has a hardware wallet sign the PSBT, picking the connected device holding
+/// one of its keys unless `fingerprint` says which.
+fn sign_with_device(
+    hwi: &Path,
+    base64: &str,
+    fingerprint: Option<Fingerprint>,
+    network: Network,
+) -> Result<String, Box<dyn Error>> {
+    let fingerprint = match fingerprint {
+        Some(fingerprint) => fingerprint,
+        None => {
+            let psbt = PartiallySignedTransaction::from_str(base64)?;
+            let keys: HashSet<Fingerprint> = psbt
+                .inputs
+                .iter()
+                .flat_map(|input| input.bip32_derivation.values())
+                .map(|(fingerprint, _)| *fingerprint)
+                .collect();
+
+            let devices = run_hwi(hwi, &["enumerate"])?;
+            let matching: Vec<Fingerprint> = devices
+                .as_array()
+                .ok_or("unexpected output of hwi enumerate")?
+                .iter()
+                .filter_map(|device| device.get("fingerprint")?.as_str())
+                .filter_map(|fingerprint| Fingerprint::from_str(fingerprint).ok())
+                .filter(|fingerprint| keys.contains(fingerprint))
+                .collect();
+
+            match matching[..] {
+                [fingerprint] => fingerprint,
+                [] => {
+                    return Err("no connected and unlocked device holds a key of this PSBT".into())
+                }
+                _ => return Err(
+                    "several connected devices hold keys of this PSBT, pick one with --fingerprint"
+                        .into(),
+                ),
+            }
+        }
+    };
+
+    let chain = match network {
+        Network::Bitcoin => "main",
+        Network::Testnet => "test",
+        Network::Regtest => "regtest",
+        Network::Signet => "signet",
+    };
+    let signed = run_hwi(
+        hwi,
+        &[
+            "--fingerprint",
+            &fingerprint.to_string(),
+            "--chain",
+            chain,
+            "signtx",
+            base64,
+        ],
+    )?;
+    if signed.get("signed") == Some(&serde_json::Value::Bool(false)) {
+        return Err(format!("device {} did not sign the PSBT", fingerprint).into());
+    }
+
+    Ok(signed
+        .get("psbt")
+        .and_then(|psbt| psbt.as_str())
+        .ok_or("unexpected output of hwi signtx")?
+        .to_string())
+}
+
 async fn handle_cosigner_requests(
     client: &mut Client,
     options: &CosignerOptions,
@@ -111,10 +451,12 @@ async fn handle_cosigner_requests(
         CosignerOptions::Register {
             email_address,
             xpub,
+            name,
         } => {
             let request = Request::new(proto::RegisterCosignerRequest {
                 email_address: email_address.to_string(),
-                xpub: xpub.to_string(),
+                xpub: xpub.clone(),
+                name: name.clone(),
             });
             Ok(Response::RegisterCosigner(
                 client.register_cosigner(request).await?,
@@ -131,22 +473,56 @@ async fn handle_cosigner_requests(
         CosignerOptions::Find {
             email_address,
             xpub,
+            name,
+            include_archived,
         } => {
             let request = Request::new(proto::FindCosignerRequest {
                 email_address: email_address.as_ref().map(|email| email.to_string()),
                 xpub: xpub.map(|xpub| xpub.to_string()),
+                name: name.clone(),
+                include_archived: *include_archived,
             });
             Ok(Response::FindCosigner(client.find_cosigner(request).await?))
         }
 
-        CosignerOptions::Forget { cosigner_id } => {
+        CosignerOptions::Update {
+            cosigner_id,
+            name,
+            origin,
+        } => {
+            let request = Request::new(proto::UpdateCosignerRequest {
+                cosigner_id: cosigner_id.to_string(),
+                name: name.clone(),
+                origin: origin.clone(),
+            });
+            Ok(Response::UpdateCosigner(
+                client.update_cosigner(request).await?,
+            ))
+        }
+
+        CosignerOptions::Forget {
+            cosigner_id,
+            force,
+            actor,
+        } => {
             let request = Request::new(proto::ForgetCosignerRequest {
                 cosigner_id: cosigner_id.to_string(),
+                force: *force,
+                actor: actor.clone(),
             });
             Ok(Response::ForgetCosigner(
                 client.forget_cosigner(request).await?,
             ))
         }
+
+        CosignerOptions::Restore { cosigner_id } => {
+            let request = Request::new(proto::RestoreCosignerRequest {
+                cosigner_id: cosigner_id.to_string(),
+            });
+            Ok(Response::RestoreCosigner(
+                client.restore_cosigner(request).await?,
+            ))
+        }
     }
 }
 async fn handle_wallet_requests(
@@ -159,6 +535,11 @@ async fn handle_wallet_requests(
             network,
             required_sigs,
             cosigner_ids,
+            name,
+            description,
+            tags,
+            mnemonic,
+            passphrase,
         } => {
             let cosigners = cosigner_ids.iter().map(|uuid| uuid.to_string()).collect();
             let request = Request::new(proto::CreateWalletRequest {
@@ -166,6 +547,11 @@ async fn handle_wallet_requests(
                 network: proto::Network::from(*network).into(),
                 required_sigs: *required_sigs,
                 cosigner_ids: cosigners,
+                name: name.clone(),
+                description: description.clone(),
+                tags: tags.clone(),
+                mnemonic: mnemonic.clone(),
+                passphrase: passphrase.clone(),
             });
             Ok(Response::CreateWallet(client.create_wallet(request).await?))
         }
@@ -181,6 +567,9 @@ async fn handle_wallet_requests(
             address_type,
             network,
             descriptor,
+            name,
+            tag,
+            include_archived,
         } => {
             let request = Request::new(proto::FindWalletRequest {
                 address_type: address_type
@@ -188,16 +577,125 @@ async fn handle_wallet_requests(
                     .map(|address_type| proto::AddressType::from(address_type.as_str()).into()),
                 network: network.map(|network| proto::Network::from(network).into()),
                 descriptor: descriptor.clone(),
+                name: name.clone(),
+                tag: tag.clone(),
+                include_archived: *include_archived,
             });
             Ok(Response::FindWallet(client.find_wallet(request).await?))
         }
 
-        WalletOptions::Forget { wallet_id } => {
+        WalletOptions::Update {
+            wallet_id,
+            name,
+            description,
+            tags,
+            clear_tags,
+        } => {
+            let tags = match (*clear_tags, tags.is_empty()) {
+                (true, _) => Some(proto::WalletTags { tags: vec![] }),
+                (false, false) => Some(proto::WalletTags { tags: tags.clone() }),
+                (false, true) => None,
+            };
+            let request = Request::new(proto::UpdateWalletRequest {
+                wallet_id: wallet_id.to_string(),
+                name: name.clone(),
+                description: description.clone(),
+                tags,
+            });
+            Ok(Response::UpdateWallet(client.update_wallet(request).await?))
+        }
+
+        WalletOptions::NewAddress { wallet_id, label } => {
+            let request = Request::new(proto::GetNewReceiveAddressRequest {
+                wallet_id: wallet_id.to_string(),
+                label: label.clone(),
+            });
+            Ok(Response::GetNewReceiveAddress(
+                client.get_new_receive_address(request).await?,
+            ))
+        }
+
+        WalletOptions::Addresses {
+            wallet_id,
+            used,
+            unused,
+        } => {
+            let request = Request::new(proto::ListReceiveAddressesRequest {
+                wallet_id: wallet_id.to_string(),
+                used: match (used, unused) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                },
+            });
+            Ok(Response::ListReceiveAddresses(
+                client.list_receive_addresses(request).await?,
+            ))
+        }
+
+        WalletOptions::VerifyAddress { wallet_id, address } => {
+            let request = Request::new(proto::VerifyAddressRequest {
+                wallet_id: wallet_id.to_string(),
+                address: address.to_string(),
+            });
+            Ok(Response::VerifyAddress(
+                client.verify_address(request).await?,
+            ))
+        }
+
+        WalletOptions::Forget { wallet_id, actor } => {
             let request = Request::new(proto::ForgetWalletRequest {
                 wallet_id: wallet_id.to_string(),
+                actor: actor.clone(),
             });
             Ok(Response::ForgetWallet(client.forget_wallet(request).await?))
         }
+
+        WalletOptions::Restore { wallet_id } => {
+            let request = Request::new(proto::RestoreWalletRequest {
+                wallet_id: wallet_id.to_string(),
+            });
+            Ok(Response::RestoreWallet(
+                client.restore_wallet(request).await?,
+            ))
+        }
+
+        WalletOptions::Export { wallet_id, format } => {
+            let request = Request::new(proto::ExportWalletRequest {
+                wallet_id: wallet_id.to_string(),
+                format: (*format).into(),
+            });
+            Ok(Response::ExportWallet(client.export_wallet(request).await?))
+        }
+
+        WalletOptions::Rotate {
+            wallet_id,
+            swaps,
+            fee_rate,
+        } => {
+            let request = Request::new(proto::RotateWalletRequest {
+                wallet_id: wallet_id.to_string(),
+                swaps: swaps.clone(),
+                fee_rate: *fee_rate,
+            });
+            Ok(Response::RotateWallet(client.rotate_wallet(request).await?))
+        }
+
+        WalletOptions::Transactions { wallet_id } => {
+            let request = Request::new(proto::ListTransactionsRequest {
+                wallet_id: wallet_id.to_string(),
+            });
+            Ok(Response::ListTransactions(
+                client.list_transactions(request).await?,
+            ))
+        }
+
+        WalletOptions::Utxos { wallet_id } => {
+            let request = Request::new(proto::ListUtxosRequest {
+                wallet_id: wallet_id.to_string(),
+            });
+            Ok(Response::ListUtxos(client.list_utxos(request).await?))
+        }
     }
 }
 
@@ -210,11 +708,13 @@ async fn handle_psbt_requests(
             wallet_id,
             amount,
             recipient,
+            fee_rate,
         } => {
             let request = Request::new(proto::CreatePsbtRequest {
                 wallet_id: wallet_id.to_string(),
                 amount: amount.clone(),
                 recipient: recipient.to_string(),
+                fee_rate: *fee_rate,
             });
             Ok(Response::CreatePsbt(client.create_psbt(request).await?))
         }
@@ -234,9 +734,13 @@ async fn handle_psbt_requests(
             Ok(Response::GetPsbt(client.get_psbt(request).await?))
         }
 
-        PsbtOptions::Find { wallet_id } => {
+        PsbtOptions::Find {
+            wallet_id,
+            include_archived,
+        } => {
             let request = Request::new(proto::FindPsbtRequest {
                 wallet_id: wallet_id.to_string(),
+                include_archived: *include_archived,
             });
             Ok(Response::FindPsbt(client.find_psbt(request).await?))
         }
@@ -248,6 +752,53 @@ async fn handle_psbt_requests(
             Ok(Response::SignPsbt(client.sign_psbt(request).await?))
         }
 
+        PsbtOptions::SignWithDevice {
+            psbt_id,
+            fingerprint,
+            network,
+            hwi,
+        } => {
+            let request = Request::new(proto::GetPsbtRequest {
+                psbt_id: psbt_id.to_string(),
+            });
+            let psbt = client
+                .get_psbt(request)
+                .await?
+                .into_inner()
+                .psbt
+                .ok_or("PSBT could not be found")?;
+
+            let network = match network {
+                Some(network) => *network,
+                None => {
+                    let request = Request::new(proto::GetWalletRequest {
+                        wallet_id: psbt.wallet_id.clone(),
+                    });
+                    let wallet = client
+                        .get_wallet(request)
+                        .await?
+                        .into_inner()
+                        .wallet
+                        .ok_or("wallet could not be found")?;
+
+                    // Extended keys only tell mainnet from the test networks apart.
+                    match wallet.descriptor.contains("xpub") {
+                        true => Network::Bitcoin,
+                        false => Network::Testnet,
+                    }
+                }
+            };
+
+            let base64 = sign_with_device(hwi, &psbt.base64, *fingerprint, network)?;
+            let request = Request::new(proto::CombineWithOtherPsbtRequest {
+                psbt_id: psbt_id.to_string(),
+                base64,
+            });
+            Ok(Response::CombineWithOtherPsbt(
+                client.combine_with_other_psbt(request).await?,
+            ))
+        }
+
         PsbtOptions::Combine { psbt_id, psbt } => {
             let request = Request::new(proto::CombineWithOtherPsbtRequest {
                 psbt_id: psbt_id.to_string(),
@@ -267,12 +818,226 @@ async fn handle_psbt_requests(
             ))
         }
 
-        PsbtOptions::Forget { psbt_id } => {
+        PsbtOptions::Decode { psbt_id } => {
+            let request = Request::new(proto::DecodePsbtRequest {
+                psbt_id: psbt_id.to_string(),
+            });
+            Ok(Response::DecodePsbt(client.decode_psbt(request).await?))
+        }
+
+        PsbtOptions::Forget { psbt_id, actor } => {
             let request = Request::new(proto::ForgetPsbtRequest {
                 psbt_id: psbt_id.to_string(),
+                actor: actor.clone(),
             });
             Ok(Response::ForgetPsbt(client.forget_psbt(request).await?))
         }
+
+        PsbtOptions::Restore { psbt_id } => {
+            let request = Request::new(proto::RestorePsbtRequest {
+                psbt_id: psbt_id.to_string(),
+            });
+            Ok(Response::RestorePsbt(client.restore_psbt(request).await?))
+        }
+    }
+}
+
+async fn handle_bsms_requests(
+    client: &mut Client,
+    options: &BsmsOptions,
+) -> Result<Response, Box<dyn Error>> {
+    match options {
+        BsmsOptions::Create {
+            address_type,
+            network,
+            required_sigs,
+            signers,
+        } => {
+            let request = Request::new(proto::CreateBsmsSessionRequest {
+                address_type: proto::AddressType::from(address_type.as_str()).into(),
+                network: proto::Network::from(*network).into(),
+                required_sigs: *required_sigs,
+                signers: *signers,
+            });
+            Ok(Response::CreateBsmsSession(
+                client.create_bsms_session(request).await?,
+            ))
+        }
+
+        BsmsOptions::Submit {
+            session_id,
+            key_record,
+        } => {
+            let request = Request::new(proto::SubmitBsmsKeyRecordRequest {
+                session_id: session_id.to_string(),
+                key_record: fs::read_to_string(key_record)?,
+            });
+            Ok(Response::SubmitBsmsKeyRecord(
+                client.submit_bsms_key_record(request).await?,
+            ))
+        }
+
+        BsmsOptions::Info { session_id } => {
+            let request = Request::new(proto::GetBsmsSessionRequest {
+                session_id: session_id.to_string(),
+            });
+            Ok(Response::GetBsmsSession(
+                client.get_bsms_session(request).await?,
+            ))
+        }
+    }
+}
+
+async fn handle_label_requests(
+    client: &mut Client,
+    options: &LabelOptions,
+) -> Result<Response, Box<dyn Error>> {
+    match options {
+        LabelOptions::Set {
+            wallet_id,
+            label_type,
+            reference,
+            label,
+            origin,
+            spendable,
+        } => {
+            let request = Request::new(proto::SetLabelRequest {
+                label: Some(proto::Label {
+                    wallet_id: wallet_id.to_string(),
+                    label_type: (*label_type).into(),
+                    reference: reference.clone(),
+                    label: label.clone(),
+                    origin: origin.clone(),
+                    spendable: *spendable,
+                    archived_at: None,
+                    archived_by: None,
+                }),
+            });
+            Ok(Response::SetLabel(client.set_label(request).await?))
+        }
+
+        LabelOptions::Find {
+            wallet_id,
+            label_type,
+            reference,
+            include_archived,
+        } => {
+            let request = Request::new(proto::FindLabelRequest {
+                wallet_id: wallet_id.to_string(),
+                label_type: label_type.map(|label_type| label_type.into()),
+                reference: reference.clone(),
+                include_archived: *include_archived,
+            });
+            Ok(Response::FindLabel(client.find_label(request).await?))
+        }
+
+        LabelOptions::Forget {
+            wallet_id,
+            label_type,
+            reference,
+            actor,
+        } => {
+            let request = Request::new(proto::ForgetLabelRequest {
+                wallet_id: wallet_id.to_string(),
+                label_type: (*label_type).into(),
+                reference: reference.clone(),
+                actor: actor.clone(),
+            });
+            Ok(Response::ForgetLabel(client.forget_label(request).await?))
+        }
+
+        LabelOptions::Restore {
+            wallet_id,
+            label_type,
+            reference,
+        } => {
+            let request = Request::new(proto::RestoreLabelRequest {
+                wallet_id: wallet_id.to_string(),
+                label_type: (*label_type).into(),
+                reference: reference.clone(),
+            });
+            Ok(Response::RestoreLabel(client.restore_label(request).await?))
+        }
+
+        LabelOptions::Import { wallet_id, labels } => {
+            let request = Request::new(proto::ImportLabelsRequest {
+                wallet_id: wallet_id.to_string(),
+                jsonl: fs::read_to_string(labels)?,
+            });
+            Ok(Response::ImportLabels(client.import_labels(request).await?))
+        }
+
+        LabelOptions::Export { wallet_id } => {
+            let request = Request::new(proto::ExportLabelsRequest {
+                wallet_id: wallet_id.to_string(),
+            });
+            Ok(Response::ExportLabels(client.export_labels(request).await?))
+        }
+    }
+}
+
+async fn handle_admin_requests(
+    client: &mut Client,
+    options: &AdminOptions,
+) -> Result<Response, Box<dyn Error>> {
+    match options {
+        AdminOptions::Backup {
+            output,
+            passphrase,
+            admin_token,
+            actor,
+        } => {
+            let mut request = Request::new(proto::BackupRequest {
+                passphrase: passphrase.clone(),
+                actor: actor.clone(),
+            });
+            request
+                .metadata_mut()
+                .insert("authorization", format!("Bearer {}", admin_token).parse()?);
+            let mut response = client.backup(request).await?;
+            fs::write(output, std::mem::take(&mut response.get_mut().archive))?;
+            Ok(Response::Backup(response))
+        }
+
+        AdminOptions::ExportKey {
+            wallet_id,
+            admin_token,
+            actor,
+        } => {
+            let mut request = Request::new(proto::ExportInternalKeyRequest {
+                wallet_id: wallet_id.to_string(),
+                actor: actor.clone(),
+            });
+            request
+                .metadata_mut()
+                .insert("authorization", format!("Bearer {}", admin_token).parse()?);
+            Ok(Response::ExportInternalKey(
+                client.export_internal_key(request).await?,
+            ))
+        }
+
+        AdminOptions::SplitKey {
+            wallet_id,
+            threshold,
+            share_count,
+            admin_token,
+            passphrase,
+            actor,
+        } => {
+            let mut request = Request::new(proto::SplitInternalKeyRequest {
+                wallet_id: wallet_id.to_string(),
+                threshold: *threshold,
+                share_count: *share_count,
+                passphrase: passphrase.clone(),
+                actor: actor.clone(),
+            });
+            request
+                .metadata_mut()
+                .insert("authorization", format!("Bearer {}", admin_token).parse()?);
+            Ok(Response::SplitInternalKey(
+                client.split_internal_key(request).await?,
+            ))
+        }
     }
 }
 
@@ -285,7 +1050,217 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Command::Cosigner(opts) => handle_cosigner_requests(&mut client, &opts).await?,
         Command::Wallet(opts) => handle_wallet_requests(&mut client, &opts).await?,
         Command::Psbt(opts) => handle_psbt_requests(&mut client, &opts).await?,
+        Command::Bsms(opts) => handle_bsms_requests(&mut client, &opts).await?,
+        Command::Label(opts) => handle_label_requests(&mut client, &opts).await?,
+        Command::Admin(opts) => handle_admin_requests(&mut client, &opts).await?,
     };
     println!("RESPONSE={:?}", response);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, os::unix::fs::PermissionsExt, sync::Once};
+
+    use bdk::bitcoin::{
+        secp256k1::{PublicKey, Secp256k1, SecretKey},
+        util::bip32::DerivationPath,
+        Transaction, TxIn,
+    };
+
+    use super::*;
+
+    /// Fake HWI executables, each answering like HWI would in one situation.
+    const FAKE_HWIS: &[(&str, &str)] = &[
+        (
+            "devices",
+            r#"case "$1" in
+enumerate) echo '[{"type": "trezor", "fingerprint": "deadbeef"}, {"type": "coldcard", "fingerprint": "01020304"}]' ;;
+*) echo "{\"psbt\": \"signed:$2:$4:$6\", \"signed\": true}" ;;
+esac"#,
+        ),
+        ("no-devices", r#"echo '[]'"#),
+        (
+            "missing-device",
+            r#"echo '{"error": "Could not find device with specified fingerprint", "code": -3}'
+exit 1"#,
+        ),
+        (
+            "crash",
+            r#"echo 'Traceback (most recent call last): boom' >&2
+exit 1"#,
+        ),
+        (
+            "exit-status",
+            r#"echo '{"psbt": "cHNidP8B"}'
+exit 2"#,
+        ),
+        (
+            "unsigned",
+            r#"echo '{"psbt": "cHNidP8B", "signed": false}'"#,
+        ),
+    ];
+
+    /// Path of the fake HWI called `name`.
+    ///
+    /// They are all written before the first one runs, as executing a file
+    /// another thread is still writing fails.
+    fn fake_hwi(name: &str) -> PathBuf {
+        static WRITE: Once = Once::new();
+        let dir = env::temp_dir().join(format!("ohm-fake-hwi-{}", process::id()));
+        WRITE.call_once(|| {
+            fs::create_dir_all(&dir).unwrap();
+            for (name, script) in FAKE_HWIS {
+                let path = dir.join(name);
+                fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            }
+        });
+        dir.join(name)
+    }
+
+    /// PSBT with one input per fingerprint, each derived from that key.
+    fn psbt(fingerprints: &[&str]) -> String {
+        let key = PublicKey::from_secret_key(
+            &Secp256k1::new(),
+            &SecretKey::from_slice(&[1; 32]).unwrap(),
+        );
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn::default(); fingerprints.len()],
+            output: vec![],
+        };
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+        for (input, fingerprint) in psbt.inputs.iter_mut().zip(fingerprints) {
+            input.bip32_derivation.insert(
+                key,
+                (
+                    Fingerprint::from_str(fingerprint).unwrap(),
+                    DerivationPath::master(),
+                ),
+            );
+        }
+        psbt.to_string()
+    }
+
+    #[test]
+    fn sign_with_fingerprint() {
+        let signed = sign_with_device(
+            &fake_hwi("devices"),
+            "cHNidP8B",
+            Some(Fingerprint::from_str("deadbeef").unwrap()),
+            Network::Testnet,
+        )
+        .unwrap();
+        assert_eq!(signed, "signed:deadbeef:test:cHNidP8B");
+    }
+
+    #[test]
+    fn sign_with_matching_device() {
+        let psbt = psbt(&["01020304"]);
+        let signed = sign_with_device(&fake_hwi("devices"), &psbt, None, Network::Bitcoin).unwrap();
+        assert_eq!(signed, format!("signed:01020304:main:{}", psbt));
+    }
+
+    #[test]
+    fn no_matching_device() {
+        let err = sign_with_device(
+            &fake_hwi("devices"),
+            &psbt(&["aabbccdd"]),
+            None,
+            Network::Testnet,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("no connected and unlocked device"),
+            "{}",
+            err
+        );
+
+        let err = sign_with_device(
+            &fake_hwi("no-devices"),
+            &psbt(&["01020304"]),
+            None,
+            Network::Testnet,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string().contains("no connected and unlocked device"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn several_matching_devices() {
+        let err = sign_with_device(
+            &fake_hwi("devices"),
+            &psbt(&["01020304", "deadbeef"]),
+            None,
+            Network::Testnet,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("--fingerprint"), "{}", err);
+    }
+
+    #[test]
+    fn missing_device() {
+        let err = sign_with_device(
+            &fake_hwi("missing-device"),
+            "cHNidP8B",
+            Some(Fingerprint::from_str("deadbeef").unwrap()),
+            Network::Testnet,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("Could not find device"), "{}", err);
+    }
+
+    #[test]
+    fn non_zero_exit() {
+        let fingerprint = Some(Fingerprint::from_str("deadbeef").unwrap());
+
+        let err = sign_with_device(
+            &fake_hwi("crash"),
+            "cHNidP8B",
+            fingerprint,
+            Network::Testnet,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("boom"), "{}", err);
+
+        // Output is not trusted if HWI says it failed.
+        let err = sign_with_device(
+            &fake_hwi("exit-status"),
+            "cHNidP8B",
+            fingerprint,
+            Network::Testnet,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("exit status: 2"), "{}", err);
+    }
+
+    #[test]
+    fn not_signed() {
+        let err = sign_with_device(
+            &fake_hwi("unsigned"),
+            "cHNidP8B",
+            Some(Fingerprint::from_str("deadbeef").unwrap()),
+            Network::Testnet,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("did not sign"), "{}", err);
+    }
+
+    #[test]
+    fn hwi_not_installed() {
+        let err = sign_with_device(
+            &fake_hwi("not-installed"),
+            "cHNidP8B",
+            Some(Fingerprint::from_str("deadbeef").unwrap()),
+            Network::Testnet,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("failed to run"), "{}", err);
+    }
+}
@@ -1,7 +1,9 @@
-use std::{error::Error, fs::File};
+use std::{error::Error, fs, fs::File, io, io::BufRead, path::PathBuf};
 
 use structopt::{clap::AppSettings, StructOpt};
 
+use bdk::bitcoin::Network;
+
 use ohm::{Config, Server};
 
 #[derive(Debug, StructOpt)]
@@ -9,6 +11,40 @@ use ohm::{Config, Server};
 struct Options {
     #[structopt(short, long, default_value = "/etc/ohm/config.yaml")]
     config_file: String,
+    /// Apply pending database migrations before starting
+    #[structopt(long)]
+    migrate: bool,
+    /// Permanently delete records archived longer than `archive_retention_days`, then exit
+    #[structopt(long, conflicts_with = "migrate")]
+    purge: bool,
+    /// Write an encrypted backup of the database to the given file, then exit
+    #[structopt(long, conflicts_with_all = &["migrate", "purge"])]
+    backup: Option<PathBuf>,
+    /// Recreate the records of an encrypted backup in an empty database, then exit
+    #[structopt(long, conflicts_with_all = &["migrate", "purge", "backup"])]
+    restore: Option<PathBuf>,
+    /// Passphrase protecting backups
+    #[structopt(long, env = "OHM_BACKUP_PASSPHRASE", hide_env_values = true)]
+    passphrase: Option<String>,
+    /// Recreate the wallet of the given descriptor export from SLIP-39 shares of its internal
+    /// key, read one per line from standard input, then exit
+    #[structopt(
+        long,
+        conflicts_with_all = &["migrate", "purge", "backup", "restore"],
+        requires = "network"
+    )]
+    recover_key: Option<PathBuf>,
+    /// Network of the wallet to recover
+    #[structopt(long)]
+    network: Option<Network>,
+    /// Passphrase the SLIP-39 shares were made with
+    #[structopt(
+        long,
+        env = "OHM_SHARE_PASSPHRASE",
+        hide_env_values = true,
+        default_value = ""
+    )]
+    share_passphrase: String,
 }
 
 #[tokio::main]
@@ -18,9 +54,68 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let config_file = File::open(cli_opts.config_file)
         .map_err(|msg| format!("Failed to open config file: {}", msg))?;
     let config: Config = serde_yaml::from_reader(config_file)?;
+
+    if cli_opts.purge {
+        let purged = Server::purge(&config)?;
+        println!("Purged {} archived record(s)", purged);
+        return Ok(());
+    }
+
+    if let Some(path) = &cli_opts.backup {
+        let passphrase = cli_opts
+            .passphrase
+            .ok_or("a backup passphrase is required")?;
+        fs::write(path, Server::backup(&config, &passphrase)?)?;
+        println!("Wrote backup to {}", path.display());
+        return Ok(());
+    }
+
+    if let Some(path) = &cli_opts.restore {
+        let passphrase = cli_opts
+            .passphrase
+            .ok_or("a backup passphrase is required")?;
+        let (backup, rescanned, failures) =
+            Server::restore(&config, &fs::read(path)?, &passphrase)?;
+        println!(
+            "Restored {} wallet(s) and {} cosigner(s) from the backup of {}, rescanned {} wallet(s)",
+            backup.wallet_count(),
+            backup.cosigner_count(),
+            backup.creation_time(),
+            rescanned
+        );
+        for failure in failures {
+            eprintln!(
+                "warning: failed to rescan {}; it is rescanned when next used",
+                failure
+            );
+        }
+        return Ok(());
+    }
+
+    if let (Some(path), Some(network)) = (&cli_opts.recover_key, cli_opts.network) {
+        let shares = io::stdin()
+            .lock()
+            .lines()
+            .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let wallet = Server::recover(
+            &config,
+            network.into(),
+            &fs::read_to_string(path)?,
+            &shares,
+            &cli_opts.share_passphrase,
+        )?;
+        println!(
+            "Recovered wallet {} with {} external cosigner(s)",
+            wallet.uuid().unwrap_or_default(),
+            wallet.cosigners().len()
+        );
+        return Ok(());
+    }
+
     let address = format!("{}:{}", &config.bind_addr, &config.port);
 
-    let server = Server::new(config)?;
+    let server = Server::new(config, cli_opts.migrate)?;
     server.serve(address.parse()?).await?;
 
     Ok(())
@@ -0,0 +1,350 @@
+use std::{error::Error, str::FromStr};
+
+use bdk::{
+    bitcoin,
+    bitcoin::{
+        secp256k1,
+        util::{bip32, misc},
+    },
+    descriptor::DescriptorPublicKey,
+};
+use uuid::Uuid;
+
+use super::{
+    backend::Pool, AddressType, Cosigner, CosignerType, ExportFormat, KeyOptions, Network, Wallet,
+};
+use crate::db;
+
+const BSMS_VERSION: &str = "BSMS 1.0";
+
+/// Token of sessions whose records travel unencrypted. BIP129 requires key and
+/// descriptor records to be encrypted with any other token.
+const NO_ENCRYPTION_TOKEN: &str = "00";
+
+pub struct KeyRecord {
+    token: String,
+    xpub: bip32::ExtendedPubKey,
+    origin: Option<bip32::KeySource>,
+}
+
+impl KeyRecord {
+    pub fn parse(record: &str) -> Result<Self, Box<dyn Error>> {
+        let lines: Vec<&str> = record.trim().lines().map(|line| line.trim()).collect();
+        if lines.len() != 5 {
+            return Err("key record should consist of 5 lines".into());
+        }
+
+        if lines[0] != BSMS_VERSION {
+            return Err(format!("unsupported key record version: {}", lines[0]).into());
+        }
+
+        if lines[1] != NO_ENCRYPTION_TOKEN {
+            return Err("encrypted key records are unsupported, use the token 00".into());
+        }
+
+        let xkey = match DescriptorPublicKey::from_str(lines[2])? {
+            DescriptorPublicKey::XPub(xkey) => xkey,
+            DescriptorPublicKey::SinglePub(_) => {
+                return Err("key record should contain an extended public key".into())
+            }
+        };
+        let xpub = xkey.xkey;
+
+        let signature = misc::MessageSignature::from_base64(lines[4])?;
+        let message = lines[..4].join("\n");
+        let secp = secp256k1::Secp256k1::verification_only();
+        let public_key = signature.recover_pubkey(&secp, misc::signed_msg_hash(&message))?;
+
+        if public_key.inner != xpub.public_key {
+            return Err("key record signature does not match its key".into());
+        }
+
+        Ok(Self {
+            token: lines[1].to_string(),
+            xpub,
+            origin: xkey.origin,
+        })
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn xpub(&self) -> &bip32::ExtendedPubKey {
+        &self.xpub
+    }
+
+    pub fn origin(&self) -> Option<&bip32::KeySource> {
+        self.origin.as_ref()
+    }
+}
+
+pub struct BsmsSession {
+    uuid: Option<String>,
+    token: String,
+    address_type: AddressType,
+    network: Network,
+    required_signatures: u64,
+    signers: u64,
+    cosigners: Vec<Uuid>,
+    wallet: Option<Uuid>,
+}
+
+impl BsmsSession {
+    pub fn new(
+        address_type: AddressType,
+        network: Network,
+        required_signatures: u64,
+        signers: u64,
+    ) -> Result<Self, Box<dyn Error>> {
+        if signers < 1 {
+            return Err("a session requires at least one external signer".into());
+        }
+
+        // The internal cosigner is added on top of the external signers.
+        if required_signatures < 1 || required_signatures > signers + 1 {
+            return Err("required signatures should be between 1 and the number of signers".into());
+        }
+
+        Ok(Self {
+            uuid: None,
+            token: NO_ENCRYPTION_TOKEN.to_string(),
+            address_type,
+            network,
+            required_signatures,
+            signers,
+            cosigners: vec![],
+            wallet: None,
+        })
+    }
+
+    pub fn from_db(
+        connection: &mut db::DbConnection,
+        uuid: Uuid,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        let record = match db::BsmsSession::find(connection, Some(&uuid))?.pop() {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+
+        let mut cosigners = vec![];
+        for key_record in db::BsmsKeyRecord::find(connection, &uuid)? {
+            cosigners.push(Uuid::from_str(&key_record.cosigner_uuid)?);
+        }
+
+        Ok(Some(Self {
+            uuid: Some(record.uuid),
+            token: record.token,
+            address_type: record.address_type,
+            network: record.network,
+            required_signatures: record.required_signatures as u64,
+            signers: record.signers as u64,
+            cosigners,
+            wallet: record
+                .wallet_uuid
+                .map(|uuid| Uuid::from_str(&uuid))
+                .transpose()?,
+        }))
+    }
+
+    pub fn uuid(&self) -> Option<&str> {
+        self.uuid.as_deref()
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn address_type(&self) -> AddressType {
+        self.address_type
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn required_signatures(&self) -> u64 {
+        self.required_signatures
+    }
+
+    pub fn signers(&self) -> u64 {
+        self.signers
+    }
+
+    pub fn cosigners(&self) -> &Vec<Uuid> {
+        &self.cosigners
+    }
+
+    pub fn wallet(&self) -> &Option<Uuid> {
+        &self.wallet
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.wallet.is_some()
+    }
+
+    pub fn submit_key_record(
+        &mut self,
+        connection: &mut db::DbConnection,
+        pool: Option<&Pool>,
+        record: &str,
+        key: &KeyOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        let session_uuid =
+            Uuid::from_str(self.uuid.as_ref().ok_or("please save this session first")?)?;
+
+        if self.is_complete() {
+            return Err("session has already been completed".into());
+        }
+
+        let key_record = KeyRecord::parse(record)?;
+        if key_record.token() != self.token {
+            return Err("key record token does not match the session".into());
+        }
+
+        let expected_network = match self.network {
+            Network::Mainnet => bitcoin::Network::Bitcoin,
+            _ => bitcoin::Network::Testnet,
+        };
+        if key_record.xpub().network != expected_network {
+            return Err("key record belongs to a different network".into());
+        }
+
+        for uuid in &self.cosigners {
+            let cosigner = Cosigner::from_db(connection, Some(*uuid))?
+                .ok_or("session cosigner could not be found")?;
+            if cosigner.xpub() == key_record.xpub() {
+                return Err("key record has already been submitted".into());
+            }
+        }
+
+        let mut cosigner =
+            Cosigner::new(CosignerType::External, None, Some(*key_record.xpub()), None)?;
+        cosigner.set_origin(key_record.origin().cloned())?;
+        cosigner.save(connection)?;
+        let cosigner_uuid = cosigner.uuid().unwrap().to_string();
+
+        db::BsmsKeyRecord::new(&session_uuid, &cosigner_uuid, record).insert(connection)?;
+        self.cosigners.push(Uuid::from_str(&cosigner_uuid)?);
+
+        if self.cosigners.len() as u64 == self.signers {
+            let mut wallet = Wallet::new(
+                connection,
+                pool,
+                self.address_type,
+                self.network,
+                self.required_signatures,
+                self.cosigners.clone(),
+                key,
+            )?;
+            wallet.save(connection)?;
+
+            self.wallet = Some(Uuid::from_str(wallet.uuid().unwrap())?);
+            self.save(connection)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn descriptor_record(
+        &self,
+        connection: &mut db::DbConnection,
+        pool: Option<&Pool>,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        match self.wallet {
+            Some(uuid) => {
+                let wallet = Wallet::from_db(connection, pool, Some(uuid))?
+                    .ok_or("session wallet could not be found")?;
+                Ok(Some(wallet.export(connection, ExportFormat::Bsms)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn save(&mut self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
+        let mut new_record = db::BsmsSession::new(
+            &self.token,
+            self.address_type,
+            self.network,
+            self.required_signatures as i16,
+            self.signers as i16,
+            self.wallet.as_ref(),
+        );
+
+        if let Some(uuid) = &self.uuid {
+            new_record.uuid = uuid.clone();
+        };
+
+        let record = new_record.upsert(connection)?;
+
+        if self.uuid.is_none() {
+            self.uuid = Some(record.uuid)
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bdk::bitcoin::secp256k1::Message;
+
+    use super::*;
+    use crate::cosigner::format_origin;
+
+    fn key_record(token: &str, key: &str, signer: &bip32::ExtendedPrivKey) -> String {
+        let message = format!("{}\n{}\n{}\nalice", BSMS_VERSION, token, key);
+        let secp = secp256k1::Secp256k1::new();
+        let hash = Message::from_slice(&misc::signed_msg_hash(&message)).unwrap();
+        let signature = secp.sign_ecdsa_recoverable(&hash, &signer.private_key);
+        let signature = misc::MessageSignature::new(signature, true);
+
+        format!("{}\n{}", message, signature.to_base64())
+    }
+
+    fn account() -> (bip32::ExtendedPrivKey, bip32::ExtendedPubKey, String) {
+        let secp = secp256k1::Secp256k1::new();
+        let master =
+            bip32::ExtendedPrivKey::new_master(bitcoin::Network::Testnet, &[1; 32]).unwrap();
+        let path = bip32::DerivationPath::from_str("m/48'/1'/0'/2'").unwrap();
+        let xprv = master.derive_priv(&secp, &path).unwrap();
+        let xpub = bip32::ExtendedPubKey::from_priv(&secp, &xprv);
+        let origin = format!("{}/48'/1'/0'/2'", master.fingerprint(&secp));
+
+        (xprv, xpub, origin)
+    }
+
+    #[test]
+    fn parse_with_origin() {
+        let (xprv, xpub, origin) = account();
+
+        let record =
+            KeyRecord::parse(&key_record("00", &format!("[{}]{}", origin, xpub), &xprv)).unwrap();
+        assert_eq!(record.token(), "00");
+        assert_eq!(record.xpub(), &xpub);
+        assert_eq!(format_origin(record.origin().unwrap()), origin);
+    }
+
+    #[test]
+    fn encrypted_token() {
+        let (xprv, xpub, _) = account();
+
+        let record = key_record("a54044308ceac9b7", &xpub.to_string(), &xprv);
+        let err = KeyRecord::parse(&record).err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("encrypted key records are unsupported"));
+    }
+
+    #[test]
+    fn wrong_signature() {
+        let (xprv, xpub, _) = account();
+        let other = xprv
+            .ckd_priv(&secp256k1::Secp256k1::new(), bip32::ChildNumber::from(0))
+            .unwrap();
+
+        let record = key_record("00", &xpub.to_string(), &other);
+        let err = KeyRecord::parse(&record).err().unwrap();
+        assert!(err.to_string().contains("does not match its key"));
+    }
+}
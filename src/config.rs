@@ -3,10 +3,164 @@ use std::{net::Ipv4Addr, path::PathBuf};
 use serde::{Deserialize, Serialize};
 use url::Url;
 
+use super::{Network, Secret};
+
+/// What to do when a wallet already has `gap_limit` unfunded receive addresses.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GapLimitPolicy {
+    #[default]
+    Refuse,
+    Reuse,
+}
+
+fn default_gap_limit() -> u64 {
+    20
+}
+
+fn default_db_pool_size() -> u32 {
+    8
+}
+
+fn default_archive_retention_days() -> u32 {
+    30
+}
+
+fn default_retries() -> u8 {
+    3
+}
+
+fn default_signer_timeout() -> u64 {
+    120
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BackendConfig {
+    pub url: Url,
+    /// Tried in order whenever `url` (or the previous fallback) is unreachable.
+    #[serde(default)]
+    pub failover: Vec<Url>,
+    /// Bitcoin Core cookie file, used instead of credentials in the URL.
+    #[serde(default)]
+    pub cookie_file: Option<PathBuf>,
+    /// Request timeout in seconds, for Electrum and Esplora.
+    #[serde(default)]
+    pub timeout: Option<u8>,
+    #[serde(default = "default_retries")]
+    pub retries: u8,
+    /// `host:port` of a SOCKS5 proxy such as Tor, for Electrum and Esplora.
+    #[serde(default)]
+    pub socks5: Option<String>,
+    /// Hex encoded challenge script of a custom signet; the default signet's
+    /// challenge is assumed if unset.
+    #[serde(default)]
+    pub signet_challenge: Option<String>,
+}
+
+impl BackendConfig {
+    pub fn urls(&self) -> impl Iterator<Item = &Url> {
+        std::iter::once(&self.url).chain(self.failover.iter())
+    }
+}
+
+/// Where the internal cosigner of new wallets keeps its key and signs.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SignerConfig {
+    /// A fresh key per wallet, kept in the database.
+    #[default]
+    Database,
+    /// An xprv, preceded by its origin unless it's a master key, read from
+    /// this file whenever it's needed.
+    KeyFile { path: PathBuf },
+    /// A shared library exporting `ohm_signer_sign` and `ohm_signer_free`,
+    /// ohm's own signing ABI.
+    #[serde(alias = "module")]
+    Library {
+        path: PathBuf,
+        /// Preceded by its origin unless it's a master key.
+        xpub: String,
+        #[serde(default)]
+        slot: u64,
+        #[serde(default)]
+        pin: Option<Secret<String>>,
+    },
+    /// A process listening on this Unix socket, exchanging base64 encoded
+    /// PSBTs one per line.
+    Socket {
+        path: PathBuf,
+        /// Preceded by its origin unless it's a master key.
+        xpub: String,
+        /// Seconds to wait for the signed PSBT.
+        #[serde(default = "default_signer_timeout")]
+        timeout: u64,
+    },
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Backends {
+    pub regtest: Option<BackendConfig>,
+    pub testnet: Option<BackendConfig>,
+    pub mainnet: Option<BackendConfig>,
+    pub signet: Option<BackendConfig>,
+}
+
+impl Backends {
+    pub fn get(&self, network: Network) -> Option<&BackendConfig> {
+        match network {
+            Network::Regtest => self.regtest.as_ref(),
+            Network::Testnet => self.testnet.as_ref(),
+            Network::Mainnet => self.mainnet.as_ref(),
+            Network::Signet => self.signet.as_ref(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Network, &BackendConfig)> {
+        [
+            (Network::Regtest, self.regtest.as_ref()),
+            (Network::Testnet, self.testnet.as_ref()),
+            (Network::Mainnet, self.mainnet.as_ref()),
+            (Network::Signet, self.signet.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(network, backend)| backend.map(|backend| (network, backend)))
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     pub bind_addr: Ipv4Addr,
     pub port: u16,
-    pub backend_url: Url,
-    pub db_path: PathBuf,
+    #[serde(default)]
+    pub backends: Backends,
+    /// `postgres://` URL, or path of the SQLite database (formerly `db_path`).
+    #[serde(alias = "db_path")]
+    pub database_url: String,
+    /// Maximum number of concurrently open database connections.
+    #[serde(default = "default_db_pool_size")]
+    pub db_pool_size: u32,
+    #[serde(default = "default_gap_limit")]
+    pub gap_limit: u64,
+    #[serde(default)]
+    pub gap_limit_policy: GapLimitPolicy,
+    /// Run without any chain backend, e.g. on an airgapped signer.
+    #[serde(default)]
+    pub offline: bool,
+    /// How long forgotten records are kept before `--purge` deletes them.
+    #[serde(default = "default_archive_retention_days")]
+    pub archive_retention_days: u32,
+    /// Lets clients download encrypted backups through the `Backup` RPC.
+    #[serde(default)]
+    pub allow_backup_rpc: bool,
+    /// Keeps the mnemonics of new internal keys, encrypted under this
+    /// passphrase, until they are exported.
+    #[serde(default)]
+    pub key_encryption_passphrase: Option<Secret<String>>,
+    /// Bearer token required by `ExportInternalKey`, which is disabled if unset.
+    #[serde(default)]
+    pub admin_token: Option<Secret<String>>,
+    /// Signer of the internal cosigner of wallets whose key isn't kept in
+    /// the database.
+    #[serde(default)]
+    pub signer: SignerConfig,
 }
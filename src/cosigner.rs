@@ -1,24 +1,91 @@
 use std::{error::Error, str::FromStr};
 
 use bdk::{
-    bitcoin::{secp256k1, util::bip32},
+    bitcoin::{secp256k1, secp256k1::rand::RngCore, util::bip32},
+    descriptor::DescriptorPublicKey,
     keys::{bip39, DerivableKey, ExtendedKey},
+    miniscript::descriptor::Wildcard,
 };
-use diesel::SqliteConnection;
+use chrono::{NaiveDateTime, Utc};
 use email_address::EmailAddress;
 use uuid::Uuid;
 
 use super::Network;
-use crate::db;
+use crate::{crypto::Sealed, db, signer::SignerKey, slip39, Secret};
 pub use db::CosignerType;
 
+/// A private key, its public key and its mnemonic, if sealed to be kept.
+type KeyPair = (
+    Secret<bip32::ExtendedPrivKey>,
+    bip32::ExtendedPubKey,
+    Option<String>,
+);
+
+/// How the key of an internal cosigner comes about.
+#[derive(Default)]
+pub struct KeyOptions<'a> {
+    /// BIP39 mnemonic to derive the key from instead of generating one.
+    pub mnemonic: Option<bip39::Mnemonic>,
+    /// BIP39 passphrase; never stored.
+    pub passphrase: Option<&'a str>,
+    /// An existing key to take as is, e.g. one recovered from SLIP-39 shares.
+    pub xprv: Option<Secret<bip32::ExtendedPrivKey>>,
+    /// Key of an external signer, of which the cosigner gets a child picked
+    /// at random; no private key is kept then.
+    pub xpub: Option<SignerKey>,
+    /// Keeps the mnemonic, encrypted under this passphrase, until it is
+    /// exported. Without it the mnemonic is discarded.
+    pub encryption_passphrase: Option<&'a str>,
+}
+
 pub struct Cosigner {
     uuid: Option<String>,
     type_: CosignerType,
     email_address: Option<EmailAddress>,
     xpub: bip32::ExtendedPubKey,
-    xprv: Option<bip32::ExtendedPrivKey>,
+    /// The encoded private key, only decoded for as long as it's needed.
+    xprv: Secret<Option<String>>,
     wallet: Option<Uuid>,
+    name: Option<String>,
+    archived_at: Option<NaiveDateTime>,
+    archived_by: Option<String>,
+    mnemonic: Option<String>,
+    mnemonic_has_passphrase: bool,
+    mnemonic_exported_at: Option<NaiveDateTime>,
+    origin: Option<bip32::KeySource>,
+}
+
+/// Parses an extended public key, optionally preceded by its origin the way
+/// descriptors write it: `[d34db33f/48'/0'/0'/2']xpub...`.
+pub fn parse_key(
+    key: &str,
+) -> Result<(bip32::ExtendedPubKey, Option<bip32::KeySource>), Box<dyn Error>> {
+    match DescriptorPublicKey::from_str(key.trim()) {
+        Ok(DescriptorPublicKey::XPub(xkey))
+            if xkey.derivation_path.as_ref().is_empty() && xkey.wildcard == Wildcard::None =>
+        {
+            Ok((xkey.xkey, xkey.origin))
+        }
+        _ => Err("expected an xpub, optionally preceded by its [fingerprint/path] origin".into()),
+    }
+}
+
+/// Writes a key origin as `d34db33f/48'/0'/0'/2'`.
+pub fn format_origin((fingerprint, path): &bip32::KeySource) -> String {
+    path.into_iter()
+        .fold(fingerprint.to_string(), |origin, child| {
+            format!("{}/{}", origin, child)
+        })
+}
+
+/// Reads a key origin written as `d34db33f/48'/0'/0'/2'`.
+pub fn parse_origin(origin: &str) -> Result<bip32::KeySource, Box<dyn Error>> {
+    let (fingerprint, path) = origin.split_once('/').unwrap_or((origin, ""));
+
+    Ok((
+        bip32::Fingerprint::from_str(fingerprint)?,
+        bip32::DerivationPath::from_str(format!("m/{}", path).trim_end_matches('/'))?,
+    ))
 }
 
 impl Cosigner {
@@ -28,42 +95,81 @@ impl Cosigner {
         xpub: Option<bip32::ExtendedPubKey>,
         network: Option<Network>,
     ) -> Result<Self, Box<dyn Error>> {
-        let (xprv, xpub) = match type_ {
+        let xpub = match type_ {
             CosignerType::Internal => {
-                if let Some(network) = network {
-                    let (xprv, xpub) =
-                        Self::generate_key_pair(network).map_err(|err| -> Box<dyn Error> {
-                            format!("failed to create a key pair: {}", err).into()
-                        })?;
-                    Ok((Some(xprv), xpub))
-                } else {
-                    Err("CosignerType::Internal requires a network to be supplied")
+                return match network {
+                    Some(network) => Self::new_internal(network, &KeyOptions::default()),
+                    None => Err("CosignerType::Internal requires a network to be supplied".into()),
                 }
             }
             CosignerType::External => {
-                if let Some(xpub) = xpub {
-                    Ok((None, xpub))
-                } else {
-                    Err("CosignerType::External requires a xpub to be supplied")
-                }
+                xpub.ok_or("CosignerType::External requires a xpub to be supplied")?
             }
-        }?;
+        };
 
         Ok(Self {
             uuid: None,
             type_,
             email_address,
+            xprv: Secret::default(),
+            xpub,
+            wallet: None,
+            name: None,
+            archived_at: None,
+            archived_by: None,
+            mnemonic: None,
+            mnemonic_has_passphrase: false,
+            mnemonic_exported_at: None,
+            origin: None,
+        })
+    }
+
+    pub fn new_internal(network: Network, options: &KeyOptions) -> Result<Self, Box<dyn Error>> {
+        let (xprv, xpub, mnemonic, origin) = match &options.xpub {
+            Some((xpub, (fingerprint, path))) => {
+                let child = bip32::ChildNumber::from_normal_idx(
+                    secp256k1::rand::thread_rng().next_u32() & 0x7fff_ffff,
+                )?;
+                let origin = (*fingerprint, path.child(child));
+                let xpub = xpub.ckd_pub(&secp256k1::Secp256k1::new(), child)?;
+                (Secret::default(), xpub, None, Some(origin))
+            }
+            None => {
+                let (xprv, xpub, mnemonic) =
+                    Self::generate_key_pair(network, options).map_err(|err| -> Box<dyn Error> {
+                        format!("failed to create a key pair: {}", err).into()
+                    })?;
+                (
+                    Secret::new(Some(xprv.expose().to_string())),
+                    xpub,
+                    mnemonic,
+                    None,
+                )
+            }
+        };
+
+        Ok(Self {
+            uuid: None,
+            type_: CosignerType::Internal,
+            email_address: None,
             xprv,
             xpub,
             wallet: None,
+            name: None,
+            archived_at: None,
+            archived_by: None,
+            mnemonic,
+            mnemonic_has_passphrase: options.passphrase.is_some(),
+            mnemonic_exported_at: None,
+            origin,
         })
     }
 
     pub fn from_db(
-        connection: &mut SqliteConnection,
+        connection: &mut db::DbConnection,
         uuid: Option<Uuid>,
     ) -> Result<Option<Self>, Box<dyn Error>> {
-        let mut cosigners = Self::find(connection, uuid, None, None, None)?;
+        let mut cosigners = Self::find(connection, uuid, None, None, None, None, false)?;
 
         Ok(match !cosigners.is_empty() {
             true => Some(cosigners.remove(0)),
@@ -72,11 +178,13 @@ impl Cosigner {
     }
 
     pub fn find(
-        connection: &mut SqliteConnection,
+        connection: &mut db::DbConnection,
         uuid: Option<Uuid>,
         email_address: Option<EmailAddress>,
         xpub: Option<bip32::ExtendedPubKey>,
         wallet: Option<Uuid>,
+        name: Option<&str>,
+        include_archived: bool,
     ) -> Result<Vec<Self>, Box<dyn Error>> {
         let records = db::Cosigner::find(
             connection,
@@ -84,6 +192,8 @@ impl Cosigner {
             email_address.as_ref(),
             xpub.as_ref(),
             wallet.as_ref(),
+            name,
+            include_archived,
         )?;
 
         let mut cosigners = vec![];
@@ -95,38 +205,186 @@ impl Cosigner {
                     .email_address
                     .map(|email| EmailAddress::from_str(&email))
                     .transpose()?,
-                xprv: record
-                    .xprv
-                    .map(|xprv| bip32::ExtendedPrivKey::from_str(&xprv))
-                    .transpose()?,
+                xprv: record.xprv,
                 xpub: bip32::ExtendedPubKey::from_str(&record.xpub)?,
                 wallet: record
                     .wallet_uuid
                     .map(|uuid| Uuid::from_str(&uuid))
                     .transpose()?,
+                name: record.name,
+                archived_at: record.archived_at,
+                archived_by: record.archived_by,
+                mnemonic: record.mnemonic,
+                mnemonic_has_passphrase: record.mnemonic_has_passphrase,
+                mnemonic_exported_at: record.mnemonic_exported_at,
+                origin: record.origin.as_deref().map(parse_origin).transpose()?,
             });
         }
 
         Ok(cosigners)
     }
 
+    /// Derives the key pair from the given or a new mnemonic, which is
+    /// returned encrypted if it is to be kept.
     fn generate_key_pair(
         network: Network,
-    ) -> Result<(bip32::ExtendedPrivKey, bip32::ExtendedPubKey), Box<dyn Error>> {
-        let mnemonic = bip39::Mnemonic::generate_in_with(
-            &mut secp256k1::rand::thread_rng(),
-            bip39::Language::English,
-            24,
+        options: &KeyOptions,
+    ) -> Result<KeyPair, Box<dyn Error>> {
+        let secp = secp256k1::Secp256k1::new();
+        if let Some(xprv) = &options.xprv {
+            let xpub = bip32::ExtendedPubKey::from_priv(&secp, xprv.expose());
+            return Ok((xprv.clone(), xpub, None));
+        }
+
+        let mnemonic = match &options.mnemonic {
+            Some(mnemonic) => mnemonic.clone(),
+            None => bip39::Mnemonic::generate_in_with(
+                &mut secp256k1::rand::thread_rng(),
+                bip39::Language::English,
+                24,
+            )?,
+        };
+
+        let xkey: ExtendedKey =
+            (mnemonic.clone(), options.passphrase.map(String::from)).into_extended_key()?;
+        let xprv = Secret::new(
+            xkey.into_xprv(network.into())
+                .ok_or("mnemonic did not yield a private key")?,
+        );
+
+        let xpub = bip32::ExtendedPubKey::from_priv(&secp, xprv.expose());
+
+        let sealed = options
+            .encryption_passphrase
+            .map(|passphrase| {
+                let sealed = Sealed::seal(
+                    passphrase,
+                    Secret::new(mnemonic.to_string()).expose().as_bytes(),
+                    xpub.to_string().as_bytes(),
+                )?;
+                Ok::<_, Box<dyn Error>>(serde_json::to_string(&sealed)?)
+            })
+            .transpose()?;
+
+        Ok((xprv, xpub, sealed))
+    }
+
+    /// Hands out the mnemonic of an internal key once, for a paper backup,
+    /// then forgets it. Every attempt is recorded on behalf of `actor`.
+    pub fn export_mnemonic(
+        &mut self,
+        connection: &mut db::DbConnection,
+        encryption_passphrase: &str,
+        actor: &str,
+    ) -> Result<Secret<String>, Box<dyn Error>> {
+        let sealed = match (&self.mnemonic, &self.mnemonic_exported_at) {
+            (_, Some(exported_at)) => {
+                self.record_key_export(connection, actor, "refused: already exported")?;
+                return Err(format!("the mnemonic was already exported on {}", exported_at).into());
+            }
+            (None, None) => {
+                self.record_key_export(connection, actor, "refused: no mnemonic stored")?;
+                return Err("no mnemonic was kept for this key".into());
+            }
+            (Some(sealed), None) => serde_json::from_str::<Sealed>(sealed)?,
+        };
+
+        let mnemonic = match sealed.open(encryption_passphrase, self.xpub.to_string().as_bytes()) {
+            Ok(mnemonic) => Secret::new(String::from_utf8(mnemonic)?),
+            Err(err) => {
+                self.record_key_export(
+                    connection,
+                    actor,
+                    "failed: mnemonic could not be decrypted",
+                )?;
+                return Err(err);
+            }
+        };
+
+        let uuid = self
+            .uuid
+            .as_ref()
+            .ok_or("please save this cosigner first")?;
+        let exported_at = Utc::now().naive_local();
+        connection.transaction(|connection| {
+            db::Cosigner::mark_mnemonic_exported(connection, uuid, exported_at)?;
+            db::KeyExport::new(Some(uuid), actor, "exported").insert(connection)?;
+            Ok(())
+        })?;
+        self.mnemonic = None;
+        self.mnemonic_exported_at = Some(exported_at);
+
+        Ok(mnemonic)
+    }
+
+    /// Splits the key into `count` SLIP-39 shares, any `threshold` of which
+    /// recover it along with `passphrase`, recording this on behalf of `actor`.
+    ///
+    /// The shares hold the chain code and private key of the master key, not a
+    /// BIP32 seed, so only `recover_key` can use them: other SLIP-39 wallets
+    /// would derive an unrelated key from the recombined secret.
+    pub fn split_key(
+        &self,
+        connection: &mut db::DbConnection,
+        threshold: u8,
+        count: u8,
+        passphrase: &str,
+        actor: &str,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let xprv = self.xprv()?.ok_or("this cosigner holds no private key")?;
+        let xprv = xprv.expose();
+        if xprv.depth != 0 {
+            return Err("only master keys can be split".into());
+        }
+
+        let secret = Secret::new([&xprv.chain_code[..], &xprv.private_key.secret_bytes()].concat());
+        let shares = slip39::split(secret.expose(), threshold, count, passphrase)?;
+        self.record_key_export(
+            connection,
+            actor,
+            &format!("split into {}-of-{} shares", threshold, count),
         )?;
 
-        let xkey: ExtendedKey = mnemonic.clone().into_extended_key()?;
-        let xprv: bip32::ExtendedPrivKey = xkey.into_xprv(network.into()).unwrap();
+        Ok(shares)
+    }
 
-        let xkey: ExtendedKey = mnemonic.into_extended_key()?;
-        let secp = secp256k1::Secp256k1::new();
-        let xpub = xkey.into_xpub(network.into(), &secp);
+    /// Recombines the master key from SLIP-39 shares made by `split_key`.
+    pub fn recover_key<S: AsRef<str>>(
+        network: Network,
+        shares: &[S],
+        passphrase: &str,
+    ) -> Result<Secret<bip32::ExtendedPrivKey>, Box<dyn Error>> {
+        let secret = Secret::new(slip39::combine(shares, passphrase)?);
+        let secret = secret.expose();
+        if secret.len() != 64 {
+            return Err("the shares do not hold a key".into());
+        }
 
-        Ok((xprv, xpub))
+        Ok(Secret::new(bip32::ExtendedPrivKey {
+            network: network.into(),
+            depth: 0,
+            parent_fingerprint: Default::default(),
+            child_number: bip32::ChildNumber::from_normal_idx(0)?,
+            private_key: secp256k1::SecretKey::from_slice(&secret[32..])?,
+            chain_code: bip32::ChainCode::from(&secret[..32]),
+        }))
+    }
+
+    /// Records an attempt to export the key, e.g. one that was refused
+    /// before reaching `export_mnemonic`.
+    pub fn record_key_export(
+        &self,
+        connection: &mut db::DbConnection,
+        actor: &str,
+        outcome: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let uuid = self
+            .uuid
+            .as_ref()
+            .ok_or("please save this cosigner first")?;
+        db::KeyExport::new(Some(uuid), actor, outcome).insert(connection)?;
+
+        Ok(())
     }
 
     pub fn type_(&self) -> CosignerType {
@@ -141,18 +399,90 @@ impl Cosigner {
         self.uuid.as_deref()
     }
 
-    pub fn xprv(&self) -> &Option<bip32::ExtendedPrivKey> {
-        &self.xprv
+    /// Decodes the private key, if it's kept in the database, for as long as
+    /// the returned value lives.
+    pub fn xprv(&self) -> Result<Option<Secret<bip32::ExtendedPrivKey>>, Box<dyn Error>> {
+        Ok(self
+            .xprv
+            .expose()
+            .as_deref()
+            .map(bip32::ExtendedPrivKey::from_str)
+            .transpose()?
+            .map(Secret::new))
+    }
+
+    pub fn has_xprv(&self) -> bool {
+        self.xprv.expose().is_some()
     }
 
     pub fn xpub(&self) -> &bip32::ExtendedPubKey {
         &self.xpub
     }
 
+    /// Master fingerprint and derivation path of the key, if known. A master
+    /// key is its own origin.
+    pub fn origin(&self) -> Option<bip32::KeySource> {
+        match &self.origin {
+            Some(origin) => Some(origin.clone()),
+            None if self.xpub.depth == 0 => {
+                Some((self.xpub.fingerprint(), bip32::DerivationPath::master()))
+            }
+            None => None,
+        }
+    }
+
+    pub fn set_origin(&mut self, origin: Option<bip32::KeySource>) -> Result<(), Box<dyn Error>> {
+        if let Some((fingerprint, path)) = &origin {
+            let matches = match path.as_ref().last() {
+                Some(child) => {
+                    path.as_ref().len() == self.xpub.depth as usize
+                        && *child == self.xpub.child_number
+                }
+                None => self.xpub.depth == 0 && *fingerprint == self.xpub.fingerprint(),
+            };
+            if !matches {
+                return Err("the key origin doesn't match the depth of the key".into());
+            }
+        }
+        self.origin = origin;
+
+        Ok(())
+    }
+
     pub fn wallet(&self) -> &Option<Uuid> {
         &self.wallet
     }
 
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn archived_at(&self) -> Option<&NaiveDateTime> {
+        self.archived_at.as_ref()
+    }
+
+    pub fn archived_by(&self) -> Option<&str> {
+        self.archived_by.as_deref()
+    }
+
+    /// Whether the mnemonic of the key is kept for an export.
+    pub fn has_mnemonic(&self) -> bool {
+        self.mnemonic.is_some()
+    }
+
+    /// Whether the key also needs a BIP39 passphrase besides its mnemonic.
+    pub fn mnemonic_has_passphrase(&self) -> bool {
+        self.mnemonic_has_passphrase
+    }
+
+    pub fn mnemonic_exported_at(&self) -> Option<&NaiveDateTime> {
+        self.mnemonic_exported_at.as_ref()
+    }
+
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name.filter(|name| !name.is_empty());
+    }
+
     pub fn set_wallet(&mut self, uuid: Uuid) -> Result<&Uuid, Box<dyn Error>> {
         match &self.wallet {
             Some(_) => Err("wallet has already been set".into()),
@@ -163,27 +493,73 @@ impl Cosigner {
         }
     }
 
-    pub fn remove(&mut self, connection: &mut SqliteConnection) -> Result<(), Box<dyn Error>> {
+    /// Wallets created with this cosigner as an external signer.
+    pub fn wallets(&self, connection: &mut db::DbConnection) -> Result<Vec<Uuid>, Box<dyn Error>> {
+        let uuid = match &self.uuid {
+            Some(uuid) => uuid,
+            None => return Ok(vec![]),
+        };
+
+        db::WalletCosigner::find(connection, None, Some(uuid))?
+            .into_iter()
+            .map(|record| Ok(Uuid::from_str(&record.wallet_uuid)?))
+            .collect()
+    }
+
+    /// Archives the cosigner on behalf of `actor`. Callers decide whether a
+    /// cosigner still in use may be forgotten.
+    pub fn archive(
+        &mut self,
+        connection: &mut db::DbConnection,
+        actor: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(uuid) = &self.uuid {
+            let archived_at = Utc::now().naive_local();
+            db::Cosigner::archive(connection, uuid, archived_at, actor)?;
+            self.archived_at = Some(archived_at);
+            self.archived_by = Some(actor.to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn restore(&mut self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
+        if let Some(uuid) = &self.uuid {
+            db::Cosigner::restore(connection, uuid)?;
+        }
+        self.archived_at = None;
+        self.archived_by = None;
+
+        Ok(())
+    }
+
+    /// Removes the cosigner along with its links to wallets.
+    pub fn remove(&mut self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
         if let Some(uuid) = &self.uuid {
-            db::Cosigner::remove(connection, uuid)?;
+            connection.transaction(|connection| db::Cosigner::remove(connection, uuid))?;
         }
         self.uuid = None;
 
         Ok(())
     }
 
-    pub fn save(&mut self, connection: &mut SqliteConnection) -> Result<(), Box<dyn Error>> {
+    pub fn save(&mut self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
         let mut new_record = db::Cosigner::new(
             self.type_,
             self.email_address.as_ref(),
-            self.xprv.as_ref(),
+            self.xprv.expose().as_deref(),
             &self.xpub,
             self.wallet.as_ref(),
+            self.name.as_deref(),
         );
 
         if let Some(uuid) = &self.uuid {
             new_record.uuid = uuid.clone();
         };
+        new_record.mnemonic = self.mnemonic.clone();
+        new_record.mnemonic_has_passphrase = self.mnemonic_has_passphrase;
+        new_record.mnemonic_exported_at = self.mnemonic_exported_at;
+        new_record.origin = self.origin.as_ref().map(format_origin);
 
         let record = new_record.upsert(connection)?;
 
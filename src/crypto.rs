@@ -0,0 +1,174 @@
+use std::error::Error;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use bdk::bitcoin::hashes::hex::{FromHex, ToHex};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// Argon2id cost parameters for new keys; each `Kdf` records its own.
+const MEMORY_COST: u32 = 64 * 1024;
+const TIME_COST: u32 = 3;
+const PARALLELISM: u32 = 1;
+
+/// Highest cost parameters accepted from a `Kdf` read back, so a crafted
+/// backup can't make deriving its key exhaust memory or time.
+const MAX_MEMORY_COST: u32 = 4 * MEMORY_COST;
+const MAX_TIME_COST: u32 = 4 * TIME_COST;
+const MAX_PARALLELISM: u32 = 4;
+
+/// How a key was derived from a passphrase.
+#[derive(Deserialize, Serialize)]
+pub struct Kdf {
+    salt: String,
+    memory_cost: u32,
+    time_cost: u32,
+    parallelism: u32,
+}
+
+impl Kdf {
+    /// Parameters for a new key, with a fresh random salt.
+    pub fn generate() -> Self {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+
+        Self {
+            salt: salt.to_hex(),
+            memory_cost: MEMORY_COST,
+            time_cost: TIME_COST,
+            parallelism: PARALLELISM,
+        }
+    }
+
+    /// Argon2id parameters, capped at the highest costs accepted.
+    fn params(&self) -> Result<Params, Box<dyn Error>> {
+        Ok(Params::new(
+            self.memory_cost.min(MAX_MEMORY_COST),
+            self.time_cost.min(MAX_TIME_COST),
+            self.parallelism.min(MAX_PARALLELISM),
+            Some(32),
+        )
+        .map_err(|err| format!("invalid key derivation parameters: {}", err))?)
+    }
+
+    pub fn cipher(&self, passphrase: &str) -> Result<ChaCha20Poly1305, Box<dyn Error>> {
+        let params = self.params()?;
+
+        let mut key = Key::default();
+        let derived = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(
+                passphrase.as_bytes(),
+                &Vec::<u8>::from_hex(&self.salt)?,
+                &mut key,
+            )
+            .map(|_| ChaCha20Poly1305::new(&key));
+        // The cipher wipes its own copy when dropped.
+        key.as_mut_slice().zeroize();
+
+        Ok(derived.map_err(|err| format!("key derivation failed: {}", err))?)
+    }
+}
+
+/// Encrypts `plaintext`, returning the hex encoded nonce and ciphertext.
+pub fn encrypt(
+    cipher: &ChaCha20Poly1305,
+    plaintext: &[u8],
+    associated_data: &[u8],
+) -> Result<(String, String), Box<dyn Error>> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: associated_data,
+            },
+        )
+        .map_err(|_| "encryption failed")?;
+
+    Ok((nonce.to_hex(), ciphertext.to_hex()))
+}
+
+/// Decrypts what `encrypt` returned, failing on a wrong key or any tampering.
+pub fn decrypt(
+    cipher: &ChaCha20Poly1305,
+    nonce: &str,
+    ciphertext: &str,
+    associated_data: &[u8],
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let nonce = Vec::<u8>::from_hex(nonce)?;
+    if nonce.len() != 12 {
+        return Err("invalid nonce".into());
+    }
+
+    cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &Vec::<u8>::from_hex(ciphertext)?,
+                aad: associated_data,
+            },
+        )
+        .map_err(|_| "wrong passphrase or corrupted data".into())
+}
+
+/// Data encrypted under a passphrase, along with what it takes to decrypt it.
+#[derive(Deserialize, Serialize)]
+pub struct Sealed {
+    kdf: Kdf,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl Sealed {
+    pub fn seal(
+        passphrase: &str,
+        plaintext: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Self, Box<dyn Error>> {
+        let kdf = Kdf::generate();
+        let (nonce, ciphertext) = encrypt(&kdf.cipher(passphrase)?, plaintext, associated_data)?;
+
+        Ok(Self {
+            kdf,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    pub fn open(
+        &self,
+        passphrase: &str,
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        decrypt(
+            &self.kdf.cipher(passphrase)?,
+            &self.nonce,
+            &self.ciphertext,
+            associated_data,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn excessive_cost() {
+        let kdf = Kdf {
+            memory_cost: u32::MAX,
+            time_cost: u32::MAX,
+            parallelism: u32::MAX,
+            ..Kdf::generate()
+        };
+
+        let params = kdf.params().unwrap();
+        assert_eq!(params.m_cost(), MAX_MEMORY_COST);
+        assert_eq!(params.t_cost(), MAX_TIME_COST);
+        assert_eq!(params.p_cost(), MAX_PARALLELISM);
+    }
+}
@@ -0,0 +1,195 @@
+use std::{collections::HashMap, error::Error, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{
+    cosigner::CosignerRecord,
+    label::LabelRecord,
+    psbt::PsbtRecord,
+    wallet::{
+        DecimalWrapper, WalletAddressRecord, WalletCosignerRecord, WalletRecord, WalletTagRecord,
+    },
+    Cosigner, DbConnection, Label, Psbt, Wallet, WalletAddress, WalletCosigner, WalletTag,
+};
+
+/// Every cosigner, wallet, PSBT and label in the database, archived ones
+/// included, along with what belongs to the wallets.
+#[derive(Deserialize, Serialize)]
+pub struct Snapshot {
+    pub cosigners: Vec<CosignerRecord>,
+    pub wallets: Vec<WalletRecord>,
+    pub wallet_cosigners: Vec<WalletCosignerRecord>,
+    pub wallet_tags: Vec<WalletTagRecord>,
+    pub wallet_addresses: Vec<WalletAddressRecord>,
+    pub psbts: Vec<PsbtRecord>,
+    pub labels: Vec<LabelRecord>,
+}
+
+impl Snapshot {
+    pub fn take(connection: &mut DbConnection) -> Result<Self, Box<dyn Error>> {
+        connection.transaction(|connection| {
+            let wallets = Wallet::find(connection, None, None, None, None, None, None, true)?;
+
+            let mut wallet_tags = vec![];
+            let mut wallet_addresses = vec![];
+            let mut labels = vec![];
+            for wallet in &wallets {
+                wallet_tags.extend(WalletTag::find(connection, &wallet.uuid)?);
+                wallet_addresses.extend(WalletAddress::find(connection, &wallet.uuid, None)?);
+                labels.extend(Label::find(
+                    connection,
+                    &Uuid::from_str(&wallet.uuid)?,
+                    None,
+                    None,
+                    true,
+                )?);
+            }
+
+            Ok(Self {
+                cosigners: Cosigner::find(connection, None, None, None, None, None, true)?,
+                wallet_cosigners: WalletCosigner::find(connection, None, None)?,
+                psbts: Psbt::find(connection, None, None, true)?,
+                wallets,
+                wallet_tags,
+                wallet_addresses,
+                labels,
+            })
+        })
+    }
+
+    /// Recreates the records with their original identifiers, timestamps and
+    /// archive stamps. Refuses to touch a database that already holds
+    /// cosigners or wallets.
+    pub fn restore(&self, connection: &mut DbConnection) -> Result<(), Box<dyn Error>> {
+        if !Wallet::find(connection, None, None, None, None, None, None, true)?.is_empty()
+            || !Cosigner::find(connection, None, None, None, None, None, true)?.is_empty()
+        {
+            return Err("the database already holds cosigners or wallets".into());
+        }
+
+        connection.transaction(|connection| {
+            for wallet in &self.wallets {
+                Wallet {
+                    uuid: wallet.uuid.clone(),
+                    address_type: wallet.address_type,
+                    network: wallet.network,
+                    receive_descriptor: wallet.receive_descriptor.expose(),
+                    receive_descriptor_watch_only: &wallet.receive_descriptor_watch_only,
+                    receive_address_index: wallet.receive_address_index,
+                    change_descriptor: wallet.change_descriptor.expose(),
+                    change_descriptor_watch_only: &wallet.change_descriptor_watch_only,
+                    change_address_index: wallet.change_address_index,
+                    required_signatures: wallet.required_signatures,
+                    balance: DecimalWrapper(wallet.balance.0),
+                    creation_time: wallet.creation_time,
+                    name: wallet.name.as_deref(),
+                    description: wallet.description.as_deref(),
+                }
+                .upsert(connection)?;
+            }
+
+            for wallet in &self.wallets {
+                if let Some(successor_uuid) = &wallet.successor_uuid {
+                    Wallet::set_successor(connection, &wallet.uuid, successor_uuid)?;
+                }
+            }
+
+            for cosigner in &self.cosigners {
+                Cosigner {
+                    uuid: cosigner.uuid.clone(),
+                    type_: cosigner.type_,
+                    email_address: cosigner.email_address.clone(),
+                    xpub: cosigner.xpub.clone(),
+                    xprv: cosigner.xprv.expose().as_deref(),
+                    creation_time: cosigner.creation_time,
+                    wallet_uuid: cosigner.wallet_uuid.clone(),
+                    name: cosigner.name.clone(),
+                    mnemonic: cosigner.mnemonic.clone(),
+                    mnemonic_has_passphrase: cosigner.mnemonic_has_passphrase,
+                    mnemonic_exported_at: cosigner.mnemonic_exported_at,
+                    origin: cosigner.origin.clone(),
+                }
+                .upsert(connection)?;
+            }
+
+            for link in &self.wallet_cosigners {
+                WalletCosigner::new(&link.wallet_uuid, &link.cosigner_uuid).insert(connection)?;
+            }
+
+            let mut tags = HashMap::<&str, Vec<String>>::new();
+            for tag in &self.wallet_tags {
+                tags.entry(&tag.wallet_uuid)
+                    .or_default()
+                    .push(tag.tag.clone());
+            }
+            for (wallet_uuid, tags) in tags {
+                WalletTag::replace(connection, wallet_uuid, &tags)?;
+            }
+
+            for address in &self.wallet_addresses {
+                WalletAddress {
+                    wallet_uuid: &address.wallet_uuid,
+                    address_index: address.address_index,
+                    address: address.address.clone(),
+                    used: address.used,
+                    creation_time: address.creation_time,
+                }
+                .insert(connection)?;
+            }
+
+            for psbt in &self.psbts {
+                Psbt {
+                    uuid: psbt.uuid.clone(),
+                    base64: &psbt.base64,
+                    creation_time: psbt.creation_time,
+                    wallet_uuid: psbt.wallet_uuid.clone(),
+                }
+                .upsert(connection)?;
+            }
+
+            for label in &self.labels {
+                Label {
+                    wallet_uuid: label.wallet_uuid.clone(),
+                    type_: label.type_,
+                    ref_: &label.ref_,
+                    label: &label.label,
+                    origin: label.origin.as_deref(),
+                    spendable: label.spendable,
+                    creation_time: label.creation_time,
+                    archived_at: label.archived_at,
+                    archived_by: label.archived_by.as_deref(),
+                }
+                .upsert(connection)?;
+            }
+
+            // Wallets go last, archiving only what isn't already archived on
+            // its own.
+            for cosigner in &self.cosigners {
+                if let (Some(archived_at), Some(archived_by)) =
+                    (cosigner.archived_at, &cosigner.archived_by)
+                {
+                    Cosigner::archive(connection, &cosigner.uuid, archived_at, archived_by)?;
+                }
+            }
+
+            for psbt in &self.psbts {
+                if let (Some(archived_at), Some(archived_by)) =
+                    (psbt.archived_at, &psbt.archived_by)
+                {
+                    Psbt::archive(connection, &psbt.uuid, archived_at, archived_by)?;
+                }
+            }
+
+            for wallet in &self.wallets {
+                if let (Some(archived_at), Some(archived_by)) =
+                    (wallet.archived_at, &wallet.archived_by)
+                {
+                    Wallet::archive(connection, &wallet.uuid, archived_at, archived_by)?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,160 @@
+use std::error::Error;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    backend, backend::Backend, deserialize, pg, serialize, sql_types, sqlite, ExpressionMethods,
+    OptionalExtension, QueryDsl, RunQueryDsl,
+};
+
+use super::{schema, schema::broadcast::dsl, DbConnection};
+
+#[repr(i16)]
+#[derive(AsExpression, Debug, Clone, Copy, PartialEq, Eq, FromSqlRow)]
+#[diesel(sql_type = sql_types::SmallInt)]
+pub enum BroadcastStatus {
+    Pending = 1,
+    Sent = 2,
+}
+
+impl serialize::ToSql<sql_types::SmallInt, sqlite::Sqlite> for BroadcastStatus {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut serialize::Output<'b, '_, sqlite::Sqlite>,
+    ) -> serialize::Result {
+        out.set_value(*self as i32);
+        Ok(serialize::IsNull::No)
+    }
+}
+
+impl serialize::ToSql<sql_types::SmallInt, pg::Pg> for BroadcastStatus {
+    fn to_sql<'b>(&'b self, out: &mut serialize::Output<'b, '_, pg::Pg>) -> serialize::Result {
+        <i16 as serialize::ToSql<sql_types::SmallInt, pg::Pg>>::to_sql(
+            &(*self as i16),
+            &mut out.reborrow(),
+        )
+    }
+}
+
+impl<DB> deserialize::FromSql<sql_types::SmallInt, DB> for BroadcastStatus
+where
+    DB: Backend,
+    i16: deserialize::FromSql<sql_types::SmallInt, DB>,
+{
+    fn from_sql(bytes: backend::RawValue<DB>) -> deserialize::Result<Self> {
+        match i16::from_sql(bytes)? {
+            1 => Ok(BroadcastStatus::Pending),
+            2 => Ok(BroadcastStatus::Sent),
+            x => Err(format!("Unrecognized broadcast status {}", x).into()),
+        }
+    }
+}
+
+#[derive(Identifiable, Queryable)]
+#[diesel(table_name = schema::broadcast)]
+pub struct BroadcastRecord {
+    pub id: i32,
+    pub txid: String,
+    pub wallet_uuid: String,
+    pub psbt_uuid: String,
+    pub raw_transaction: String,
+    pub status: BroadcastStatus,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub creation_time: NaiveDateTime,
+    pub broadcast_time: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::broadcast)]
+pub struct Broadcast<'a> {
+    pub txid: String,
+    pub wallet_uuid: &'a str,
+    pub psbt_uuid: &'a str,
+    pub raw_transaction: String,
+    pub status: BroadcastStatus,
+    pub creation_time: NaiveDateTime,
+}
+
+impl<'a> Broadcast<'a> {
+    pub fn new(
+        txid: String,
+        wallet_uuid: &'a str,
+        psbt_uuid: &'a str,
+        raw_transaction: String,
+    ) -> Self {
+        Self {
+            txid,
+            wallet_uuid,
+            psbt_uuid,
+            raw_transaction,
+            status: BroadcastStatus::Pending,
+            creation_time: Utc::now().naive_local(),
+        }
+    }
+
+    /// Records the broadcast unless one of the same transaction already is,
+    /// and returns whichever is on record.
+    pub fn insert(&self, connection: &mut DbConnection) -> Result<BroadcastRecord, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            diesel::insert_into(schema::broadcast::table)
+                .values(self)
+                .on_conflict_do_nothing()
+                .execute(connection)?;
+        });
+
+        Self::find(connection, &self.txid)?.ok_or_else(|| "broadcast could not be recorded".into())
+    }
+
+    pub fn find(
+        connection: &mut DbConnection,
+        txid: &str,
+    ) -> Result<Option<BroadcastRecord>, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(dsl::broadcast
+                .filter(schema::broadcast::txid.eq(txid))
+                .first::<BroadcastRecord>(connection)
+                .optional()?)
+        })
+    }
+
+    pub fn pending(connection: &mut DbConnection) -> Result<Vec<BroadcastRecord>, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(dsl::broadcast
+                .filter(schema::broadcast::status.eq(BroadcastStatus::Pending))
+                .order(schema::broadcast::id)
+                .load::<BroadcastRecord>(connection)?)
+        })
+    }
+
+    pub fn record_failure(
+        connection: &mut DbConnection,
+        txid: &str,
+        error: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(
+                diesel::update(dsl::broadcast.filter(schema::broadcast::txid.eq(txid)))
+                    .set((
+                        schema::broadcast::attempts.eq(schema::broadcast::attempts + 1),
+                        schema::broadcast::last_error.eq(error),
+                    ))
+                    .execute(connection)?,
+            )
+        })
+    }
+
+    pub fn mark_sent(connection: &mut DbConnection, txid: &str) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(
+                diesel::update(dsl::broadcast.filter(schema::broadcast::txid.eq(txid)))
+                    .set((
+                        schema::broadcast::status.eq(BroadcastStatus::Sent),
+                        schema::broadcast::attempts.eq(schema::broadcast::attempts + 1),
+                        schema::broadcast::last_error.eq(None::<String>),
+                        schema::broadcast::broadcast_time.eq(Utc::now().naive_local()),
+                    ))
+                    .execute(connection)?,
+            )
+        })
+    }
+}
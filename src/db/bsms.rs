@@ -0,0 +1,142 @@
+use std::error::Error;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{AsChangeset, ExpressionMethods, QueryDsl, RunQueryDsl};
+use uuid::Uuid;
+
+use super::{
+    schema,
+    schema::{bsms_key_record, bsms_session::dsl},
+    AddressType, DbConnection, Network,
+};
+
+#[derive(Identifiable, Queryable)]
+#[diesel(table_name = schema::bsms_session)]
+pub struct BsmsSessionRecord {
+    pub id: i32,
+    pub uuid: String,
+    pub token: String,
+    pub address_type: AddressType,
+    pub network: Network,
+    pub required_signatures: i16,
+    pub signers: i16,
+    pub wallet_uuid: Option<String>,
+    pub creation_time: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = schema::bsms_session)]
+pub struct BsmsSession<'a> {
+    pub uuid: String,
+    pub token: &'a str,
+    pub address_type: AddressType,
+    pub network: Network,
+    pub required_signatures: i16,
+    pub signers: i16,
+    pub wallet_uuid: Option<String>,
+    pub creation_time: NaiveDateTime,
+}
+
+#[derive(Identifiable, Queryable)]
+#[diesel(table_name = schema::bsms_key_record)]
+pub struct BsmsKeyRecordRecord {
+    pub id: i32,
+    pub session_uuid: String,
+    pub cosigner_uuid: String,
+    pub record: String,
+    pub creation_time: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::bsms_key_record)]
+pub struct BsmsKeyRecord<'a> {
+    pub session_uuid: String,
+    pub cosigner_uuid: String,
+    pub record: &'a str,
+    pub creation_time: NaiveDateTime,
+}
+
+impl<'a> BsmsSession<'a> {
+    pub fn new(
+        token: &'a str,
+        address_type: AddressType,
+        network: Network,
+        required_signatures: i16,
+        signers: i16,
+        wallet_uuid: Option<&Uuid>,
+    ) -> Self {
+        Self {
+            uuid: Uuid::new_v4().to_string(),
+            token,
+            address_type,
+            network,
+            required_signatures,
+            signers,
+            wallet_uuid: wallet_uuid.map(|uuid| uuid.to_string()),
+            creation_time: Utc::now().naive_local(),
+        }
+    }
+
+    pub fn upsert(
+        &self,
+        connection: &mut DbConnection,
+    ) -> Result<BsmsSessionRecord, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::insert_into(schema::bsms_session::table)
+                .values(self)
+                .on_conflict(dsl::uuid)
+                .do_update()
+                .set(self)
+                .get_result(connection)?)
+        })
+    }
+
+    pub fn find(
+        connection: &mut DbConnection,
+        uuid: Option<&Uuid>,
+    ) -> Result<Vec<BsmsSessionRecord>, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            let mut query = dsl::bsms_session.into_boxed();
+
+            if let Some(uuid) = uuid {
+                query = query.filter(schema::bsms_session::uuid.eq(uuid.to_string()));
+            }
+
+            Ok(query.load::<BsmsSessionRecord>(connection)?)
+        })
+    }
+}
+
+impl<'a> BsmsKeyRecord<'a> {
+    pub fn new(session_uuid: &Uuid, cosigner_uuid: &str, record: &'a str) -> Self {
+        Self {
+            session_uuid: session_uuid.to_string(),
+            cosigner_uuid: cosigner_uuid.to_string(),
+            record,
+            creation_time: Utc::now().naive_local(),
+        }
+    }
+
+    pub fn insert(
+        &self,
+        connection: &mut DbConnection,
+    ) -> Result<BsmsKeyRecordRecord, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::insert_into(schema::bsms_key_record::table)
+                .values(self)
+                .get_result(connection)?)
+        })
+    }
+
+    pub fn find(
+        connection: &mut DbConnection,
+        session_uuid: &Uuid,
+    ) -> Result<Vec<BsmsKeyRecordRecord>, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(bsms_key_record::dsl::bsms_key_record
+                .filter(bsms_key_record::session_uuid.eq(session_uuid.to_string()))
+                .order(bsms_key_record::id)
+                .load::<BsmsKeyRecordRecord>(connection)?)
+        })
+    }
+}
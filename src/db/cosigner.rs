@@ -3,16 +3,18 @@ use std::error::Error;
 use bdk::bitcoin::util::bip32;
 use chrono::{NaiveDateTime, Utc};
 use diesel::{
-    deserialize, serialize, sql_types, sqlite, AsChangeset, ExpressionMethods, QueryDsl,
-    RunQueryDsl, SqliteConnection,
+    backend, backend::Backend, deserialize, pg, serialize, sql_types, sqlite, AsChangeset,
+    ExpressionMethods, QueryDsl, RunQueryDsl, TextExpressionMethods,
 };
 use email_address::EmailAddress;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::{schema, schema::cosigner::dsl};
+use super::{schema, schema::cosigner::dsl, schema::wallet_cosigner, DbConnection};
+use crate::Secret;
 
 #[repr(i16)]
-#[derive(AsExpression, Debug, Clone, Copy, FromSqlRow)]
+#[derive(AsExpression, Debug, Clone, Copy, FromSqlRow, Deserialize, Serialize)]
 #[diesel(sql_type = sql_types::SmallInt)]
 pub enum CosignerType {
     Internal = 1,
@@ -29,9 +31,22 @@ impl serialize::ToSql<sql_types::SmallInt, sqlite::Sqlite> for CosignerType {
     }
 }
 
-impl deserialize::FromSql<sql_types::SmallInt, sqlite::Sqlite> for CosignerType {
-    fn from_sql(value: sqlite::SqliteValue) -> deserialize::Result<Self> {
-        match <i16 as deserialize::FromSql<sql_types::SmallInt, sqlite::Sqlite>>::from_sql(value)? {
+impl serialize::ToSql<sql_types::SmallInt, pg::Pg> for CosignerType {
+    fn to_sql<'b>(&'b self, out: &mut serialize::Output<'b, '_, pg::Pg>) -> serialize::Result {
+        <i16 as serialize::ToSql<sql_types::SmallInt, pg::Pg>>::to_sql(
+            &(*self as i16),
+            &mut out.reborrow(),
+        )
+    }
+}
+
+impl<DB> deserialize::FromSql<sql_types::SmallInt, DB> for CosignerType
+where
+    DB: Backend,
+    i16: deserialize::FromSql<sql_types::SmallInt, DB>,
+{
+    fn from_sql(value: backend::RawValue<DB>) -> deserialize::Result<Self> {
+        match i16::from_sql(value)? {
             1 => Ok(CosignerType::Internal),
             2 => Ok(CosignerType::External),
             x => Err(format!("Unrecognized cosigner type {}", x).into()),
@@ -39,7 +54,7 @@ impl deserialize::FromSql<sql_types::SmallInt, sqlite::Sqlite> for CosignerType
     }
 }
 
-#[derive(Identifiable, Queryable)]
+#[derive(Identifiable, Queryable, Deserialize, Serialize)]
 #[diesel(table_name = schema::cosigner)]
 pub struct CosignerRecord {
     pub id: i32,
@@ -47,86 +62,240 @@ pub struct CosignerRecord {
     pub type_: CosignerType,
     pub email_address: Option<String>,
     pub xpub: String,
-    pub xprv: Option<String>,
+    #[diesel(deserialize_as = Option<String>)]
+    pub xprv: Secret<Option<String>>,
     pub creation_time: NaiveDateTime,
     pub wallet_uuid: Option<String>,
+    pub name: Option<String>,
+    pub archived_at: Option<NaiveDateTime>,
+    pub archived_by: Option<String>,
+    // Absent from backups taken before mnemonics were kept.
+    #[serde(default)]
+    pub mnemonic: Option<String>,
+    #[serde(default)]
+    pub mnemonic_has_passphrase: bool,
+    #[serde(default)]
+    pub mnemonic_exported_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub origin: Option<String>,
 }
 
 #[derive(Insertable, AsChangeset)]
-#[diesel(table_name = schema::cosigner)]
-pub struct Cosigner {
+#[diesel(table_name = schema::cosigner, treat_none_as_null = true)]
+pub struct Cosigner<'a> {
     pub uuid: String,
     pub type_: CosignerType,
     pub email_address: Option<String>,
     pub xpub: String,
-    pub xprv: Option<String>,
+    pub xprv: Option<&'a str>,
     pub creation_time: NaiveDateTime,
     pub wallet_uuid: Option<String>,
+    pub name: Option<String>,
+    pub mnemonic: Option<String>,
+    pub mnemonic_has_passphrase: bool,
+    pub mnemonic_exported_at: Option<NaiveDateTime>,
+    pub origin: Option<String>,
+}
+
+#[derive(Identifiable, Queryable)]
+#[diesel(table_name = schema::key_export)]
+pub struct KeyExportRecord {
+    pub id: i32,
+    pub cosigner_uuid: Option<String>,
+    pub actor: String,
+    pub outcome: String,
+    pub creation_time: NaiveDateTime,
 }
 
-impl Cosigner {
+/// An attempt to export the key of an internal cosigner, or all of them in a
+/// backup, successful or not.
+#[derive(Insertable)]
+#[diesel(table_name = schema::key_export)]
+pub struct KeyExport<'a> {
+    pub cosigner_uuid: Option<&'a str>,
+    pub actor: &'a str,
+    pub outcome: &'a str,
+    pub creation_time: NaiveDateTime,
+}
+
+impl<'a> KeyExport<'a> {
+    pub fn new(cosigner_uuid: Option<&'a str>, actor: &'a str, outcome: &'a str) -> Self {
+        Self {
+            cosigner_uuid,
+            actor,
+            outcome,
+            creation_time: Utc::now().naive_local(),
+        }
+    }
+
+    pub fn insert(&self, connection: &mut DbConnection) -> Result<KeyExportRecord, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::insert_into(schema::key_export::table)
+                .values(self)
+                .get_result(connection)?)
+        })
+    }
+}
+
+impl<'a> Cosigner<'a> {
     pub fn new(
         type_: CosignerType,
         email_address: Option<&EmailAddress>,
-        xprv: Option<&bip32::ExtendedPrivKey>,
+        xprv: Option<&'a str>,
         xpub: &bip32::ExtendedPubKey,
         wallet_uuid: Option<&Uuid>,
+        name: Option<&str>,
     ) -> Self {
         Self {
             uuid: Uuid::new_v4().to_string(),
             type_,
             email_address: email_address.map(|email| email.to_string()),
-            xprv: xprv.map(|xprv| xprv.to_string()),
+            xprv,
             xpub: xpub.to_string(),
             creation_time: Utc::now().naive_local(),
             wallet_uuid: wallet_uuid.map(|uuid| uuid.to_string()),
+            name: name.map(|name| name.to_string()),
+            mnemonic: None,
+            mnemonic_has_passphrase: false,
+            mnemonic_exported_at: None,
+            origin: None,
         }
     }
 
-    pub fn upsert(
-        &self,
-        connection: &mut SqliteConnection,
-    ) -> Result<CosignerRecord, Box<dyn Error>> {
-        Ok(diesel::insert_into(schema::cosigner::table)
-            .values(self)
-            .on_conflict(dsl::uuid)
-            .do_update()
-            .set(self)
-            .get_result(connection)?)
+    pub fn upsert(&self, connection: &mut DbConnection) -> Result<CosignerRecord, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::insert_into(schema::cosigner::table)
+                .values(self)
+                .on_conflict(dsl::uuid)
+                .do_update()
+                .set(self)
+                .get_result(connection)?)
+        })
     }
 
     pub fn find(
-        connection: &mut SqliteConnection,
+        connection: &mut DbConnection,
         uuid: Option<&Uuid>,
         email_address: Option<&EmailAddress>,
         xpub: Option<&bip32::ExtendedPubKey>,
         wallet_uuid: Option<&Uuid>,
+        name: Option<&str>,
+        include_archived: bool,
     ) -> Result<Vec<CosignerRecord>, Box<dyn Error>> {
-        let mut query = dsl::cosigner.into_boxed();
+        with_connection!(connection, |connection| {
+            let mut query = dsl::cosigner.into_boxed();
 
-        if let Some(uuid) = uuid {
-            query = query.filter(schema::cosigner::uuid.eq(uuid.to_string()));
-        }
+            if let Some(uuid) = uuid {
+                query = query.filter(schema::cosigner::uuid.eq(uuid.to_string()));
+            }
 
-        if let Some(email_address) = email_address {
-            query = query.filter(schema::cosigner::email_address.eq(email_address.to_string()));
-        }
+            if let Some(email_address) = email_address {
+                query = query.filter(schema::cosigner::email_address.eq(email_address.to_string()));
+            }
 
-        if let Some(xpub) = xpub {
-            query = query.filter(schema::cosigner::xpub.eq(xpub.to_string()));
-        }
+            if let Some(xpub) = xpub {
+                query = query.filter(schema::cosigner::xpub.eq(xpub.to_string()));
+            }
+
+            if let Some(uuid) = wallet_uuid {
+                query = query.filter(schema::cosigner::wallet_uuid.eq(uuid.to_string()));
+            }
+
+            if let Some(name) = name {
+                query = query.filter(schema::cosigner::name.like(format!("%{}%", name)));
+            }
+
+            if !include_archived {
+                query = query.filter(schema::cosigner::archived_at.is_null());
+            }
+
+            Ok(query.load::<CosignerRecord>(connection)?)
+        })
+    }
 
-        if let Some(uuid) = wallet_uuid {
-            query = query.filter(schema::cosigner::wallet_uuid.eq(uuid.to_string()));
+    pub fn archive(
+        connection: &mut DbConnection,
+        uuid: &str,
+        archived_at: NaiveDateTime,
+        archived_by: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::update(
+                dsl::cosigner
+                    .filter(schema::cosigner::uuid.eq(uuid))
+                    .filter(schema::cosigner::archived_at.is_null()),
+            )
+            .set((
+                schema::cosigner::archived_at.eq(archived_at),
+                schema::cosigner::archived_by.eq(archived_by),
+            ))
+            .execute(connection)?)
+        })
+    }
+
+    /// Drops the stored mnemonic once it has been handed out.
+    pub fn mark_mnemonic_exported(
+        connection: &mut DbConnection,
+        uuid: &str,
+        exported_at: NaiveDateTime,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(
+                diesel::update(dsl::cosigner.filter(schema::cosigner::uuid.eq(uuid)))
+                    .set((
+                        schema::cosigner::mnemonic.eq(None::<String>),
+                        schema::cosigner::mnemonic_exported_at.eq(exported_at),
+                    ))
+                    .execute(connection)?,
+            )
+        })
+    }
+
+    pub fn restore(connection: &mut DbConnection, uuid: &str) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(
+                diesel::update(dsl::cosigner.filter(schema::cosigner::uuid.eq(uuid)))
+                    .set((
+                        schema::cosigner::archived_at.eq(None::<NaiveDateTime>),
+                        schema::cosigner::archived_by.eq(None::<String>),
+                    ))
+                    .execute(connection)?,
+            )
+        })
+    }
+
+    /// Removes the cosigners archived before `archived_before`.
+    pub fn purge(
+        connection: &mut DbConnection,
+        archived_before: NaiveDateTime,
+    ) -> Result<usize, Box<dyn Error>> {
+        let uuids: Vec<String> = with_connection!(connection, |connection| {
+            dsl::cosigner
+                .filter(schema::cosigner::archived_at.lt(archived_before))
+                .select(schema::cosigner::uuid)
+                .load(connection)?
+        });
+
+        let mut purged = 0;
+        for uuid in uuids {
+            purged += Self::remove(connection, &uuid)?;
         }
 
-        Ok(query.load::<CosignerRecord>(connection)?)
+        Ok(purged)
     }
 
-    pub fn remove(connection: &mut SqliteConnection, uuid: &str) -> Result<usize, Box<dyn Error>> {
-        Ok(
-            diesel::delete(dsl::cosigner.filter(schema::cosigner::uuid.eq(uuid.to_string())))
-                .execute(connection)?,
-        )
+    pub fn remove(connection: &mut DbConnection, uuid: &str) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            diesel::delete(
+                wallet_cosigner::dsl::wallet_cosigner
+                    .filter(wallet_cosigner::cosigner_uuid.eq(uuid)),
+            )
+            .execute(connection)?;
+
+            Ok(
+                diesel::delete(dsl::cosigner.filter(schema::cosigner::uuid.eq(uuid.to_string())))
+                    .execute(connection)?,
+            )
+        })
     }
 }
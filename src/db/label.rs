@@ -0,0 +1,233 @@
+use std::error::Error;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+    backend, backend::Backend, deserialize, pg, serialize, sql_types, sqlite, AsChangeset,
+    ExpressionMethods, QueryDsl, RunQueryDsl,
+};
+use int_enum::IntEnum;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{schema, schema::wallet_label::dsl, DbConnection};
+
+#[repr(i16)]
+#[derive(
+    AsExpression, Debug, Copy, Clone, PartialEq, Eq, FromSqlRow, IntEnum, Deserialize, Serialize,
+)]
+#[diesel(sql_type = sql_types::SmallInt)]
+pub enum LabelType {
+    Tx = 1,
+    Addr = 2,
+    Pubkey = 3,
+    Input = 4,
+    Output = 5,
+    Xpub = 6,
+}
+
+impl serialize::ToSql<sql_types::SmallInt, sqlite::Sqlite> for LabelType {
+    fn to_sql<'b>(
+        &'b self,
+        out: &mut serialize::Output<'b, '_, sqlite::Sqlite>,
+    ) -> serialize::Result {
+        out.set_value(*self as i32);
+        Ok(serialize::IsNull::No)
+    }
+}
+
+impl serialize::ToSql<sql_types::SmallInt, pg::Pg> for LabelType {
+    fn to_sql<'b>(&'b self, out: &mut serialize::Output<'b, '_, pg::Pg>) -> serialize::Result {
+        <i16 as serialize::ToSql<sql_types::SmallInt, pg::Pg>>::to_sql(
+            &(*self as i16),
+            &mut out.reborrow(),
+        )
+    }
+}
+
+impl<DB> deserialize::FromSql<sql_types::SmallInt, DB> for LabelType
+where
+    DB: Backend,
+    i16: deserialize::FromSql<sql_types::SmallInt, DB>,
+{
+    fn from_sql(bytes: backend::RawValue<DB>) -> deserialize::Result<Self> {
+        match i16::from_sql(bytes)? {
+            1 => Ok(LabelType::Tx),
+            2 => Ok(LabelType::Addr),
+            3 => Ok(LabelType::Pubkey),
+            4 => Ok(LabelType::Input),
+            5 => Ok(LabelType::Output),
+            6 => Ok(LabelType::Xpub),
+            x => Err(format!("Unrecognized label type {}", x).into()),
+        }
+    }
+}
+
+#[derive(Identifiable, Queryable, Deserialize, Serialize)]
+#[diesel(table_name = schema::wallet_label)]
+pub struct LabelRecord {
+    pub id: i32,
+    pub wallet_uuid: String,
+    pub type_: LabelType,
+    pub ref_: String,
+    pub label: String,
+    pub origin: Option<String>,
+    pub spendable: Option<bool>,
+    pub creation_time: NaiveDateTime,
+    pub archived_at: Option<NaiveDateTime>,
+    pub archived_by: Option<String>,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = schema::wallet_label, treat_none_as_null = true)]
+pub struct Label<'a> {
+    pub wallet_uuid: String,
+    pub type_: LabelType,
+    pub ref_: &'a str,
+    pub label: &'a str,
+    pub origin: Option<&'a str>,
+    pub spendable: Option<bool>,
+    pub creation_time: NaiveDateTime,
+    // Always unset, so that setting an archived label brings it back.
+    pub archived_at: Option<NaiveDateTime>,
+    pub archived_by: Option<&'a str>,
+}
+
+impl<'a> Label<'a> {
+    pub fn new(
+        wallet_uuid: &Uuid,
+        type_: LabelType,
+        ref_: &'a str,
+        label: &'a str,
+        origin: Option<&'a str>,
+        spendable: Option<bool>,
+    ) -> Self {
+        Self {
+            wallet_uuid: wallet_uuid.to_string(),
+            type_,
+            ref_,
+            label,
+            origin,
+            spendable,
+            creation_time: Utc::now().naive_local(),
+            archived_at: None,
+            archived_by: None,
+        }
+    }
+
+    pub fn upsert(&self, connection: &mut DbConnection) -> Result<LabelRecord, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::insert_into(schema::wallet_label::table)
+                .values(self)
+                .on_conflict((dsl::wallet_uuid, dsl::type_, dsl::ref_))
+                .do_update()
+                .set(self)
+                .get_result(connection)?)
+        })
+    }
+
+    pub fn find(
+        connection: &mut DbConnection,
+        wallet_uuid: &Uuid,
+        type_: Option<LabelType>,
+        ref_: Option<&str>,
+        include_archived: bool,
+    ) -> Result<Vec<LabelRecord>, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            let mut query = dsl::wallet_label
+                .filter(schema::wallet_label::wallet_uuid.eq(wallet_uuid.to_string()))
+                .into_boxed();
+
+            if let Some(type_) = type_ {
+                query = query.filter(schema::wallet_label::type_.eq(type_));
+            }
+
+            if let Some(ref_) = ref_ {
+                query = query.filter(schema::wallet_label::ref_.eq(ref_.to_string()));
+            }
+
+            if !include_archived {
+                query = query.filter(schema::wallet_label::archived_at.is_null());
+            }
+
+            Ok(query
+                .order(schema::wallet_label::id)
+                .load::<LabelRecord>(connection)?)
+        })
+    }
+
+    pub fn archive(
+        connection: &mut DbConnection,
+        wallet_uuid: &Uuid,
+        type_: LabelType,
+        ref_: &str,
+        archived_at: NaiveDateTime,
+        archived_by: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::update(
+                dsl::wallet_label
+                    .filter(schema::wallet_label::wallet_uuid.eq(wallet_uuid.to_string()))
+                    .filter(schema::wallet_label::type_.eq(type_))
+                    .filter(schema::wallet_label::ref_.eq(ref_.to_string()))
+                    .filter(schema::wallet_label::archived_at.is_null()),
+            )
+            .set((
+                schema::wallet_label::archived_at.eq(archived_at),
+                schema::wallet_label::archived_by.eq(archived_by),
+            ))
+            .execute(connection)?)
+        })
+    }
+
+    pub fn restore(
+        connection: &mut DbConnection,
+        wallet_uuid: &Uuid,
+        type_: LabelType,
+        ref_: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::update(
+                dsl::wallet_label
+                    .filter(schema::wallet_label::wallet_uuid.eq(wallet_uuid.to_string()))
+                    .filter(schema::wallet_label::type_.eq(type_))
+                    .filter(schema::wallet_label::ref_.eq(ref_.to_string()))
+                    .filter(schema::wallet_label::archived_at.is_not_null()),
+            )
+            .set((
+                schema::wallet_label::archived_at.eq(None::<NaiveDateTime>),
+                schema::wallet_label::archived_by.eq(None::<String>),
+            ))
+            .execute(connection)?)
+        })
+    }
+
+    /// Removes the labels archived before `archived_before`.
+    pub fn purge(
+        connection: &mut DbConnection,
+        archived_before: NaiveDateTime,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::delete(
+                dsl::wallet_label.filter(schema::wallet_label::archived_at.lt(archived_before)),
+            )
+            .execute(connection)?)
+        })
+    }
+
+    pub fn remove(
+        connection: &mut DbConnection,
+        wallet_uuid: &Uuid,
+        type_: LabelType,
+        ref_: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::delete(
+                dsl::wallet_label
+                    .filter(schema::wallet_label::wallet_uuid.eq(wallet_uuid.to_string()))
+                    .filter(schema::wallet_label::type_.eq(type_))
+                    .filter(schema::wallet_label::ref_.eq(ref_.to_string())),
+            )
+            .execute(connection)?)
+        })
+    }
+}
@@ -1,17 +1,282 @@
-use diesel::{Connection, SqliteConnection};
+use std::{collections::HashSet, error::Error, time::Duration};
+
+use chrono::NaiveDateTime;
+
+use diesel::{
+    backend::Backend,
+    connection::{AnsiTransactionManager, SimpleConnection, TransactionManager},
+    migration::{Migration, MigrationSource},
+    r2d2::{self, ConnectionManager, CustomizeConnection, Pool, PooledConnection},
+    PgConnection, SqliteConnection,
+};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+/// Runs `$body` with `$connection` bound to the backend specific connection
+/// behind a `DbConnection`, so queries are written once for all backends.
+macro_rules! with_connection {
+    ($db_connection:expr, |$connection:ident| $body:expr) => {
+        match $db_connection {
+            $crate::db::DbConnection::Sqlite($connection) => {
+                let $connection: &mut diesel::SqliteConnection = $connection;
+                $body
+            }
+            $crate::db::DbConnection::Postgres($connection) => {
+                let $connection: &mut diesel::PgConnection = $connection;
+                $body
+            }
+        }
+    };
+}
 
 #[rustfmt::skip]
 mod schema;
 
+mod backup;
+mod broadcast;
+mod bsms;
 mod cosigner;
+mod label;
 mod psbt;
 mod wallet;
 
-pub use cosigner::{Cosigner, CosignerRecord, CosignerType};
+pub use backup::Snapshot;
+pub use broadcast::{Broadcast, BroadcastRecord};
+pub use bsms::{BsmsKeyRecord, BsmsSession};
+pub use cosigner::{Cosigner, CosignerType, KeyExport};
+pub use label::{Label, LabelType};
 pub use psbt::Psbt;
-pub use wallet::{AddressType, Network, Wallet, WalletDescriptors, WalletRecord};
+pub use wallet::{
+    AddressType, Network, SecretDescriptors, Wallet, WalletAddress, WalletCosigner,
+    WalletDescriptors, WalletMetadata, WalletTag,
+};
+
+pub enum ConnectionPool {
+    Sqlite(Pool<ConnectionManager<SqliteConnection>>),
+    Postgres(Pool<ConnectionManager<PgConnection>>),
+}
+
+pub enum DbConnection {
+    Sqlite(PooledConnection<ConnectionManager<SqliteConnection>>),
+    Postgres(PooledConnection<ConnectionManager<PgConnection>>),
+}
+
+impl ConnectionPool {
+    pub fn get(&self) -> Result<DbConnection, r2d2::PoolError> {
+        Ok(match self {
+            ConnectionPool::Sqlite(pool) => DbConnection::Sqlite(pool.get()?),
+            ConnectionPool::Postgres(pool) => DbConnection::Postgres(pool.get()?),
+        })
+    }
+}
+
+impl DbConnection {
+    /// Runs `f` in a transaction, committing if it succeeds and rolling back
+    /// everything it did otherwise.
+    pub fn transaction<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        with_connection!(self, |connection| {
+            AnsiTransactionManager::begin_transaction(connection)
+        })?;
+
+        match f(self) {
+            Ok(value) => {
+                with_connection!(self, |connection| {
+                    AnsiTransactionManager::commit_transaction(connection)
+                })?;
+                Ok(value)
+            }
+            Err(err) => {
+                with_connection!(self, |connection| {
+                    AnsiTransactionManager::rollback_transaction(connection)
+                })?;
+                Err(err)
+            }
+        }
+    }
+}
+
+/// How long a connection waits for another one's write lock before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+struct ConnectionOptions;
+
+impl CustomizeConnection<SqliteConnection, r2d2::Error> for ConnectionOptions {
+    fn on_acquire(&self, connection: &mut SqliteConnection) -> Result<(), r2d2::Error> {
+        // WAL lets readers proceed while a write is in progress, and SQLite
+        // only enforces foreign keys when asked to.
+        connection
+            .batch_execute(&format!(
+                "PRAGMA journal_mode = WAL; PRAGMA busy_timeout = {}; PRAGMA foreign_keys = ON;",
+                BUSY_TIMEOUT.as_millis()
+            ))
+            .map_err(r2d2::Error::QueryError)
+    }
+}
+
+/// Connects to PostgreSQL for `postgres://` and `postgresql://` URLs, and to
+/// the SQLite database at the given path (optionally prefixed with
+/// `sqlite://`) otherwise.
+pub fn establish_pool(database_url: &str, max_size: u32) -> Result<ConnectionPool, Box<dyn Error>> {
+    let pool =
+        if database_url.starts_with("postgres://") || database_url.starts_with("postgresql://") {
+            Pool::builder()
+                .max_size(max_size)
+                .build(ConnectionManager::new(database_url))
+                .map(ConnectionPool::Postgres)
+        } else {
+            let path = database_url
+                .strip_prefix("sqlite://")
+                .unwrap_or(database_url);
+
+            Pool::builder()
+                .max_size(max_size)
+                .connection_customizer(Box::new(ConnectionOptions))
+                .build(ConnectionManager::new(path))
+                .map(ConnectionPool::Sqlite)
+        };
+
+    pool.map_err(|err| format!("Error connecting to {}: {}", database_url, err).into())
+}
+
+const SQLITE_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/sqlite");
+const POSTGRES_MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations/postgres");
+
+/// Brings the database schema up to date if `apply` is set, and otherwise
+/// only checks that it is. A schema with migrations unknown to this build was
+/// written by a newer version and is never touched.
+pub fn migrate(pool: &ConnectionPool, apply: bool) -> Result<(), Box<dyn Error>> {
+    match &mut pool.get()? {
+        DbConnection::Sqlite(connection) => {
+            run_migrations(&mut **connection, SQLITE_MIGRATIONS, apply)
+        }
+        DbConnection::Postgres(connection) => {
+            run_migrations(&mut **connection, POSTGRES_MIGRATIONS, apply)
+        }
+    }
+}
+
+/// Version diesel recorded for the initial migration while its directory was
+/// still called `initialize_schema`.
+const LEGACY_INITIAL_VERSION: &str = "initialize";
+
+/// Version of the initial migration since it was renamed to sort first.
+const INITIAL_VERSION: &str = "00000000000000";
+
+fn run_migrations<DB: Backend>(
+    connection: &mut (impl MigrationHarness<DB> + SimpleConnection),
+    migrations: EmbeddedMigrations,
+    apply: bool,
+) -> Result<(), Box<dyn Error>> {
+    let known = MigrationSource::<DB>::migrations(&migrations)
+        .map_err(|err| -> Box<dyn Error> { err })?
+        .iter()
+        .map(|migration| migration.name().version().as_owned())
+        .collect::<HashSet<_>>();
+
+    let mut applied = connection
+        .applied_migrations()
+        .map_err(|err| -> Box<dyn Error> { err })?;
+    if applied
+        .iter()
+        .any(|version| version.to_string() == LEGACY_INITIAL_VERSION)
+    {
+        // Same schema under its old name, which sorted after the others.
+        connection.batch_execute(&format!(
+            "UPDATE __diesel_schema_migrations SET version = '{}' WHERE version = '{}';",
+            INITIAL_VERSION, LEGACY_INITIAL_VERSION
+        ))?;
+        applied = connection
+            .applied_migrations()
+            .map_err(|err| -> Box<dyn Error> { err })?;
+    }
+
+    if let Some(version) = applied.into_iter().find(|version| !known.contains(version)) {
+        return Err(format!(
+            "database schema version {} is newer than this server supports",
+            version
+        )
+        .into());
+    }
+
+    let pending = connection
+        .pending_migrations(migrations)
+        .map_err(|err| -> Box<dyn Error> { err })?;
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    if !apply {
+        return Err(format!(
+            "database schema is {} migration(s) behind, start with --migrate to apply them",
+            pending.len()
+        )
+        .into());
+    }
+
+    for migration in pending {
+        connection
+            .run_migration(&*migration)
+            .map_err(|err| -> Box<dyn Error> { err })?;
+    }
+
+    Ok(())
+}
+
+/// Permanently deletes everything archived before `archived_before`, wallets
+/// first so that what was archived with them goes along. Returns the number
+/// of wallets, cosigners, PSBTs and labels deleted.
+pub fn purge(
+    connection: &mut DbConnection,
+    archived_before: NaiveDateTime,
+) -> Result<usize, Box<dyn Error>> {
+    connection.transaction(|connection| {
+        Ok(Wallet::purge(connection, archived_before)?
+            + Cosigner::purge(connection, archived_before)?
+            + Psbt::purge(connection, archived_before)?
+            + Label::purge(connection, archived_before)?)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process};
+
+    use super::*;
+
+    #[test]
+    fn migrate_baseline_schema() {
+        let path = std::env::temp_dir().join(format!("ohm-baseline-{}.sqlite", process::id()));
+        let _ = fs::remove_file(&path);
+        let pool = establish_pool(path.to_str().unwrap(), 1).unwrap();
+
+        // What `diesel migration run` left behind before the initial
+        // migration was renamed.
+        match &mut pool.get().unwrap() {
+            DbConnection::Sqlite(connection) => connection
+                .batch_execute(&format!(
+                    "{}
+                    CREATE TABLE __diesel_schema_migrations (
+                        version VARCHAR(50) PRIMARY KEY NOT NULL,
+                        run_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+                    );
+                    INSERT INTO __diesel_schema_migrations (version) VALUES ('initialize');",
+                    include_str!("../../migrations/sqlite/00000000000000_initialize_schema/up.sql")
+                ))
+                .unwrap(),
+            DbConnection::Postgres(_) => unreachable!(),
+        }
+
+        let err = migrate(&pool, false).unwrap_err();
+        assert!(err.to_string().contains("behind"), "{}", err);
+        migrate(&pool, true).unwrap();
+        migrate(&pool, false).unwrap();
 
-pub fn establish_connection(db_path: &str) -> SqliteConnection {
-    SqliteConnection::establish(db_path)
-        .unwrap_or_else(|_| panic!("Error connecting to {}", db_path))
+        drop(pool);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
 }
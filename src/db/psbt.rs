@@ -1,12 +1,13 @@
 use std::error::Error;
 
 use chrono::{NaiveDateTime, Utc};
-use diesel::{AsChangeset, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use diesel::{AsChangeset, ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::{schema, schema::psbt::dsl};
+use super::{schema, schema::psbt::dsl, DbConnection};
 
-#[derive(Identifiable, Queryable)]
+#[derive(Identifiable, Queryable, Deserialize, Serialize)]
 #[diesel(table_name = schema::psbt)]
 pub struct PsbtRecord {
     pub id: i32,
@@ -14,6 +15,8 @@ pub struct PsbtRecord {
     pub base64: String,
     pub creation_time: NaiveDateTime,
     pub wallet_uuid: String,
+    pub archived_at: Option<NaiveDateTime>,
+    pub archived_by: Option<String>,
 }
 
 #[derive(Insertable, AsChangeset)]
@@ -35,34 +38,94 @@ impl<'a> Psbt<'a> {
         }
     }
 
-    pub fn upsert(&self, connection: &mut SqliteConnection) -> Result<PsbtRecord, Box<dyn Error>> {
-        Ok(diesel::insert_into(schema::psbt::table)
-            .values(self)
-            .on_conflict(dsl::uuid)
-            .do_update()
-            .set(self)
-            .get_result(connection)?)
+    pub fn upsert(&self, connection: &mut DbConnection) -> Result<PsbtRecord, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::insert_into(schema::psbt::table)
+                .values(self)
+                .on_conflict(dsl::uuid)
+                .do_update()
+                .set(self)
+                .get_result(connection)?)
+        })
     }
 
     pub fn find(
-        connection: &mut SqliteConnection,
+        connection: &mut DbConnection,
         uuid: Option<&Uuid>,
         wallet_uuid: Option<&Uuid>,
+        include_archived: bool,
     ) -> Result<Vec<PsbtRecord>, Box<dyn Error>> {
-        let mut query = dsl::psbt.into_boxed();
+        with_connection!(connection, |connection| {
+            let mut query = dsl::psbt.into_boxed();
 
-        if let Some(uuid) = uuid {
-            query = query.filter(schema::psbt::uuid.eq(uuid.to_string()));
-        }
+            if let Some(uuid) = uuid {
+                query = query.filter(schema::psbt::uuid.eq(uuid.to_string()));
+            }
 
-        if let Some(wallet_uuid) = wallet_uuid {
-            query = query.filter(schema::psbt::wallet_uuid.eq(wallet_uuid.to_string()));
-        }
+            if let Some(wallet_uuid) = wallet_uuid {
+                query = query.filter(schema::psbt::wallet_uuid.eq(wallet_uuid.to_string()));
+            }
+
+            if !include_archived {
+                query = query.filter(schema::psbt::archived_at.is_null());
+            }
+
+            Ok(query.load::<PsbtRecord>(connection)?)
+        })
+    }
+
+    pub fn archive(
+        connection: &mut DbConnection,
+        uuid: &str,
+        archived_at: NaiveDateTime,
+        archived_by: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::update(
+                dsl::psbt
+                    .filter(schema::psbt::uuid.eq(uuid))
+                    .filter(schema::psbt::archived_at.is_null()),
+            )
+            .set((
+                schema::psbt::archived_at.eq(archived_at),
+                schema::psbt::archived_by.eq(archived_by),
+            ))
+            .execute(connection)?)
+        })
+    }
+
+    pub fn restore(connection: &mut DbConnection, uuid: &str) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(
+                diesel::update(dsl::psbt.filter(schema::psbt::uuid.eq(uuid)))
+                    .set((
+                        schema::psbt::archived_at.eq(None::<NaiveDateTime>),
+                        schema::psbt::archived_by.eq(None::<String>),
+                    ))
+                    .execute(connection)?,
+            )
+        })
+    }
 
-        Ok(query.load::<PsbtRecord>(connection)?)
+    /// Removes the PSBTs archived before `archived_before`.
+    pub fn purge(
+        connection: &mut DbConnection,
+        archived_before: NaiveDateTime,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(
+                diesel::delete(dsl::psbt.filter(schema::psbt::archived_at.lt(archived_before)))
+                    .execute(connection)?,
+            )
+        })
     }
 
-    pub fn remove(connection: &mut SqliteConnection, uuid: &str) -> Result<usize, Box<dyn Error>> {
-        Ok(diesel::delete(dsl::psbt.filter(schema::psbt::uuid.eq(uuid))).execute(connection)?)
+    pub fn remove(connection: &mut DbConnection, uuid: &str) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(
+                diesel::delete(dsl::psbt.filter(schema::psbt::uuid.eq(uuid)))
+                    .execute(connection)?,
+            )
+        })
     }
 }
@@ -1,5 +1,44 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    broadcast (id) {
+        id -> Integer,
+        txid -> Text,
+        wallet_uuid -> Text,
+        psbt_uuid -> Text,
+        raw_transaction -> Text,
+        status -> SmallInt,
+        attempts -> Integer,
+        last_error -> Nullable<Text>,
+        creation_time -> Timestamp,
+        broadcast_time -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    bsms_key_record (id) {
+        id -> Integer,
+        session_uuid -> Text,
+        cosigner_uuid -> Text,
+        record -> Text,
+        creation_time -> Timestamp,
+    }
+}
+
+diesel::table! {
+    bsms_session (id) {
+        id -> Integer,
+        uuid -> Text,
+        token -> Text,
+        address_type -> SmallInt,
+        network -> SmallInt,
+        required_signatures -> SmallInt,
+        signers -> SmallInt,
+        wallet_uuid -> Nullable<Text>,
+        creation_time -> Timestamp,
+    }
+}
+
 diesel::table! {
     cosigner (id) {
         id -> Integer,
@@ -11,6 +50,23 @@ diesel::table! {
         xprv -> Nullable<Text>,
         creation_time -> Timestamp,
         wallet_uuid -> Nullable<Text>,
+        name -> Nullable<Text>,
+        archived_at -> Nullable<Timestamp>,
+        archived_by -> Nullable<Text>,
+        mnemonic -> Nullable<Text>,
+        mnemonic_has_passphrase -> Bool,
+        mnemonic_exported_at -> Nullable<Timestamp>,
+        origin -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    key_export (id) {
+        id -> Integer,
+        cosigner_uuid -> Nullable<Text>,
+        actor -> Text,
+        outcome -> Text,
+        creation_time -> Timestamp,
     }
 }
 
@@ -21,6 +77,8 @@ diesel::table! {
         base64 -> Text,
         creation_time -> Timestamp,
         wallet_uuid -> Text,
+        archived_at -> Nullable<Timestamp>,
+        archived_by -> Nullable<Text>,
     }
 }
 
@@ -41,11 +99,68 @@ diesel::table! {
         required_signatures -> SmallInt,
         balance -> Text,
         creation_time -> Timestamp,
+        name -> Nullable<Text>,
+        description -> Nullable<Text>,
+        archived_at -> Nullable<Timestamp>,
+        archived_by -> Nullable<Text>,
+        successor_uuid -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    wallet_address (id) {
+        id -> Integer,
+        wallet_uuid -> Text,
+        address_index -> BigInt,
+        address -> Text,
+        used -> Bool,
+        creation_time -> Timestamp,
+    }
+}
+
+diesel::table! {
+    wallet_cosigner (id) {
+        id -> Integer,
+        wallet_uuid -> Text,
+        cosigner_uuid -> Text,
+    }
+}
+
+diesel::table! {
+    wallet_label (id) {
+        id -> Integer,
+        wallet_uuid -> Text,
+        #[sql_name = "type"]
+        type_ -> SmallInt,
+        #[sql_name = "ref"]
+        ref_ -> Text,
+        label -> Text,
+        origin -> Nullable<Text>,
+        spendable -> Nullable<Bool>,
+        creation_time -> Timestamp,
+        archived_at -> Nullable<Timestamp>,
+        archived_by -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    wallet_tag (id) {
+        id -> Integer,
+        wallet_uuid -> Text,
+        tag -> Text,
     }
 }
 
 diesel::allow_tables_to_appear_in_same_query!(
+    broadcast,
+    bsms_key_record,
+    bsms_session,
     cosigner,
+    key_export,
     psbt,
     wallet,
+    wallet_address,
+    wallet_cosigner,
+    wallet_label,
+    wallet_tag,
 );
@@ -1,22 +1,28 @@
-use std::{error::Error, fmt, str::FromStr};
+use std::{error::Error, fmt, io::Write, str::FromStr};
 
-use bdk::{bitcoin, Balance};
+use bdk::bitcoin;
 use chrono::{NaiveDateTime, Utc};
 use diesel::{
-    deserialize, serialize, sql_types, sqlite, AsChangeset, ExpressionMethods, QueryDsl,
-    RunQueryDsl, SqliteConnection,
+    backend, backend::Backend, deserialize, pg, serialize, sql_types, sqlite, AsChangeset,
+    ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, TextExpressionMethods,
 };
 use int_enum::IntEnum;
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use super::{
     schema,
-    schema::{cosigner::dsl::cosigner, psbt::dsl::psbt, wallet::dsl},
+    schema::{
+        cosigner::dsl::cosigner, psbt::dsl::psbt, wallet::dsl, wallet_address, wallet_cosigner,
+        wallet_label, wallet_tag,
+    },
+    DbConnection,
 };
+use crate::Secret;
 
 #[repr(i16)]
-#[derive(AsExpression, Debug, Copy, Clone, FromSqlRow, IntEnum)]
+#[derive(AsExpression, Debug, Copy, Clone, FromSqlRow, IntEnum, Deserialize, Serialize)]
 #[diesel(sql_type = sql_types::SmallInt)]
 pub enum AddressType {
     P2sh = 1,
@@ -34,9 +40,22 @@ impl serialize::ToSql<sql_types::SmallInt, sqlite::Sqlite> for AddressType {
     }
 }
 
-impl deserialize::FromSql<sql_types::SmallInt, sqlite::Sqlite> for AddressType {
-    fn from_sql(bytes: sqlite::SqliteValue) -> deserialize::Result<Self> {
-        match <i16 as deserialize::FromSql<sql_types::SmallInt, sqlite::Sqlite>>::from_sql(bytes)? {
+impl serialize::ToSql<sql_types::SmallInt, pg::Pg> for AddressType {
+    fn to_sql<'b>(&'b self, out: &mut serialize::Output<'b, '_, pg::Pg>) -> serialize::Result {
+        <i16 as serialize::ToSql<sql_types::SmallInt, pg::Pg>>::to_sql(
+            &(*self as i16),
+            &mut out.reborrow(),
+        )
+    }
+}
+
+impl<DB> deserialize::FromSql<sql_types::SmallInt, DB> for AddressType
+where
+    DB: Backend,
+    i16: deserialize::FromSql<sql_types::SmallInt, DB>,
+{
+    fn from_sql(bytes: backend::RawValue<DB>) -> deserialize::Result<Self> {
+        match i16::from_sql(bytes)? {
             1 => Ok(AddressType::P2sh),
             2 => Ok(AddressType::P2wsh),
             3 => Ok(AddressType::P2shwsh),
@@ -46,12 +65,13 @@ impl deserialize::FromSql<sql_types::SmallInt, sqlite::Sqlite> for AddressType {
 }
 
 #[repr(i16)]
-#[derive(AsExpression, Debug, Copy, Clone, FromSqlRow, IntEnum)]
+#[derive(AsExpression, Debug, Copy, Clone, FromSqlRow, IntEnum, Deserialize, Serialize)]
 #[diesel(sql_type = sql_types::SmallInt)]
 pub enum Network {
     Regtest = 1,
     Testnet = 2,
     Mainnet = 3,
+    Signet = 4,
 }
 
 impl serialize::ToSql<sql_types::SmallInt, sqlite::Sqlite> for Network {
@@ -64,13 +84,27 @@ impl serialize::ToSql<sql_types::SmallInt, sqlite::Sqlite> for Network {
     }
 }
 
-impl deserialize::FromSql<sql_types::SmallInt, sqlite::Sqlite> for Network {
-    fn from_sql(bytes: sqlite::SqliteValue) -> deserialize::Result<Self> {
-        match <i16 as deserialize::FromSql<sql_types::SmallInt, sqlite::Sqlite>>::from_sql(bytes)? {
+impl serialize::ToSql<sql_types::SmallInt, pg::Pg> for Network {
+    fn to_sql<'b>(&'b self, out: &mut serialize::Output<'b, '_, pg::Pg>) -> serialize::Result {
+        <i16 as serialize::ToSql<sql_types::SmallInt, pg::Pg>>::to_sql(
+            &(*self as i16),
+            &mut out.reborrow(),
+        )
+    }
+}
+
+impl<DB> deserialize::FromSql<sql_types::SmallInt, DB> for Network
+where
+    DB: Backend,
+    i16: deserialize::FromSql<sql_types::SmallInt, DB>,
+{
+    fn from_sql(bytes: backend::RawValue<DB>) -> deserialize::Result<Self> {
+        match i16::from_sql(bytes)? {
             1 => Ok(Network::Regtest),
             2 => Ok(Network::Testnet),
             3 => Ok(Network::Mainnet),
-            x => Err(format!("Unrecognized address type {}", x).into()),
+            4 => Ok(Network::Signet),
+            x => Err(format!("Unrecognized network {}", x).into()),
         }
     }
 }
@@ -81,11 +115,23 @@ impl From<Network> for bitcoin::Network {
             Network::Regtest => Self::Regtest,
             Network::Testnet => Self::Testnet,
             Network::Mainnet => Self::Bitcoin,
+            Network::Signet => Self::Signet,
+        }
+    }
+}
+
+impl From<bitcoin::Network> for Network {
+    fn from(network: bitcoin::Network) -> Self {
+        match network {
+            bitcoin::Network::Regtest => Self::Regtest,
+            bitcoin::Network::Testnet => Self::Testnet,
+            bitcoin::Network::Bitcoin => Self::Mainnet,
+            bitcoin::Network::Signet => Self::Signet,
         }
     }
 }
 
-#[derive(AsExpression, Debug, FromSqlRow)]
+#[derive(AsExpression, Debug, FromSqlRow, Deserialize, Serialize)]
 #[diesel(sql_type = sql_types::Text)]
 pub struct DecimalWrapper(pub Decimal);
 
@@ -105,45 +151,73 @@ impl serialize::ToSql<sql_types::Text, sqlite::Sqlite> for DecimalWrapper {
     }
 }
 
-impl deserialize::FromSql<sql_types::Text, sqlite::Sqlite> for DecimalWrapper {
-    fn from_sql(bytes: sqlite::SqliteValue) -> deserialize::Result<Self> {
-        let decimal = Decimal::from_str(&<String as deserialize::FromSql<
-            sql_types::Text,
-            sqlite::Sqlite,
-        >>::from_sql(bytes)?)?;
+impl serialize::ToSql<sql_types::Text, pg::Pg> for DecimalWrapper {
+    fn to_sql<'b>(&'b self, out: &mut serialize::Output<'b, '_, pg::Pg>) -> serialize::Result {
+        write!(out, "{}", self.0)?;
+        Ok(serialize::IsNull::No)
+    }
+}
+
+impl<DB> deserialize::FromSql<sql_types::Text, DB> for DecimalWrapper
+where
+    DB: Backend,
+    String: deserialize::FromSql<sql_types::Text, DB>,
+{
+    fn from_sql(bytes: backend::RawValue<DB>) -> deserialize::Result<Self> {
+        let decimal = Decimal::from_str(&String::from_sql(bytes)?)?;
         Ok(DecimalWrapper(decimal))
     }
 }
 
 pub struct WalletDescriptors {
-    pub receive_descriptor: String,
     pub receive_descriptor_watch_only: String,
-    pub change_descriptor: String,
     pub change_descriptor_watch_only: String,
 }
 
-#[derive(Debug, Queryable, Identifiable)]
+/// Descriptors holding the private key of the internal cosigner, only built
+/// for as long as they're being written.
+pub struct SecretDescriptors {
+    pub receive_descriptor: Secret<String>,
+    pub change_descriptor: Secret<String>,
+}
+
+#[derive(Default)]
+pub struct WalletMetadata {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Queryable, Identifiable, Deserialize, Serialize)]
 #[diesel(table_name = schema::wallet)]
 pub struct WalletRecord {
     pub id: i32,
     pub uuid: String,
     pub address_type: AddressType,
     pub network: Network,
-    pub receive_descriptor: String,
+    #[diesel(deserialize_as = String)]
+    pub receive_descriptor: Secret<String>,
     pub receive_descriptor_watch_only: String,
     pub receive_address_index: i64,
     pub receive_address: String,
-    pub change_descriptor: String,
+    #[diesel(deserialize_as = String)]
+    pub change_descriptor: Secret<String>,
     pub change_descriptor_watch_only: String,
     pub change_address_index: i64,
     pub change_address: String,
     pub required_signatures: i16,
     pub balance: DecimalWrapper,
     pub creation_time: NaiveDateTime,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub archived_at: Option<NaiveDateTime>,
+    pub archived_by: Option<String>,
+    #[serde(default)]
+    pub successor_uuid: Option<String>,
 }
 
 #[derive(Insertable, AsChangeset)]
-#[diesel(table_name = schema::wallet)]
+#[diesel(table_name = schema::wallet, treat_none_as_null = true)]
 pub struct Wallet<'a> {
     pub uuid: String,
     pub address_type: AddressType,
@@ -157,83 +231,459 @@ pub struct Wallet<'a> {
     pub required_signatures: i16,
     pub balance: DecimalWrapper,
     pub creation_time: NaiveDateTime,
+    pub name: Option<&'a str>,
+    pub description: Option<&'a str>,
+}
+
+#[derive(Identifiable, Queryable, Deserialize, Serialize)]
+#[diesel(table_name = schema::wallet_tag)]
+pub struct WalletTagRecord {
+    pub id: i32,
+    pub wallet_uuid: String,
+    pub tag: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::wallet_tag)]
+pub struct WalletTag<'a> {
+    pub wallet_uuid: &'a str,
+    pub tag: &'a str,
+}
+
+#[derive(Identifiable, Queryable, Deserialize, Serialize)]
+#[diesel(table_name = schema::wallet_cosigner)]
+pub struct WalletCosignerRecord {
+    pub id: i32,
+    pub wallet_uuid: String,
+    pub cosigner_uuid: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::wallet_cosigner)]
+pub struct WalletCosigner<'a> {
+    pub wallet_uuid: &'a str,
+    pub cosigner_uuid: &'a str,
+}
+
+#[derive(Identifiable, Queryable, Deserialize, Serialize)]
+#[diesel(table_name = schema::wallet_address)]
+pub struct WalletAddressRecord {
+    pub id: i32,
+    pub wallet_uuid: String,
+    pub address_index: i64,
+    pub address: String,
+    pub used: bool,
+    pub creation_time: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = schema::wallet_address)]
+pub struct WalletAddress<'a> {
+    pub wallet_uuid: &'a str,
+    pub address_index: i64,
+    pub address: String,
+    pub used: bool,
+    pub creation_time: NaiveDateTime,
+}
+
+impl<'a> WalletAddress<'a> {
+    pub fn new(wallet_uuid: &'a str, address_index: i64, address: String) -> Self {
+        Self {
+            wallet_uuid,
+            address_index,
+            address,
+            used: false,
+            creation_time: Utc::now().naive_local(),
+        }
+    }
+
+    pub fn insert(
+        &self,
+        connection: &mut DbConnection,
+    ) -> Result<WalletAddressRecord, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::insert_into(schema::wallet_address::table)
+                .values(self)
+                .get_result(connection)?)
+        })
+    }
+
+    pub fn find(
+        connection: &mut DbConnection,
+        wallet_uuid: &str,
+        used: Option<bool>,
+    ) -> Result<Vec<WalletAddressRecord>, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            let mut query = wallet_address::dsl::wallet_address
+                .filter(wallet_address::wallet_uuid.eq(wallet_uuid))
+                .into_boxed();
+
+            if let Some(used) = used {
+                query = query.filter(wallet_address::used.eq(used));
+            }
+
+            Ok(query
+                .order(wallet_address::address_index)
+                .load::<WalletAddressRecord>(connection)?)
+        })
+    }
+
+    pub fn mark_used(
+        connection: &mut DbConnection,
+        wallet_uuid: &str,
+        addresses: &[String],
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::update(
+                wallet_address::dsl::wallet_address
+                    .filter(wallet_address::wallet_uuid.eq(wallet_uuid))
+                    .filter(wallet_address::address.eq_any(addresses)),
+            )
+            .set(wallet_address::used.eq(true))
+            .execute(connection)?)
+        })
+    }
+}
+
+impl<'a> WalletCosigner<'a> {
+    pub fn new(wallet_uuid: &'a str, cosigner_uuid: &'a str) -> Self {
+        Self {
+            wallet_uuid,
+            cosigner_uuid,
+        }
+    }
+
+    pub fn insert(
+        &self,
+        connection: &mut DbConnection,
+    ) -> Result<WalletCosignerRecord, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::insert_into(schema::wallet_cosigner::table)
+                .values(self)
+                .get_result(connection)?)
+        })
+    }
+
+    pub fn find(
+        connection: &mut DbConnection,
+        wallet_uuid: Option<&str>,
+        cosigner_uuid: Option<&str>,
+    ) -> Result<Vec<WalletCosignerRecord>, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            let mut query = wallet_cosigner::dsl::wallet_cosigner.into_boxed();
+
+            if let Some(wallet_uuid) = wallet_uuid {
+                query = query.filter(wallet_cosigner::wallet_uuid.eq(wallet_uuid));
+            }
+
+            if let Some(cosigner_uuid) = cosigner_uuid {
+                query = query.filter(wallet_cosigner::cosigner_uuid.eq(cosigner_uuid));
+            }
+
+            Ok(query
+                .order(wallet_cosigner::id)
+                .load::<WalletCosignerRecord>(connection)?)
+        })
+    }
+}
+
+impl<'a> WalletTag<'a> {
+    pub fn find(
+        connection: &mut DbConnection,
+        wallet_uuid: &str,
+    ) -> Result<Vec<WalletTagRecord>, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(wallet_tag::dsl::wallet_tag
+                .filter(wallet_tag::wallet_uuid.eq(wallet_uuid))
+                .order(wallet_tag::tag)
+                .load::<WalletTagRecord>(connection)?)
+        })
+    }
+
+    pub fn replace(
+        connection: &mut DbConnection,
+        wallet_uuid: &str,
+        tags: &[String],
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            diesel::delete(
+                wallet_tag::dsl::wallet_tag.filter(wallet_tag::wallet_uuid.eq(wallet_uuid)),
+            )
+            .execute(connection)?;
+
+            let mut inserted = 0;
+            for tag in tags {
+                inserted += diesel::insert_into(schema::wallet_tag::table)
+                    .values(WalletTag {
+                        wallet_uuid,
+                        tag: tag.as_str(),
+                    })
+                    .on_conflict_do_nothing()
+                    .execute(connection)?;
+            }
+
+            Ok(inserted)
+        })
+    }
 }
 
 impl<'a> Wallet<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         address_type: AddressType,
         network: Network,
         required_signatures: i16,
-        balance: &Balance,
+        balance: Decimal,
         descriptors: &'a WalletDescriptors,
+        secret_descriptors: &'a SecretDescriptors,
         receive_address_index: i64,
         change_address_index: i64,
+        metadata: &'a WalletMetadata,
     ) -> Self {
         Self {
             uuid: Uuid::new_v4().to_string(),
             address_type,
             network,
-            receive_descriptor: &descriptors.receive_descriptor,
+            receive_descriptor: secret_descriptors.receive_descriptor.expose(),
             receive_descriptor_watch_only: &descriptors.receive_descriptor_watch_only,
             receive_address_index,
-            change_descriptor: &descriptors.change_descriptor,
+            change_descriptor: secret_descriptors.change_descriptor.expose(),
             change_descriptor_watch_only: &descriptors.change_descriptor_watch_only,
             change_address_index,
             required_signatures,
-            balance: DecimalWrapper(Decimal::from(balance.confirmed)),
+            balance: DecimalWrapper(balance),
             creation_time: Utc::now().naive_local(),
+            name: metadata.name.as_deref(),
+            description: metadata.description.as_deref(),
         }
     }
 
-    pub fn upsert(
-        &self,
-        connection: &mut SqliteConnection,
-    ) -> Result<WalletRecord, Box<dyn Error>> {
-        Ok(diesel::insert_into(schema::wallet::table)
-            .values(self)
-            .on_conflict(dsl::uuid)
-            .do_update()
-            .set(self)
-            .get_result(connection)?)
+    pub fn upsert(&self, connection: &mut DbConnection) -> Result<WalletRecord, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(diesel::insert_into(schema::wallet::table)
+                .values(self)
+                .on_conflict(dsl::uuid)
+                .do_update()
+                .set(self)
+                .get_result(connection)?)
+        })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn find(
-        connection: &mut SqliteConnection,
+        connection: &mut DbConnection,
         uuid: Option<&Uuid>,
         address_type: Option<AddressType>,
         network: Option<Network>,
         receive_descriptor: Option<&str>,
+        name: Option<&str>,
+        tag: Option<&str>,
+        include_archived: bool,
     ) -> Result<Vec<WalletRecord>, Box<dyn Error>> {
-        let mut query = dsl::wallet.into_boxed();
+        with_connection!(connection, |connection| {
+            let mut query = dsl::wallet.into_boxed();
 
-        if let Some(uuid) = uuid {
-            query = query.filter(schema::wallet::uuid.eq(uuid.to_string()));
-        }
+            if let Some(uuid) = uuid {
+                query = query.filter(schema::wallet::uuid.eq(uuid.to_string()));
+            }
 
-        if let Some(address_type) = address_type {
-            query = query.filter(schema::wallet::address_type.eq(address_type));
-        }
+            if let Some(address_type) = address_type {
+                query = query.filter(schema::wallet::address_type.eq(address_type));
+            }
 
-        if let Some(network) = network {
-            query = query.filter(schema::wallet::network.eq(network));
-        }
+            if let Some(network) = network {
+                query = query.filter(schema::wallet::network.eq(network));
+            }
+
+            if let Some(descriptor) = receive_descriptor {
+                query = query.filter(schema::wallet::receive_descriptor_watch_only.eq(descriptor));
+            }
+
+            if let Some(name) = name {
+                query = query.filter(schema::wallet::name.like(format!("%{}%", name)));
+            }
+
+            if let Some(tag) = tag {
+                query = query.filter(
+                    schema::wallet::uuid.eq_any(
+                        wallet_tag::dsl::wallet_tag
+                            .filter(wallet_tag::tag.eq(tag.to_string()))
+                            .select(wallet_tag::wallet_uuid),
+                    ),
+                );
+            }
+
+            if !include_archived {
+                query = query.filter(schema::wallet::archived_at.is_null());
+            }
+
+            Ok(query.load::<WalletRecord>(connection)?)
+        })
+    }
+
+    pub fn set_receive_address_index(
+        connection: &mut DbConnection,
+        uuid: &str,
+        receive_address_index: i64,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(
+                diesel::update(dsl::wallet.filter(schema::wallet::uuid.eq(uuid)))
+                    .set(schema::wallet::receive_address_index.eq(receive_address_index))
+                    .execute(connection)?,
+            )
+        })
+    }
 
-        if let Some(descriptor) = receive_descriptor {
-            query = query.filter(schema::wallet::receive_descriptor_watch_only.eq(descriptor));
+    /// Links the wallet to the one that took over from it.
+    pub fn set_successor(
+        connection: &mut DbConnection,
+        uuid: &str,
+        successor_uuid: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            Ok(
+                diesel::update(dsl::wallet.filter(schema::wallet::uuid.eq(uuid)))
+                    .set(schema::wallet::successor_uuid.eq(successor_uuid))
+                    .execute(connection)?,
+            )
+        })
+    }
+
+    /// Archives the wallet along with its internal cosigner and PSBTs, all
+    /// stamped with the same time so that they are restored together.
+    pub fn archive(
+        connection: &mut DbConnection,
+        uuid: &str,
+        archived_at: NaiveDateTime,
+        archived_by: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            diesel::update(
+                cosigner
+                    .filter(schema::cosigner::wallet_uuid.eq(uuid))
+                    .filter(schema::cosigner::archived_at.is_null()),
+            )
+            .set((
+                schema::cosigner::archived_at.eq(archived_at),
+                schema::cosigner::archived_by.eq(archived_by),
+            ))
+            .execute(connection)?;
+            diesel::update(
+                psbt.filter(schema::psbt::wallet_uuid.eq(uuid))
+                    .filter(schema::psbt::archived_at.is_null()),
+            )
+            .set((
+                schema::psbt::archived_at.eq(archived_at),
+                schema::psbt::archived_by.eq(archived_by),
+            ))
+            .execute(connection)?;
+
+            Ok(diesel::update(
+                dsl::wallet
+                    .filter(schema::wallet::uuid.eq(uuid))
+                    .filter(schema::wallet::archived_at.is_null()),
+            )
+            .set((
+                schema::wallet::archived_at.eq(archived_at),
+                schema::wallet::archived_by.eq(archived_by),
+            ))
+            .execute(connection)?)
+        })
+    }
+
+    /// Restores the wallet along with what was archived with it; PSBTs
+    /// archived on their own before stay archived.
+    pub fn restore(connection: &mut DbConnection, uuid: &str) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            let archived_at = match dsl::wallet
+                .filter(schema::wallet::uuid.eq(uuid))
+                .select(schema::wallet::archived_at)
+                .first::<Option<NaiveDateTime>>(connection)
+                .optional()?
+                .flatten()
+            {
+                Some(archived_at) => archived_at,
+                None => return Ok(0),
+            };
+
+            diesel::update(
+                cosigner
+                    .filter(schema::cosigner::wallet_uuid.eq(uuid))
+                    .filter(schema::cosigner::archived_at.eq(archived_at)),
+            )
+            .set((
+                schema::cosigner::archived_at.eq(None::<NaiveDateTime>),
+                schema::cosigner::archived_by.eq(None::<String>),
+            ))
+            .execute(connection)?;
+            diesel::update(
+                psbt.filter(schema::psbt::wallet_uuid.eq(uuid))
+                    .filter(schema::psbt::archived_at.eq(archived_at)),
+            )
+            .set((
+                schema::psbt::archived_at.eq(None::<NaiveDateTime>),
+                schema::psbt::archived_by.eq(None::<String>),
+            ))
+            .execute(connection)?;
+
+            Ok(
+                diesel::update(dsl::wallet.filter(schema::wallet::uuid.eq(uuid)))
+                    .set((
+                        schema::wallet::archived_at.eq(None::<NaiveDateTime>),
+                        schema::wallet::archived_by.eq(None::<String>),
+                    ))
+                    .execute(connection)?,
+            )
+        })
+    }
+
+    /// Removes the wallets archived before `archived_before`, along with
+    /// everything that belongs to them.
+    pub fn purge(
+        connection: &mut DbConnection,
+        archived_before: NaiveDateTime,
+    ) -> Result<usize, Box<dyn Error>> {
+        let uuids: Vec<String> = with_connection!(connection, |connection| {
+            dsl::wallet
+                .filter(schema::wallet::archived_at.lt(archived_before))
+                .select(schema::wallet::uuid)
+                .load(connection)?
+        });
+
+        let mut purged = 0;
+        for uuid in uuids {
+            purged += Self::remove(connection, &uuid)?;
         }
 
-        Ok(query.load::<WalletRecord>(connection)?)
+        Ok(purged)
     }
 
-    pub fn remove(connection: &mut SqliteConnection, uuid: &str) -> Result<usize, Box<dyn Error>> {
-        diesel::delete(cosigner.filter(schema::cosigner::wallet_uuid.eq(uuid.to_string())))
+    pub fn remove(connection: &mut DbConnection, uuid: &str) -> Result<usize, Box<dyn Error>> {
+        with_connection!(connection, |connection| {
+            diesel::delete(cosigner.filter(schema::cosigner::wallet_uuid.eq(uuid.to_string())))
+                .execute(connection)?;
+            diesel::delete(psbt.filter(schema::psbt::wallet_uuid.eq(uuid.to_string())))
+                .execute(connection)?;
+            diesel::delete(wallet_tag::dsl::wallet_tag.filter(wallet_tag::wallet_uuid.eq(uuid)))
+                .execute(connection)?;
+            diesel::delete(
+                wallet_address::dsl::wallet_address.filter(wallet_address::wallet_uuid.eq(uuid)),
+            )
+            .execute(connection)?;
+            diesel::delete(
+                wallet_label::dsl::wallet_label.filter(wallet_label::wallet_uuid.eq(uuid)),
+            )
             .execute(connection)?;
-        diesel::delete(psbt.filter(schema::psbt::wallet_uuid.eq(uuid.to_string())))
+            diesel::delete(
+                wallet_cosigner::dsl::wallet_cosigner.filter(wallet_cosigner::wallet_uuid.eq(uuid)),
+            )
             .execute(connection)?;
 
-        Ok(
-            diesel::delete(dsl::wallet.filter(schema::wallet::uuid.eq(uuid.to_string())))
-                .execute(connection)?,
-        )
+            Ok(
+                diesel::delete(dsl::wallet.filter(schema::wallet::uuid.eq(uuid.to_string())))
+                    .execute(connection)?,
+            )
+        })
     }
 }
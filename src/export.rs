@@ -0,0 +1,263 @@
+use std::{error::Error, str::FromStr};
+
+use bdk::{
+    bitcoin::util::bip32,
+    descriptor::{get_checksum, Descriptor, DescriptorPublicKey},
+    miniscript::ForEachKey,
+};
+use int_enum::IntEnum;
+use serde::Serialize;
+
+use super::AddressType;
+
+#[repr(i16)]
+#[derive(Debug, Copy, Clone, IntEnum)]
+pub enum ExportFormat {
+    Descriptor = 1,
+    Coldcard = 2,
+    Bsms = 3,
+    Specter = 4,
+}
+
+pub struct ExportSigner {
+    pub label: String,
+    /// Master fingerprint and derivation path of the key, if known.
+    pub origin: Option<bip32::KeySource>,
+    pub xpub: String,
+}
+
+pub struct WalletExport<'a> {
+    pub name: String,
+    pub address_type: AddressType,
+    pub required_signatures: u64,
+    pub receive_descriptor: &'a str,
+    pub change_descriptor: &'a str,
+    pub first_address: String,
+    pub birth_height: u32,
+    pub signers: Vec<ExportSigner>,
+}
+
+#[derive(Serialize)]
+struct SpecterDevice<'a> {
+    #[serde(rename = "type")]
+    type_: &'a str,
+    label: &'a str,
+}
+
+#[derive(Serialize)]
+struct SpecterWallet<'a> {
+    label: &'a str,
+    blockheight: u32,
+    descriptor: String,
+    change_descriptor: String,
+    devices: Vec<SpecterDevice<'a>>,
+}
+
+impl<'a> WalletExport<'a> {
+    pub fn keys(descriptor: &str) -> Result<Vec<DescriptorPublicKey>, Box<dyn Error>> {
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str(descriptor)?;
+
+        let mut keys = vec![];
+        descriptor.for_each_key(|key| {
+            keys.push(key.as_key().clone());
+            true
+        });
+
+        Ok(keys)
+    }
+
+    pub fn render(&self, format: ExportFormat) -> Result<String, Box<dyn Error>> {
+        match format {
+            ExportFormat::Descriptor => self.descriptor(),
+            ExportFormat::Coldcard => self.coldcard(),
+            ExportFormat::Bsms => self.bsms(),
+            ExportFormat::Specter => self.specter(),
+        }
+    }
+
+    /// Origins of the keys, which hardware signers need to recognise theirs.
+    fn origins(&self) -> Result<Vec<&bip32::KeySource>, Box<dyn Error>> {
+        self.signers
+            .iter()
+            .map(|signer| {
+                signer.origin.as_ref().ok_or_else(|| {
+                    format!(
+                        "the key origin of {} is unknown, set it with `cosigner update --origin`",
+                        signer.label
+                    )
+                    .into()
+                })
+            })
+            .collect()
+    }
+
+    fn with_checksum(descriptor: &str) -> Result<String, Box<dyn Error>> {
+        let descriptor = descriptor.split('#').next().unwrap();
+        Ok(format!("{}#{}", descriptor, get_checksum(descriptor)?))
+    }
+
+    fn descriptor(&self) -> Result<String, Box<dyn Error>> {
+        Ok(format!(
+            "{}\n{}\n",
+            Self::with_checksum(self.receive_descriptor)?,
+            Self::with_checksum(self.change_descriptor)?
+        ))
+    }
+
+    fn coldcard(&self) -> Result<String, Box<dyn Error>> {
+        let format = match self.address_type {
+            AddressType::P2sh => "P2SH",
+            AddressType::P2wsh => "P2WSH",
+            AddressType::P2shwsh => "P2SH-P2WSH",
+        };
+
+        let mut lines = vec![
+            String::from("# Coldcard Multisig setup file (exported by ohm)"),
+            String::from("#"),
+            format!("Name: {}", self.name),
+            format!(
+                "Policy: {} of {}",
+                self.required_signatures,
+                self.signers.len()
+            ),
+            format!("Format: {}", format),
+            String::new(),
+        ];
+
+        for (signer, (fingerprint, derivation_path)) in self.signers.iter().zip(self.origins()?) {
+            lines.push(format!("Derivation: {}", derivation_path));
+            lines.push(format!(
+                "{}: {}",
+                fingerprint.to_string().to_uppercase(),
+                signer.xpub
+            ));
+            lines.push(String::new());
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    fn bsms(&self) -> Result<String, Box<dyn Error>> {
+        self.origins()?;
+        let template = self.receive_descriptor.replace("/0/*", "/**");
+
+        Ok(format!(
+            "BSMS 1.0\n{}\n/0/*,/1/*\n{}\n",
+            Self::with_checksum(&template)?,
+            self.first_address
+        ))
+    }
+
+    fn specter(&self) -> Result<String, Box<dyn Error>> {
+        self.origins()?;
+        let wallet = SpecterWallet {
+            label: &self.name,
+            blockheight: self.birth_height,
+            descriptor: Self::with_checksum(self.receive_descriptor)?,
+            change_descriptor: Self::with_checksum(self.change_descriptor)?,
+            devices: self
+                .signers
+                .iter()
+                .map(|signer| SpecterDevice {
+                    type_: "other",
+                    label: &signer.label,
+                })
+                .collect(),
+        };
+
+        Ok(serde_json::to_string_pretty(&wallet)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XPUB: &str = "tpubDE77oVsXQ3J91MpsWtJKXZKbp8bhTRbrYz36hHCq5hRUiw2y1JbmeZFicJbPowYAWZ15tSk19EzHAo8jfnQLAHoXTrbBQ4zXdhN6Pfso8J8";
+
+    fn export<'a>(
+        origin: Option<bip32::KeySource>,
+        descriptors: &'a [String; 2],
+    ) -> WalletExport<'a> {
+        WalletExport {
+            name: String::from("vault"),
+            address_type: AddressType::P2wsh,
+            required_signatures: 1,
+            receive_descriptor: &descriptors[0],
+            change_descriptor: &descriptors[1],
+            first_address: String::new(),
+            birth_height: 2_400_000,
+            signers: vec![ExportSigner {
+                label: String::from("alice"),
+                origin,
+                xpub: String::from(XPUB),
+            }],
+        }
+    }
+
+    /// Descriptors as the wallet keeps them, with their checksum.
+    fn descriptors() -> [String; 2] {
+        [0, 1].map(|keychain| {
+            let descriptor = format!(
+                "wsh(sortedmulti(1,[d34db33f/48'/1'/0'/2']{}/{}/*))",
+                XPUB, keychain
+            );
+            WalletExport::with_checksum(&descriptor).unwrap()
+        })
+    }
+
+    #[test]
+    fn coldcard_origins() {
+        let descriptors = descriptors();
+        let origin = (
+            bip32::Fingerprint::from_str("d34db33f").unwrap(),
+            bip32::DerivationPath::from_str("m/48'/1'/0'/2'").unwrap(),
+        );
+
+        let content = export(Some(origin), &descriptors)
+            .render(ExportFormat::Coldcard)
+            .unwrap();
+        assert!(content.contains("Derivation: m/48'/1'/0'/2'\nD34DB33F: tpub"));
+    }
+
+    #[test]
+    fn unknown_origin() {
+        let descriptors = descriptors();
+        let export = export(None, &descriptors);
+        assert_eq!(WalletExport::keys(&descriptors[0]).unwrap().len(), 1);
+
+        for format in [
+            ExportFormat::Coldcard,
+            ExportFormat::Bsms,
+            ExportFormat::Specter,
+        ] {
+            let err = export.render(format).unwrap_err();
+            assert!(err.to_string().contains("key origin of alice is unknown"));
+        }
+        assert!(export.render(ExportFormat::Descriptor).is_ok());
+    }
+
+    #[test]
+    fn specter() {
+        let descriptors = descriptors();
+        let origin = (
+            bip32::Fingerprint::from_str("d34db33f").unwrap(),
+            bip32::DerivationPath::from_str("m/48'/1'/0'/2'").unwrap(),
+        );
+
+        let content = export(Some(origin), &descriptors)
+            .render(ExportFormat::Specter)
+            .unwrap();
+        let wallet: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(wallet["blockheight"], 2_400_000);
+        assert!(wallet["descriptor"].as_str().unwrap().contains("/0/*))#"));
+        assert!(wallet["change_descriptor"]
+            .as_str()
+            .unwrap()
+            .contains("/1/*))#"));
+        assert_eq!(
+            wallet["descriptor"].as_str().unwrap().matches('#').count(),
+            1
+        );
+    }
+}
@@ -1,6 +1,10 @@
-use bdk::bitcoin;
+use std::str::FromStr;
+
+use bdk::{bitcoin, TransactionDetails};
 use tonic::include_proto;
 
+use crate::cosigner::format_origin;
+
 include_proto!("ohm.v1");
 
 impl From<&str> for AddressType {
@@ -16,15 +20,42 @@ impl From<&str> for AddressType {
     }
 }
 
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "descriptor" => Ok(ExportFormat::Descriptor),
+            "coldcard" => Ok(ExportFormat::Coldcard),
+            "bsms" => Ok(ExportFormat::Bsms),
+            "specter" | "sparrow" => Ok(ExportFormat::Specter),
+            _ => Err(format!("unsupported export format: {}", format)),
+        }
+    }
+}
+
+impl FromStr for LabelType {
+    type Err = String;
+
+    fn from_str(type_: &str) -> Result<Self, Self::Err> {
+        crate::LabelType::from_str(type_).map(|type_| match type_ {
+            crate::LabelType::Tx => LabelType::Tx,
+            crate::LabelType::Addr => LabelType::Addr,
+            crate::LabelType::Pubkey => LabelType::Pubkey,
+            crate::LabelType::Input => LabelType::Input,
+            crate::LabelType::Output => LabelType::Output,
+            crate::LabelType::Xpub => LabelType::Xpub,
+        })
+    }
+}
+
 impl From<bitcoin::Network> for Network {
     fn from(network: bitcoin::Network) -> Self {
         match network {
             bitcoin::Network::Bitcoin => Network::Mainnet,
             bitcoin::Network::Testnet => Network::Testnet,
             bitcoin::Network::Regtest => Network::Regtest,
-            _ => {
-                panic!("proto contains an unsupported network type")
-            }
+            bitcoin::Network::Signet => Network::Signet,
         }
     }
 }
@@ -41,6 +72,14 @@ impl From<crate::Cosigner> for Cosigner {
                 .map_or(String::from(""), |email| email.to_string()),
             xpub: cosigner.xpub().to_string(),
             wallet_id: cosigner.wallet().map(|uuid| uuid.to_string()),
+            name: cosigner.name().map(|name| name.to_string()),
+            archived_at: cosigner.archived_at().map(|time| time.timestamp() as u64),
+            archived_by: cosigner.archived_by().map(|actor| actor.to_string()),
+            has_mnemonic: cosigner.has_mnemonic(),
+            mnemonic_exported_at: cosigner
+                .mnemonic_exported_at()
+                .map(|time| time.timestamp() as u64),
+            origin: cosigner.origin().as_ref().map(format_origin),
         }
     }
 }
@@ -52,10 +91,49 @@ impl From<crate::Wallet> for Wallet {
                 .uuid()
                 .map_or(String::from(""), |uuid| uuid.to_string()),
             required_sigs: wallet.required_signatures(),
-            balance: wallet.balance().unwrap().confirmed.to_string(),
+            balance: wallet
+                .balance()
+                .ok()
+                .map(|balance| balance.confirmed.to_string()),
             descriptor: String::from(wallet.receive_descriptor()),
             receive_address: wallet.receive_address().unwrap().to_string(),
-            transactions: vec![Transaction {}], // TODO
+            transactions: wallet
+                .transactions()
+                .unwrap_or_default()
+                .iter()
+                .map(|tx| tx.into())
+                .collect(),
+            name: wallet.name().map(|name| name.to_string()),
+            description: wallet
+                .description()
+                .map(|description| description.to_string()),
+            tags: wallet.tags().clone(),
+            archived_at: wallet.archived_at().map(|time| time.timestamp() as u64),
+            archived_by: wallet.archived_by().map(|actor| actor.to_string()),
+            successor_id: wallet.successor().map(|uuid| uuid.to_string()),
+        }
+    }
+}
+
+impl From<&crate::ReceiveAddress> for ReceiveAddress {
+    fn from(address: &crate::ReceiveAddress) -> Self {
+        Self {
+            index: address.index,
+            address: address.address.to_string(),
+            used: address.used,
+            label: None,
+        }
+    }
+}
+
+impl From<&crate::AddressKey> for AddressKey {
+    fn from(key: &crate::AddressKey) -> Self {
+        Self {
+            cosigner_id: key.cosigner.map(|uuid| uuid.to_string()),
+            fingerprint: key.fingerprint.to_string(),
+            derivation_path: key.derivation_path.to_string(),
+            xpub: key.xpub.to_string(),
+            public_key: key.public_key.to_string(),
         }
     }
 }
@@ -68,6 +146,59 @@ impl From<&crate::Psbt> for Psbt {
                 .map_or(String::from(""), |uuid| uuid.to_string()),
             base64: psbt.base64(),
             wallet_id: psbt.wallet().to_string(),
+            archived_at: psbt.archived_at().map(|time| time.timestamp() as u64),
+            archived_by: psbt.archived_by().map(|actor| actor.to_string()),
+        }
+    }
+}
+
+impl From<crate::BsmsSession> for BsmsSession {
+    fn from(session: crate::BsmsSession) -> Self {
+        Self {
+            session_id: session
+                .uuid()
+                .map_or(String::from(""), |uuid| uuid.to_string()),
+            token: session.token().to_string(),
+            address_type: session.address_type() as i32,
+            network: session.network() as i32,
+            required_sigs: session.required_signatures(),
+            signers: session.signers(),
+            cosigner_ids: session
+                .cosigners()
+                .iter()
+                .map(|uuid| uuid.to_string())
+                .collect(),
+            wallet_id: session.wallet().map(|uuid| uuid.to_string()),
+            descriptor_record: None,
+        }
+    }
+}
+
+impl From<&TransactionDetails> for Transaction {
+    fn from(tx: &TransactionDetails) -> Self {
+        Self {
+            tx_id: tx.txid.to_string(),
+            received: tx.received,
+            sent: tx.sent,
+            fee: tx.fee,
+            confirmation_height: tx.confirmation_time.as_ref().map(|time| time.height),
+            confirmation_time: tx.confirmation_time.as_ref().map(|time| time.timestamp),
+            label: None,
+        }
+    }
+}
+
+impl From<crate::Label> for Label {
+    fn from(label: crate::Label) -> Self {
+        Self {
+            wallet_id: label.wallet().to_string(),
+            label_type: label.type_() as i32,
+            reference: label.reference().to_string(),
+            label: label.label().to_string(),
+            origin: label.origin().map(|origin| origin.to_string()),
+            spendable: label.spendable(),
+            archived_at: label.archived_at().map(|time| time.timestamp() as u64),
+            archived_by: label.archived_by().map(|actor| actor.to_string()),
         }
     }
 }
@@ -1,13 +1,21 @@
-use std::{str::FromStr, sync::Mutex};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
 
 use bdk::{
+    bitcoin,
     bitcoin::{psbt::PartiallySignedTransaction, util::bip32, Address},
     descriptor::DescriptorPublicKey,
+    FeeRate, KeychainKind,
 };
-use diesel::SqliteConnection;
+use chrono::{Duration, Utc};
 use email_address::EmailAddress;
 use int_enum::IntEnum;
 use rust_decimal::Decimal;
+use tokio::task::block_in_place;
 use tonic::{
     transport::{server::Router, Channel, Server},
     Request, Response, Status,
@@ -15,500 +23,2068 @@ use tonic::{
 use uuid::Uuid;
 
 use super::proto;
-use crate::db;
-use crate::{AddressType, Config, Cosigner, CosignerType, Network, Psbt, Wallet};
+use crate::{backend::Pool, cosigner, db, signer};
+use crate::{
+    AddressType, Backup, BsmsSession, Config, Cosigner, CosignerType, ExportFormat, GapLimitPolicy,
+    KeyOptions, Label, LabelType, Network, Psbt, Signer, Wallet,
+};
 use proto::{ohm_api_client as grpc_client, ohm_api_server as grpc_server};
 
 pub struct Servicer {
-    db_connection: Mutex<SqliteConnection>,
+    db_pool: db::ConnectionPool,
     config: Config,
+    backends: Option<Pool>,
+    signer: Box<dyn Signer>,
+    wallet_locks: Mutex<HashMap<Uuid, Arc<Mutex<()>>>>,
+    session_locks: Mutex<HashMap<Uuid, Arc<Mutex<()>>>>,
+}
+
+fn index_labels(labels: Vec<Label>) -> HashMap<(i16, String), Label> {
+    labels
+        .into_iter()
+        .map(|label| ((label.type_() as i16, label.reference().to_string()), label))
+        .collect()
+}
+
+fn find_label<'a>(
+    labels: &'a HashMap<(i16, String), Label>,
+    type_: LabelType,
+    reference: &str,
+) -> Option<&'a Label> {
+    labels.get(&(type_ as i16, reference.to_string()))
+}
+
+/// Who to record as acting on a request, e.g. archiving a record: the actor
+/// named in the request, or else the address the request came from.
+fn actor_of(actor: Option<String>, remote_addr: Option<SocketAddr>) -> String {
+    actor
+        .filter(|actor| !actor.is_empty())
+        .or_else(|| remote_addr.map(|addr| addr.to_string()))
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+/// Takes a requested fee rate in sat/vB, which nodes don't relay below 1.
+#[allow(clippy::result_large_err)]
+fn parse_fee_rate(fee_rate: f32) -> Result<FeeRate, Status> {
+    if !fee_rate.is_finite() || fee_rate < 1.0 {
+        return Err(Status::invalid_argument(
+            "the fee rate must be at least 1 sat/vB",
+        ));
+    }
+
+    Ok(FeeRate::from_sat_per_vb(fee_rate))
+}
+
+/// Whether the request carries `authorization: Bearer <token>`, compared
+/// without leaking how much of the token matched.
+fn is_authorized<T>(request: &Request<T>, token: &str) -> bool {
+    let presented = match request
+        .metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    {
+        Some(presented) => presented.as_bytes(),
+        None => return false,
+    };
+
+    presented.len() == token.len()
+        && presented
+            .iter()
+            .zip(token.as_bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
 }
 
+// Handler bodies run as closures returning tonic's (large) `Status` errors.
+#[allow(clippy::result_large_err)]
 #[tonic::async_trait]
 impl grpc_server::OhmApi for Servicer {
     async fn register_cosigner(
         &self,
         request: Request<proto::RegisterCosignerRequest>,
     ) -> Result<Response<proto::RegisterCosignerResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let inner = request.into_inner();
-
-        let email_address = EmailAddress::from_str(&inner.email_address)
-            .map_err(|_| Status::invalid_argument("invalid email address"))?;
-
-        let xpub = bip32::ExtendedPubKey::from_str(&inner.xpub)
-            .map_err(|_| Status::invalid_argument("invalid xpub"))?;
-
-        let mut cosigner = Cosigner::new(
-            CosignerType::External,
-            Some(email_address),
-            Some(xpub),
-            None,
-        )
-        .map_err(|_| Status::internal("failed to create cosigner"))?;
-
-        cosigner
-            .save(&mut connection)
-            .map_err(|_| Status::internal("failed to register cosigner"))?;
-
-        Ok(Response::new(proto::RegisterCosignerResponse {
-            cosigner: Some(cosigner.into()),
-        }))
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let email_address = EmailAddress::from_str(&inner.email_address)
+                .map_err(|_| Status::invalid_argument("invalid email address"))?;
+
+            let (xpub, origin) = cosigner::parse_key(&inner.xpub)
+                .map_err(|err| Status::invalid_argument(err.to_string()))?;
+
+            let mut cosigner = Cosigner::new(
+                CosignerType::External,
+                Some(email_address),
+                Some(xpub),
+                None,
+            )
+            .map_err(|_| Status::internal("failed to create cosigner"))?;
+            cosigner.set_name(inner.name);
+            cosigner
+                .set_origin(origin)
+                .map_err(|err| Status::invalid_argument(err.to_string()))?;
+
+            cosigner
+                .save(&mut connection)
+                .map_err(|_| Status::internal("failed to register cosigner"))?;
+
+            Ok(Response::new(proto::RegisterCosignerResponse {
+                cosigner: Some(cosigner.into()),
+            }))
+        })
     }
 
     async fn get_cosigner(
         &self,
         request: Request<proto::GetCosignerRequest>,
     ) -> Result<Response<proto::GetCosignerResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let cosigner_id = request.into_inner().cosigner_id;
-
-        let uuid =
-            Uuid::from_str(&cosigner_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
-
-        let cosigner = Cosigner::from_db(&mut connection, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate cosigners"))?
-            .map(|cosigner| cosigner.into());
-
-        Ok(Response::new(proto::GetCosignerResponse { cosigner }))
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let cosigner_id = request.into_inner().cosigner_id;
+
+            let uuid = Uuid::from_str(&cosigner_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let cosigner = Cosigner::from_db(&mut connection, Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate cosigners"))?
+                .map(|cosigner| cosigner.into());
+
+            Ok(Response::new(proto::GetCosignerResponse { cosigner }))
+        })
     }
 
     async fn find_cosigner(
         &self,
         request: Request<proto::FindCosignerRequest>,
     ) -> Result<Response<proto::FindCosignerResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let inner = request.into_inner();
-
-        let email_address = inner
-            .email_address
-            .map(|address| EmailAddress::from_str(&address))
-            .transpose()
-            .map_err(|_| Status::invalid_argument("invalid email address"))?;
-
-        let xpub = inner
-            .xpub
-            .map(|xpub| bip32::ExtendedPubKey::from_str(&xpub))
-            .transpose()
-            .map_err(|_| Status::invalid_argument("invalid xpub"))?;
-
-        let mut results = Cosigner::find(&mut connection, None, email_address, xpub, None)
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let email_address = inner
+                .email_address
+                .map(|address| EmailAddress::from_str(&address))
+                .transpose()
+                .map_err(|_| Status::invalid_argument("invalid email address"))?;
+
+            let xpub = inner
+                .xpub
+                .map(|xpub| bip32::ExtendedPubKey::from_str(&xpub))
+                .transpose()
+                .map_err(|_| Status::invalid_argument("invalid xpub"))?;
+
+            let results = Cosigner::find(
+                &mut connection,
+                None,
+                email_address,
+                xpub,
+                None,
+                inner.name.as_deref(),
+                inner.include_archived,
+            )
             .map_err(|_| Status::internal("failed to enumerate cosigners"))?;
 
-        let mut cosigners = vec![];
-        for i in 0..results.len() {
-            cosigners.push(results.remove(i).into());
-        }
+            let cosigners = results
+                .into_iter()
+                .map(|cosigner| cosigner.into())
+                .collect();
+
+            Ok(Response::new(proto::FindCosignerResponse { cosigners }))
+        })
+    }
 
-        Ok(Response::new(proto::FindCosignerResponse { cosigners }))
+    async fn update_cosigner(
+        &self,
+        request: Request<proto::UpdateCosignerRequest>,
+    ) -> Result<Response<proto::UpdateCosignerResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.cosigner_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let mut cosigner = Cosigner::from_db(&mut connection, Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate cosigners"))?
+                .ok_or_else(|| Status::not_found("cosigner could not be found"))?;
+
+            if inner.name.is_some() {
+                cosigner.set_name(inner.name);
+            }
+
+            if let Some(origin) = inner.origin {
+                let origin = cosigner::parse_origin(&origin)
+                    .map_err(|_| Status::invalid_argument("invalid key origin"))?;
+                cosigner
+                    .set_origin(Some(origin))
+                    .map_err(|err| Status::invalid_argument(err.to_string()))?;
+            }
+
+            cosigner
+                .save(&mut connection)
+                .map_err(|_| Status::internal("cosigner could not be saved"))?;
+
+            Ok(Response::new(proto::UpdateCosignerResponse {
+                cosigner: Some(cosigner.into()),
+            }))
+        })
     }
 
     async fn forget_cosigner(
         &self,
         request: Request<proto::ForgetCosignerRequest>,
     ) -> Result<Response<proto::ForgetCosignerResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let cosigner_id = request.into_inner().cosigner_id;
-
-        let uuid =
-            Uuid::from_str(&cosigner_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
-
-        let mut cosigner = Cosigner::from_db(&mut connection, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate cosigners"))?
-            .ok_or_else(|| Status::not_found("cosigner could not be found"))?;
-
-        cosigner
-            .remove(&mut connection)
-            .map_err(|_| Status::internal("failed to remove cosigner"))?;
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let remote_addr = request.remote_addr();
+            let inner = request.into_inner();
+            let cosigner_id = inner.cosigner_id;
+
+            let uuid = Uuid::from_str(&cosigner_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let mut cosigner = Cosigner::from_db(&mut connection, Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate cosigners"))?
+                .ok_or_else(|| Status::not_found("cosigner could not be found"))?;
+
+            if let CosignerType::Internal = cosigner.type_() {
+                return Err(Status::failed_precondition(
+                    "internal cosigners are forgotten along with their wallet",
+                ));
+            }
+
+            let wallets = cosigner
+                .wallets(&mut connection)
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?;
+
+            if !wallets.is_empty() && !inner.force {
+                return Err(Status::failed_precondition(format!(
+                    "cosigner is used by {} wallet(s), set force to forget it anyway",
+                    wallets.len()
+                )));
+            }
+
+            cosigner
+                .archive(&mut connection, &actor_of(inner.actor, remote_addr))
+                .map_err(|_| Status::internal("failed to archive cosigner"))?;
+
+            Ok(Response::new(proto::ForgetCosignerResponse { cosigner_id }))
+        })
+    }
 
-        Ok(Response::new(proto::ForgetCosignerResponse { cosigner_id }))
+    async fn restore_cosigner(
+        &self,
+        request: Request<proto::RestoreCosignerRequest>,
+    ) -> Result<Response<proto::RestoreCosignerResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let cosigner_id = request.into_inner().cosigner_id;
+
+            let uuid = Uuid::from_str(&cosigner_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let mut cosigner =
+                Cosigner::find(&mut connection, Some(uuid), None, None, None, None, true)
+                    .map_err(|_| Status::internal("failed to enumerate cosigners"))?
+                    .pop()
+                    .ok_or_else(|| Status::not_found("cosigner could not be found"))?;
+
+            if cosigner.archived_at().is_none() {
+                return Err(Status::failed_precondition("cosigner is not archived"));
+            }
+
+            if let CosignerType::Internal = cosigner.type_() {
+                return Err(Status::failed_precondition(
+                    "internal cosigners are restored along with their wallet",
+                ));
+            }
+
+            cosigner
+                .restore(&mut connection)
+                .map_err(|_| Status::internal("failed to restore cosigner"))?;
+
+            Ok(Response::new(proto::RestoreCosignerResponse {
+                cosigner: Some(cosigner.into()),
+            }))
+        })
     }
 
     async fn create_wallet(
         &self,
         request: Request<proto::CreateWalletRequest>,
     ) -> Result<Response<proto::CreateWalletResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let inner = request.into_inner();
-
-        let address_type = AddressType::from_int(inner.address_type as i16)
-            .map_err(|_| Status::invalid_argument("invalid address type"))?;
-
-        let network = Network::from_int(inner.network as i16)
-            .map_err(|_| Status::invalid_argument("invalid network"))?;
-
-        if inner.required_sigs < 1 {
-            return Err(Status::invalid_argument("required signers should be > 0"));
-        }
-
-        let mut cosigner_ids = vec![];
-        for id in inner.cosigner_ids {
-            cosigner_ids.push(
-                Uuid::from_str(id.as_ref())
-                    .map_err(|_| Status::invalid_argument("invalid UUID"))?,
-            );
-        }
-
-        if cosigner_ids.is_empty() {
-            return Err(Status::invalid_argument("No valid cosigner_ids"));
-        }
-
-        let mut wallet = Wallet::new(
-            &mut connection,
-            &self.config.backend_url,
-            address_type,
-            network,
-            inner.required_sigs,
-            cosigner_ids,
-        )
-        .map_err(|_| Status::internal("failed to create wallet"))?;
-
-        wallet
-            .save(&mut connection)
-            .map_err(|_| Status::internal("wallet could not be saved"))?;
-
-        Ok(Response::new(proto::CreateWalletResponse {
-            wallet: Some(wallet.into()),
-        }))
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let address_type = AddressType::from_int(inner.address_type as i16)
+                .map_err(|_| Status::invalid_argument("invalid address type"))?;
+
+            let network = Network::from_int(inner.network as i16)
+                .map_err(|_| Status::invalid_argument("invalid network"))?;
+
+            if inner.required_sigs < 1 {
+                return Err(Status::invalid_argument("required signers should be > 0"));
+            }
+
+            let mut cosigner_ids = vec![];
+            for id in inner.cosigner_ids {
+                cosigner_ids.push(
+                    Uuid::from_str(id.as_ref())
+                        .map_err(|_| Status::invalid_argument("invalid UUID"))?,
+                );
+            }
+
+            if cosigner_ids.is_empty() {
+                return Err(Status::invalid_argument("No valid cosigner_ids"));
+            }
+
+            if matches!(&self.backends, Some(pool) if !pool.is_configured(network)) {
+                return Err(Status::failed_precondition(format!(
+                    "no backend configured for {:?}",
+                    network
+                )));
+            }
+
+            let mnemonic = inner
+                .mnemonic
+                .as_deref()
+                .map(|mnemonic| bip39::Mnemonic::parse_in(bip39::Language::English, mnemonic))
+                .transpose()
+                .map_err(|_| Status::invalid_argument("invalid mnemonic"))?;
+            if mnemonic.is_none() && inner.passphrase.is_some() {
+                return Err(Status::invalid_argument(
+                    "a passphrase requires a mnemonic to be supplied",
+                ));
+            }
+
+            let xpub = self.signer_key(network)?;
+            if mnemonic.is_some() && xpub.is_some() {
+                return Err(Status::failed_precondition(
+                    "the internal key is held by an external signer",
+                ));
+            }
+
+            let mut wallet = Wallet::new(
+                &mut connection,
+                self.backends.as_ref(),
+                address_type,
+                network,
+                inner.required_sigs,
+                cosigner_ids,
+                &KeyOptions {
+                    mnemonic,
+                    passphrase: inner.passphrase.as_deref(),
+                    xpub,
+                    encryption_passphrase: self
+                        .config
+                        .key_encryption_passphrase
+                        .as_ref()
+                        .map(|passphrase| passphrase.expose().as_str()),
+                    ..Default::default()
+                },
+            )
+            .map_err(|_| Status::internal("failed to create wallet"))?;
+            wallet.set_name(inner.name);
+            wallet.set_description(inner.description);
+            wallet.set_tags(inner.tags);
+
+            wallet
+                .save(&mut connection)
+                .map_err(|_| Status::internal("wallet could not be saved"))?;
+
+            Ok(Response::new(proto::CreateWalletResponse {
+                wallet: Some(wallet.into()),
+            }))
+        })
     }
 
     async fn get_wallet(
         &self,
         request: Request<proto::GetWalletRequest>,
     ) -> Result<Response<proto::GetWalletResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let wallet_id = request.into_inner().wallet_id;
-
-        let uuid =
-            Uuid::from_str(&wallet_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
-
-        let wallet = Wallet::from_db(&mut connection, &self.config.backend_url, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate wallets"))?
-            .map(|wallet| wallet.into());
-
-        Ok(Response::new(proto::GetWalletResponse { wallet }))
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let wallet_id = request.into_inner().wallet_id;
+
+            let uuid =
+                Uuid::from_str(&wallet_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .map(|wallet| wallet.into());
+
+            Ok(Response::new(proto::GetWalletResponse { wallet }))
+        })
     }
 
     async fn find_wallet(
         &self,
         request: Request<proto::FindWalletRequest>,
     ) -> Result<Response<proto::FindWalletResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let inner = request.into_inner();
-
-        let mut address_type = None;
-        if let Some(type_) = inner.address_type {
-            address_type = Some(
-                AddressType::from_int(type_ as i16)
-                    .map_err(|_| Status::invalid_argument("invalid address type"))?,
-            );
-        }
-
-        let network = inner
-            .network
-            .map(|network| Network::from_int(network as i16))
-            .transpose()
-            .map_err(|_| Status::invalid_argument("invalid network"))?;
-
-        inner
-            .descriptor
-            .as_ref()
-            .map(|descriptor| DescriptorPublicKey::from_str(descriptor))
-            .transpose()
-            .map_err(|_| Status::invalid_argument("invalid receive descriptor"))?;
-
-        let results = Wallet::find(
-            &mut connection,
-            &self.config.backend_url,
-            None,
-            address_type,
-            network,
-            inner.descriptor.as_deref(),
-        )
-        .map_err(|_| Status::internal("failed to enumerate wallets"))?;
-
-        let mut wallets = vec![];
-        for result in results {
-            wallets.push(result.into());
-        }
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let mut address_type = None;
+            if let Some(type_) = inner.address_type {
+                address_type = Some(
+                    AddressType::from_int(type_ as i16)
+                        .map_err(|_| Status::invalid_argument("invalid address type"))?,
+                );
+            }
+
+            let network = inner
+                .network
+                .map(|network| Network::from_int(network as i16))
+                .transpose()
+                .map_err(|_| Status::invalid_argument("invalid network"))?;
+
+            inner
+                .descriptor
+                .as_ref()
+                .map(|descriptor| DescriptorPublicKey::from_str(descriptor))
+                .transpose()
+                .map_err(|_| Status::invalid_argument("invalid receive descriptor"))?;
+
+            let results = Wallet::find(
+                &mut connection,
+                self.backends.as_ref(),
+                None,
+                address_type,
+                network,
+                inner.descriptor.as_deref(),
+                inner.name.as_deref(),
+                inner.tag.as_deref(),
+                inner.include_archived,
+            )
+            .map_err(|_| Status::internal("failed to enumerate wallets"))?;
+
+            let mut wallets = vec![];
+            for result in results {
+                wallets.push(result.into());
+            }
+
+            Ok(Response::new(proto::FindWalletResponse { wallets }))
+        })
+    }
 
-        Ok(Response::new(proto::FindWalletResponse { wallets }))
+    async fn update_wallet(
+        &self,
+        request: Request<proto::UpdateWalletRequest>,
+    ) -> Result<Response<proto::UpdateWalletResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let lock = self.wallet_lock(uuid);
+            let _guard = lock.lock().unwrap();
+
+            let mut wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+            if inner.name.is_some() {
+                wallet.set_name(inner.name);
+            }
+
+            if inner.description.is_some() {
+                wallet.set_description(inner.description);
+            }
+
+            if let Some(tags) = inner.tags {
+                wallet.set_tags(tags.tags);
+            }
+
+            wallet
+                .save(&mut connection)
+                .map_err(|_| Status::internal("wallet could not be saved"))?;
+
+            Ok(Response::new(proto::UpdateWalletResponse {
+                wallet: Some(wallet.into()),
+            }))
+        })
     }
 
     async fn get_new_receive_address(
         &self,
         request: Request<proto::GetNewReceiveAddressRequest>,
     ) -> Result<Response<proto::GetNewReceiveAddressResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let wallet_id = request.into_inner().wallet_id;
-
-        let uuid =
-            Uuid::from_str(&wallet_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
-
-        let mut wallet = Wallet::from_db(&mut connection, &self.config.backend_url, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate wallets"))?
-            .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let lock = self.wallet_lock(uuid);
+            let _guard = lock.lock().unwrap();
+
+            let mut wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+            let unused = wallet
+                .receive_addresses(&mut connection, Some(false))
+                .map_err(|_| Status::internal("failed to enumerate receive addresses"))?;
+
+            let address = if unused.len() as u64 >= self.config.gap_limit {
+                match self.config.gap_limit_policy {
+                    GapLimitPolicy::Refuse => {
+                        return Err(Status::failed_precondition(format!(
+                            "gap limit reached: {} receive addresses are still unfunded",
+                            unused.len()
+                        )))
+                    }
+                    GapLimitPolicy::Reuse => unused.into_iter().next().unwrap(),
+                }
+            } else {
+                wallet.new_receive_address(&mut connection).map_err(|err| {
+                    Status::internal(format!("unable to get new receive address: {}", err))
+                })?
+            };
+
+            if let Some(label) = &inner.label {
+                Label::new(
+                    uuid,
+                    LabelType::Addr,
+                    &address.address.to_string(),
+                    label,
+                    None,
+                    None,
+                )
+                .and_then(|label| label.save(&mut connection))
+                .map_err(|_| Status::internal("label could not be saved"))?;
+            }
+
+            let mut receive_address: proto::ReceiveAddress = (&address).into();
+            receive_address.label = Label::find(
+                &mut connection,
+                uuid,
+                Some(LabelType::Addr),
+                Some(&receive_address.address),
+                false,
+            )
+            .map_err(|_| Status::internal("failed to enumerate labels"))?
+            .pop()
+            .map(|label| label.label().to_string());
+
+            Ok(Response::new(proto::GetNewReceiveAddressResponse {
+                address: address.address.to_string(),
+                receive_address: Some(receive_address),
+            }))
+        })
+    }
 
-        let address = wallet.new_receive_address().map_err(|err| {
-            Status::internal(format!("unable to get new receive address: {}", err))
-        })?;
+    async fn list_receive_addresses(
+        &self,
+        request: Request<proto::ListReceiveAddressesRequest>,
+    ) -> Result<Response<proto::ListReceiveAddressesResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            // Listing marks addresses used, which races with handing out new
+            // ones.
+            let lock = self.wallet_lock(uuid);
+            let _guard = lock.lock().unwrap();
+
+            let wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+            let labels = index_labels(
+                Label::find(&mut connection, uuid, Some(LabelType::Addr), None, false)
+                    .map_err(|_| Status::internal("failed to enumerate labels"))?,
+            );
 
-        wallet
-            .save(&mut connection)
-            .map_err(|_| Status::internal("wallet could not be saved"))?;
+            let addresses = wallet
+                .receive_addresses(&mut connection, inner.used)
+                .map_err(|_| Status::internal("failed to enumerate receive addresses"))?
+                .iter()
+                .map(|address| {
+                    let mut receive_address: proto::ReceiveAddress = address.into();
+                    receive_address.label =
+                        find_label(&labels, LabelType::Addr, &receive_address.address)
+                            .map(|label| label.label().to_string());
+                    receive_address
+                })
+                .collect();
+
+            Ok(Response::new(proto::ListReceiveAddressesResponse {
+                addresses,
+            }))
+        })
+    }
 
-        Ok(Response::new(proto::GetNewReceiveAddressResponse {
-            address: address.to_string(),
-        }))
+    async fn verify_address(
+        &self,
+        request: Request<proto::VerifyAddressRequest>,
+    ) -> Result<Response<proto::VerifyAddressResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let address = Address::from_str(&inner.address)
+                .map_err(|_| Status::invalid_argument("invalid address"))?;
+
+            let wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+            if !address.is_valid_for_network(wallet.network().into()) {
+                return Err(Status::invalid_argument(
+                    "address belongs to a different network",
+                ));
+            }
+
+            let verification = wallet
+                .verify_address(&mut connection, &address, self.config.gap_limit)
+                .map_err(|err| Status::internal(format!("unable to verify address: {}", err)))?;
+
+            Ok(Response::new(match verification {
+                Some(verification) => proto::VerifyAddressResponse {
+                    is_mine: true,
+                    is_change: Some(verification.keychain == KeychainKind::Internal),
+                    index: Some(verification.index as u64),
+                    keys: verification.keys.iter().map(|key| key.into()).collect(),
+                },
+                None => proto::VerifyAddressResponse {
+                    is_mine: false,
+                    is_change: None,
+                    index: None,
+                    keys: vec![],
+                },
+            }))
+        })
     }
 
     async fn forget_wallet(
         &self,
         request: Request<proto::ForgetWalletRequest>,
     ) -> Result<Response<proto::ForgetWalletResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let wallet_id = request.into_inner().wallet_id;
-
-        let uuid =
-            Uuid::from_str(&wallet_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let remote_addr = request.remote_addr();
+            let inner = request.into_inner();
+            let wallet_id = inner.wallet_id;
+
+            let uuid =
+                Uuid::from_str(&wallet_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let lock = self.wallet_lock(uuid);
+            let _guard = lock.lock().unwrap();
+
+            let mut wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+            wallet
+                .archive(&mut connection, &actor_of(inner.actor, remote_addr))
+                .map_err(|_| Status::internal("failed to archive wallet"))?;
+
+            Ok(Response::new(proto::ForgetWalletResponse { wallet_id }))
+        })
+    }
 
-        let mut wallet = Wallet::from_db(&mut connection, &self.config.backend_url, Some(uuid))
+    async fn restore_wallet(
+        &self,
+        request: Request<proto::RestoreWalletRequest>,
+    ) -> Result<Response<proto::RestoreWalletResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let wallet_id = request.into_inner().wallet_id;
+
+            let uuid =
+                Uuid::from_str(&wallet_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let lock = self.wallet_lock(uuid);
+            let _guard = lock.lock().unwrap();
+
+            let mut wallet = Wallet::find(
+                &mut connection,
+                self.backends.as_ref(),
+                Some(uuid),
+                None,
+                None,
+                None,
+                None,
+                None,
+                true,
+            )
             .map_err(|_| Status::internal("failed to enumerate wallets"))?
+            .pop()
             .ok_or_else(|| Status::not_found("wallet could not be found"))?;
 
-        wallet
-            .remove(&mut connection)
-            .map_err(|_| Status::internal("failed to remove wallet"))?;
+            if wallet.archived_at().is_none() {
+                return Err(Status::failed_precondition("wallet is not archived"));
+            }
+
+            wallet
+                .restore(&mut connection)
+                .map_err(|_| Status::internal("failed to restore wallet"))?;
+
+            Ok(Response::new(proto::RestoreWalletResponse {
+                wallet: Some(wallet.into()),
+            }))
+        })
+    }
+
+    async fn export_wallet(
+        &self,
+        request: Request<proto::ExportWalletRequest>,
+    ) -> Result<Response<proto::ExportWalletResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let format = ExportFormat::from_int(inner.format as i16)
+                .map_err(|_| Status::invalid_argument("invalid export format"))?;
+
+            let wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+            let content = wallet
+                .export(&mut connection, format)
+                .map_err(|err| Status::internal(format!("failed to export wallet: {}", err)))?;
+
+            Ok(Response::new(proto::ExportWalletResponse {
+                wallet_id: inner.wallet_id,
+                format: inner.format,
+                content,
+            }))
+        })
+    }
+
+    async fn rotate_wallet(
+        &self,
+        request: Request<proto::RotateWalletRequest>,
+    ) -> Result<Response<proto::RotateWalletResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let mut swaps = HashMap::new();
+            for swap in &inner.swaps {
+                let old = Uuid::from_str(&swap.old_cosigner_id)
+                    .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+                let new = Uuid::from_str(&swap.new_cosigner_id)
+                    .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+                swaps.insert(old, new);
+            }
+
+            let fee_rate = inner.fee_rate.map(parse_fee_rate).transpose()?;
+
+            let lock = self.wallet_lock(uuid);
+            let _guard = lock.lock().unwrap();
+
+            let mut wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+            let (successor, sweep) = wallet
+                .rotate(
+                    &mut connection,
+                    self.backends.as_ref(),
+                    &swaps,
+                    &KeyOptions {
+                        xpub: self.signer_key(wallet.network())?,
+                        encryption_passphrase: self
+                            .config
+                            .key_encryption_passphrase
+                            .as_ref()
+                            .map(|passphrase| passphrase.expose().as_str()),
+                        ..Default::default()
+                    },
+                    fee_rate,
+                )
+                .map_err(|err| Status::internal(format!("failed to rotate wallet: {}", err)))?;
+
+            Ok(Response::new(proto::RotateWalletResponse {
+                sweep_psbt: sweep
+                    .and_then(|uuid| wallet.partially_signed_transactions().get(&uuid))
+                    .map(|psbt| psbt.into()),
+                wallet: Some(successor.into()),
+            }))
+        })
+    }
+
+    async fn list_transactions(
+        &self,
+        request: Request<proto::ListTransactionsRequest>,
+    ) -> Result<Response<proto::ListTransactionsResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let wallet_id = request.into_inner().wallet_id;
+
+            let uuid =
+                Uuid::from_str(&wallet_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+            if !wallet.is_online() {
+                return Err(Status::unavailable(
+                    "transactions are unavailable in offline mode",
+                ));
+            }
+
+            let labels = index_labels(
+                Label::find(&mut connection, uuid, None, None, false)
+                    .map_err(|_| Status::internal("failed to enumerate labels"))?,
+            );
+
+            let mut transactions = vec![];
+            for tx in wallet
+                .transactions()
+                .map_err(|_| Status::internal("failed to enumerate transactions"))?
+            {
+                let mut transaction: proto::Transaction = (&tx).into();
+                transaction.label = find_label(&labels, LabelType::Tx, &tx.txid.to_string())
+                    .map(|label| label.label().to_string());
+                transactions.push(transaction);
+            }
+
+            Ok(Response::new(proto::ListTransactionsResponse {
+                transactions,
+            }))
+        })
+    }
+
+    async fn list_utxos(
+        &self,
+        request: Request<proto::ListUtxosRequest>,
+    ) -> Result<Response<proto::ListUtxosResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let wallet_id = request.into_inner().wallet_id;
+
+            let uuid =
+                Uuid::from_str(&wallet_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+            if !wallet.is_online() {
+                return Err(Status::unavailable("UTXOs are unavailable in offline mode"));
+            }
+
+            let labels = index_labels(
+                Label::find(&mut connection, uuid, None, None, false)
+                    .map_err(|_| Status::internal("failed to enumerate labels"))?,
+            );
 
-        Ok(Response::new(proto::ForgetWalletResponse { wallet_id }))
+            let mut utxos = vec![];
+            for utxo in wallet
+                .utxos()
+                .map_err(|_| Status::internal("failed to enumerate UTXOs"))?
+            {
+                let outpoint = utxo.outpoint.to_string();
+                let address = Address::from_script(
+                    &utxo.txout.script_pubkey,
+                    bitcoin::Network::from(wallet.network()),
+                )
+                .map_or(String::from(""), |address| address.to_string());
+
+                let output_label = find_label(&labels, LabelType::Output, &outpoint);
+                let label = output_label
+                    .or_else(|| find_label(&labels, LabelType::Addr, &address))
+                    .map(|label| label.label().to_string());
+
+                utxos.push(proto::Utxo {
+                    outpoint,
+                    value: utxo.txout.value,
+                    address,
+                    is_change: utxo.keychain == KeychainKind::Internal,
+                    label,
+                    spendable: output_label.and_then(|label| label.spendable()),
+                });
+            }
+
+            Ok(Response::new(proto::ListUtxosResponse { utxos }))
+        })
     }
 
     async fn create_psbt(
         &self,
         request: Request<proto::CreatePsbtRequest>,
     ) -> Result<Response<proto::CreatePsbtResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let inner = request.into_inner();
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
 
-        let uuid = Uuid::from_str(&inner.wallet_id)
-            .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
 
-        let amount = Decimal::from_str(&inner.amount)
-            .map_err(|_| Status::invalid_argument("invalid amount"))?;
+            let amount = Decimal::from_str(&inner.amount)
+                .map_err(|_| Status::invalid_argument("invalid amount"))?;
 
-        let recipient = Address::from_str(&inner.recipient)
-            .map_err(|_| Status::invalid_argument("invalid recipient"))?;
+            let recipient = Address::from_str(&inner.recipient)
+                .map_err(|_| Status::invalid_argument("invalid recipient"))?;
 
-        let mut wallet = Wallet::from_db(&mut connection, &self.config.backend_url, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate wallets"))?
-            .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+            let fee_rate = inner.fee_rate.map(parse_fee_rate).transpose()?;
+
+            let lock = self.wallet_lock(uuid);
+            let _guard = lock.lock().unwrap();
+
+            let mut wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .ok_or_else(|| Status::not_found("wallet could not be found"))?;
 
-        let psbt = wallet
-            .create_psbt(&mut connection, amount, recipient)
-            .map_err(|_| Status::internal("failed to create a PSBT"))?;
+            if !wallet.is_online() {
+                return Err(Status::unavailable(
+                    "creating PSBTs is unavailable in offline mode",
+                ));
+            }
 
-        Ok(Response::new(proto::CreatePsbtResponse {
-            psbt: Some(psbt.into()),
-        }))
+            let psbt = wallet
+                .create_psbt(&mut connection, amount, recipient, fee_rate)
+                .map_err(|err| Status::internal(format!("failed to create a PSBT: {}", err)))?;
+
+            Ok(Response::new(proto::CreatePsbtResponse {
+                psbt: Some(psbt.into()),
+            }))
+        })
     }
 
     async fn register_psbt(
         &self,
         request: Request<proto::RegisterPsbtRequest>,
     ) -> Result<Response<proto::RegisterPsbtResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let inner = request.into_inner();
-
-        let uuid = Uuid::from_str(&inner.wallet_id)
-            .map_err(|_| Status::invalid_argument("invalid UUID"))?;
-
-        let psbt = PartiallySignedTransaction::from_str(&inner.base64)
-            .map_err(|_| Status::invalid_argument("invalid PSBT"))?;
-
-        let mut wallet = Wallet::from_db(&mut connection, &self.config.backend_url, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate wallets"))?
-            .ok_or_else(|| Status::not_found("wallet could not be found"))?;
-
-        let psbt = wallet
-            .import_psbt(&mut connection, psbt)
-            .map_err(|_| Status::internal("failed to register PSBT"))?;
-
-        Ok(Response::new(proto::RegisterPsbtResponse {
-            psbt: Some(psbt.into()),
-        }))
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let psbt = PartiallySignedTransaction::from_str(&inner.base64)
+                .map_err(|_| Status::invalid_argument("invalid PSBT"))?;
+
+            let lock = self.wallet_lock(uuid);
+            let _guard = lock.lock().unwrap();
+
+            let mut wallet = Wallet::from_db(&mut connection, self.backends.as_ref(), Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate wallets"))?
+                .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+            let psbt = wallet
+                .import_psbt(&mut connection, psbt)
+                .map_err(|_| Status::internal("failed to register PSBT"))?;
+
+            Ok(Response::new(proto::RegisterPsbtResponse {
+                psbt: Some(psbt.into()),
+            }))
+        })
     }
 
     async fn get_psbt(
         &self,
         request: Request<proto::GetPsbtRequest>,
     ) -> Result<Response<proto::GetPsbtResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let psbt_id = request.into_inner().psbt_id;
-
-        let uuid =
-            Uuid::from_str(&psbt_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
-
-        let psbt = Psbt::from_db(&mut connection, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
-            .map(|psbt| (&psbt).into());
-
-        Ok(Response::new(proto::GetPsbtResponse { psbt }))
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let psbt_id = request.into_inner().psbt_id;
+
+            let uuid =
+                Uuid::from_str(&psbt_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let psbt = Psbt::from_db(&mut connection, Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
+                .map(|psbt| (&psbt).into());
+
+            Ok(Response::new(proto::GetPsbtResponse { psbt }))
+        })
     }
 
     async fn find_psbt(
         &self,
         request: Request<proto::FindPsbtRequest>,
     ) -> Result<Response<proto::FindPsbtResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let wallet_id = request.into_inner().wallet_id;
-
-        let uuid =
-            Uuid::from_str(&wallet_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
-
-        let mut results = Psbt::find(&mut connection, None, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate PSBTs"))?;
-
-        let mut psbts = vec![];
-        for i in 0..results.len() {
-            psbts.push((&results.remove(i)).into());
-        }
-
-        Ok(Response::new(proto::FindPsbtResponse { psbts }))
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let psbts = Psbt::find(&mut connection, None, Some(uuid), inner.include_archived)
+                .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
+                .iter()
+                .map(|psbt| psbt.into())
+                .collect();
+
+            Ok(Response::new(proto::FindPsbtResponse { psbts }))
+        })
     }
 
     async fn sign_psbt(
         &self,
         request: Request<proto::SignPsbtRequest>,
     ) -> Result<Response<proto::SignPsbtResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let psbt_id = request.into_inner().psbt_id;
-
-        let uuid =
-            Uuid::from_str(&psbt_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
-
-        let psbt = Psbt::from_db(&mut connection, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
-            .ok_or_else(|| Status::not_found("PSBT could not be found"))?;
-
-        let mut wallet = Wallet::from_db(
-            &mut connection,
-            &self.config.backend_url,
-            Some(*psbt.wallet()),
-        )
-        .map_err(|_| Status::internal("failed to enumerate wallets"))?
-        .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let psbt_id = request.into_inner().psbt_id;
+
+            let uuid =
+                Uuid::from_str(&psbt_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let psbt = Psbt::from_db(&mut connection, Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
+                .ok_or_else(|| Status::not_found("PSBT could not be found"))?;
+
+            let lock = self.wallet_lock(*psbt.wallet());
+            let _guard = lock.lock().unwrap();
+
+            let mut wallet = Wallet::from_db(
+                &mut connection,
+                self.backends.as_ref(),
+                Some(*psbt.wallet()),
+            )
+            .map_err(|_| Status::internal("failed to enumerate wallets"))?
+            .ok_or_else(|| Status::not_found("wallet could not be found"))?;
 
-        let signed_psbt = wallet
-            .sign_psbt(&mut connection, uuid)
-            .map_err(|_| Status::internal("failed to sign PSBT"))?;
+            let signed_psbt = wallet
+                .sign_psbt(&mut connection, uuid, self.signer.as_ref())
+                .map_err(|err| Status::internal(format!("failed to sign PSBT: {}", err)))?;
 
-        Ok(Response::new(proto::SignPsbtResponse {
-            psbt: Some(signed_psbt.into()),
-        }))
+            Ok(Response::new(proto::SignPsbtResponse {
+                psbt: Some(signed_psbt.into()),
+            }))
+        })
     }
 
     async fn combine_with_other_psbt(
         &self,
         request: Request<proto::CombineWithOtherPsbtRequest>,
     ) -> Result<Response<proto::CombineWithOtherPsbtResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let inner = request.into_inner();
-
-        let uuid =
-            Uuid::from_str(&inner.psbt_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
-
-        let psbt = Psbt::from_db(&mut connection, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
-            .ok_or_else(|| Status::not_found("PSBT could not be found"))?;
-
-        let additional_psbt = PartiallySignedTransaction::from_str(&inner.base64)
-            .map_err(|_| Status::invalid_argument("invalid PSBT"))?;
-
-        let mut wallet = Wallet::from_db(
-            &mut connection,
-            &self.config.backend_url,
-            Some(*psbt.wallet()),
-        )
-        .map_err(|_| Status::internal("failed to enumerate wallets"))?
-        .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.psbt_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let psbt = Psbt::from_db(&mut connection, Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
+                .ok_or_else(|| Status::not_found("PSBT could not be found"))?;
+
+            let additional_psbt = PartiallySignedTransaction::from_str(&inner.base64)
+                .map_err(|_| Status::invalid_argument("invalid PSBT"))?;
+
+            let lock = self.wallet_lock(*psbt.wallet());
+            let _guard = lock.lock().unwrap();
+
+            let mut wallet = Wallet::from_db(
+                &mut connection,
+                self.backends.as_ref(),
+                Some(*psbt.wallet()),
+            )
+            .map_err(|_| Status::internal("failed to enumerate wallets"))?
+            .ok_or_else(|| Status::not_found("wallet could not be found"))?;
 
-        let combined_psbt = wallet
-            .combine_psbt(&mut connection, uuid, additional_psbt)
-            .map_err(|_| Status::internal("failed to combine PSBTs"))?;
+            let combined_psbt = wallet
+                .combine_psbt(&mut connection, uuid, additional_psbt)
+                .map_err(|_| Status::internal("failed to combine PSBTs"))?;
 
-        Ok(Response::new(proto::CombineWithOtherPsbtResponse {
-            psbt: Some(combined_psbt.into()),
-        }))
+            Ok(Response::new(proto::CombineWithOtherPsbtResponse {
+                psbt: Some(combined_psbt.into()),
+            }))
+        })
     }
 
     async fn broadcast_psbt(
         &self,
         request: Request<proto::BroadcastPsbtRequest>,
     ) -> Result<Response<proto::BroadcastPsbtResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let psbt_id = request.into_inner().psbt_id;
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let psbt_id = request.into_inner().psbt_id;
+
+            let uuid =
+                Uuid::from_str(&psbt_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let psbt = Psbt::from_db(&mut connection, Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
+                .ok_or_else(|| Status::not_found("PSBT could not be found"))?;
+
+            let lock = self.wallet_lock(*psbt.wallet());
+            let _guard = lock.lock().unwrap();
+
+            let mut wallet = Wallet::from_db(
+                &mut connection,
+                self.backends.as_ref(),
+                Some(*psbt.wallet()),
+            )
+            .map_err(|_| Status::internal("failed to enumerate wallets"))?
+            .ok_or_else(|| Status::not_found("wallet could not be found"))?;
 
-        let uuid =
-            Uuid::from_str(&psbt_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+            if !wallet.is_online() {
+                return Err(Status::unavailable(
+                    "broadcasting is unavailable in offline mode",
+                ));
+            }
 
-        let psbt = Psbt::from_db(&mut connection, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
-            .ok_or_else(|| Status::not_found("PSBT could not be found"))?;
+            let tx_id = wallet
+                .broadcast_psbt(&mut connection, uuid)
+                .map_err(|_| Status::internal("failed to broadcast PSBT"))?;
 
-        let mut wallet = Wallet::from_db(
-            &mut connection,
-            &self.config.backend_url,
-            Some(*psbt.wallet()),
-        )
-        .map_err(|_| Status::internal("failed to enumerate wallets"))?
-        .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+            Ok(Response::new(proto::BroadcastPsbtResponse {
+                tx_id: tx_id.to_string(),
+            }))
+        })
+    }
 
-        let tx_id = wallet
-            .broadcast_psbt(&mut connection, uuid)
-            .map_err(|_| Status::internal("failed to broadcast PSBT"))?;
+    async fn decode_psbt(
+        &self,
+        request: Request<proto::DecodePsbtRequest>,
+    ) -> Result<Response<proto::DecodePsbtResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let psbt_id = request.into_inner().psbt_id;
+
+            let uuid =
+                Uuid::from_str(&psbt_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let psbt = Psbt::from_db(&mut connection, Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
+                .ok_or_else(|| Status::not_found("PSBT could not be found"))?;
+
+            let wallet = Wallet::from_db(
+                &mut connection,
+                self.backends.as_ref(),
+                Some(*psbt.wallet()),
+            )
+            .map_err(|_| Status::internal("failed to enumerate wallets"))?
+            .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+            let labels = index_labels(
+                Label::find(&mut connection, *psbt.wallet(), None, None, false)
+                    .map_err(|_| Status::internal("failed to enumerate labels"))?,
+            );
 
-        Ok(Response::new(proto::BroadcastPsbtResponse {
-            tx_id: tx_id.to_string(),
-        }))
+            let inputs = psbt
+                .inputs()
+                .into_iter()
+                .map(|input| {
+                    let outpoint = input.outpoint.to_string();
+                    let label = find_label(&labels, LabelType::Input, &outpoint)
+                        .or_else(|| find_label(&labels, LabelType::Output, &outpoint))
+                        .map(|label| label.label().to_string());
+
+                    proto::PsbtInput {
+                        outpoint,
+                        value: input.value,
+                        label,
+                    }
+                })
+                .collect();
+
+            let mut outputs = vec![];
+            for output in psbt.outputs() {
+                let address = Address::from_script(
+                    &output.script_pubkey,
+                    bitcoin::Network::from(wallet.network()),
+                )
+                .map(|address| address.to_string());
+
+                outputs.push(proto::PsbtOutput {
+                    label: address
+                        .as_ref()
+                        .and_then(|address| find_label(&labels, LabelType::Addr, address))
+                        .map(|label| label.label().to_string()),
+                    address,
+                    value: output.value,
+                    is_mine: wallet
+                        .is_mine(&output.script_pubkey)
+                        .map_err(|_| Status::internal("failed to inspect PSBT outputs"))?,
+                });
+            }
+
+            Ok(Response::new(proto::DecodePsbtResponse {
+                psbt_id,
+                inputs,
+                outputs,
+                fee: psbt.fee(),
+            }))
+        })
     }
 
     async fn forget_psbt(
         &self,
         request: Request<proto::ForgetPsbtRequest>,
     ) -> Result<Response<proto::ForgetPsbtResponse>, Status> {
-        let mut connection = self.db_connection.lock().unwrap();
-        let psbt_id = request.into_inner().psbt_id;
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let remote_addr = request.remote_addr();
+            let inner = request.into_inner();
+            let psbt_id = inner.psbt_id;
+
+            let uuid =
+                Uuid::from_str(&psbt_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let mut psbt = Psbt::from_db(&mut connection, Some(uuid))
+                .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
+                .ok_or_else(|| Status::not_found("PSBT could not be found"))?;
+
+            psbt.archive(&mut connection, &actor_of(inner.actor, remote_addr))
+                .map_err(|_| Status::internal("failed to archive PSBT"))?;
+
+            Ok(Response::new(proto::ForgetPsbtResponse { psbt_id }))
+        })
+    }
 
-        let uuid =
-            Uuid::from_str(&psbt_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+    async fn restore_psbt(
+        &self,
+        request: Request<proto::RestorePsbtRequest>,
+    ) -> Result<Response<proto::RestorePsbtResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let psbt_id = request.into_inner().psbt_id;
+
+            let uuid =
+                Uuid::from_str(&psbt_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let mut psbt = Psbt::find(&mut connection, Some(uuid), None, true)
+                .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
+                .pop()
+                .ok_or_else(|| Status::not_found("PSBT could not be found"))?;
+
+            if psbt.archived_at().is_none() {
+                return Err(Status::failed_precondition("PSBT is not archived"));
+            }
+
+            if db::Wallet::find(
+                &mut connection,
+                Some(psbt.wallet()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+            )
+            .map_err(|_| Status::internal("failed to enumerate wallets"))?
+            .is_empty()
+            {
+                return Err(Status::failed_precondition("wallet is archived"));
+            }
+
+            psbt.restore(&mut connection)
+                .map_err(|_| Status::internal("failed to restore PSBT"))?;
+
+            Ok(Response::new(proto::RestorePsbtResponse {
+                psbt: Some((&psbt).into()),
+            }))
+        })
+    }
+
+    async fn set_label(
+        &self,
+        request: Request<proto::SetLabelRequest>,
+    ) -> Result<Response<proto::SetLabelResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request
+                .into_inner()
+                .label
+                .ok_or_else(|| Status::invalid_argument("missing label"))?;
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let type_ = LabelType::from_int(inner.label_type as i16)
+                .map_err(|_| Status::invalid_argument("invalid label type"))?;
+
+            if db::Wallet::find(
+                &mut connection,
+                Some(&uuid),
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+            )
+            .map_err(|_| Status::internal("failed to enumerate wallets"))?
+            .is_empty()
+            {
+                return Err(Status::not_found("wallet could not be found"));
+            }
+
+            let label = Label::new(
+                uuid,
+                type_,
+                &inner.reference,
+                &inner.label,
+                inner.origin,
+                inner.spendable,
+            )
+            .map_err(|err| Status::invalid_argument(format!("invalid label: {}", err)))?;
+
+            label
+                .save(&mut connection)
+                .map_err(|_| Status::internal("label could not be saved"))?;
+
+            Ok(Response::new(proto::SetLabelResponse {
+                label: Some(label.into()),
+            }))
+        })
+    }
+
+    async fn find_label(
+        &self,
+        request: Request<proto::FindLabelRequest>,
+    ) -> Result<Response<proto::FindLabelResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let type_ = inner
+                .label_type
+                .map(|type_| LabelType::from_int(type_ as i16))
+                .transpose()
+                .map_err(|_| Status::invalid_argument("invalid label type"))?;
+
+            let labels = Label::find(
+                &mut connection,
+                uuid,
+                type_,
+                inner.reference.as_deref(),
+                inner.include_archived,
+            )
+            .map_err(|_| Status::internal("failed to enumerate labels"))?
+            .into_iter()
+            .map(|label| label.into())
+            .collect();
+
+            Ok(Response::new(proto::FindLabelResponse { labels }))
+        })
+    }
+
+    async fn forget_label(
+        &self,
+        request: Request<proto::ForgetLabelRequest>,
+    ) -> Result<Response<proto::ForgetLabelResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let remote_addr = request.remote_addr();
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let type_ = LabelType::from_int(inner.label_type as i16)
+                .map_err(|_| Status::invalid_argument("invalid label type"))?;
+
+            let archived = Label::archive(
+                &mut connection,
+                uuid,
+                type_,
+                &inner.reference,
+                &actor_of(inner.actor, remote_addr),
+            )
+            .map_err(|_| Status::internal("failed to archive label"))?;
+
+            if archived == 0 {
+                return Err(Status::not_found("label could not be found"));
+            }
+
+            Ok(Response::new(proto::ForgetLabelResponse {
+                wallet_id: inner.wallet_id,
+                label_type: inner.label_type,
+                reference: inner.reference,
+            }))
+        })
+    }
+
+    async fn restore_label(
+        &self,
+        request: Request<proto::RestoreLabelRequest>,
+    ) -> Result<Response<proto::RestoreLabelResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let type_ = LabelType::from_int(inner.label_type as i16)
+                .map_err(|_| Status::invalid_argument("invalid label type"))?;
+
+            if db::Wallet::find(
+                &mut connection,
+                Some(&uuid),
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+            )
+            .map_err(|_| Status::internal("failed to enumerate wallets"))?
+            .is_empty()
+            {
+                return Err(Status::not_found("wallet could not be found"));
+            }
+
+            let restored = Label::restore(&mut connection, uuid, type_, &inner.reference)
+                .map_err(|_| Status::internal("failed to restore label"))?;
+
+            if restored == 0 {
+                return Err(Status::not_found("archived label could not be found"));
+            }
+
+            Ok(Response::new(proto::RestoreLabelResponse {
+                wallet_id: inner.wallet_id,
+                label_type: inner.label_type,
+                reference: inner.reference,
+            }))
+        })
+    }
+
+    async fn import_labels(
+        &self,
+        request: Request<proto::ImportLabelsRequest>,
+    ) -> Result<Response<proto::ImportLabelsResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.wallet_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            if db::Wallet::find(
+                &mut connection,
+                Some(&uuid),
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+            )
+            .map_err(|_| Status::internal("failed to enumerate wallets"))?
+            .is_empty()
+            {
+                return Err(Status::not_found("wallet could not be found"));
+            }
+
+            let imported = Label::import(&mut connection, uuid, &inner.jsonl).map_err(|err| {
+                Status::invalid_argument(format!("invalid BIP329 labels: {}", err))
+            })?;
+
+            Ok(Response::new(proto::ImportLabelsResponse {
+                imported: imported as u64,
+            }))
+        })
+    }
 
-        let mut psbt = Psbt::from_db(&mut connection, Some(uuid))
-            .map_err(|_| Status::internal("failed to enumerate PSBTs"))?
-            .ok_or_else(|| Status::not_found("PSBT could not be found"))?;
+    async fn export_labels(
+        &self,
+        request: Request<proto::ExportLabelsRequest>,
+    ) -> Result<Response<proto::ExportLabelsResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let wallet_id = request.into_inner().wallet_id;
+
+            let uuid =
+                Uuid::from_str(&wallet_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let jsonl = Label::export(&mut connection, uuid)
+                .map_err(|_| Status::internal("failed to export labels"))?;
+
+            Ok(Response::new(proto::ExportLabelsResponse { jsonl }))
+        })
+    }
+
+    async fn create_bsms_session(
+        &self,
+        request: Request<proto::CreateBsmsSessionRequest>,
+    ) -> Result<Response<proto::CreateBsmsSessionResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let address_type = AddressType::from_int(inner.address_type as i16)
+                .map_err(|_| Status::invalid_argument("invalid address type"))?;
+
+            let network = Network::from_int(inner.network as i16)
+                .map_err(|_| Status::invalid_argument("invalid network"))?;
+
+            if matches!(&self.backends, Some(pool) if !pool.is_configured(network)) {
+                return Err(Status::failed_precondition(format!(
+                    "no backend configured for {:?}",
+                    network
+                )));
+            }
+
+            let mut session =
+                BsmsSession::new(address_type, network, inner.required_sigs, inner.signers)
+                    .map_err(|err| Status::invalid_argument(err.to_string()))?;
+
+            session
+                .save(&mut connection)
+                .map_err(|_| Status::internal("session could not be saved"))?;
+
+            Ok(Response::new(proto::CreateBsmsSessionResponse {
+                session: Some(session.into()),
+            }))
+        })
+    }
 
-        psbt.remove(&mut connection)
-            .map_err(|_| Status::internal("failed to remove cosigner"))?;
+    async fn submit_bsms_key_record(
+        &self,
+        request: Request<proto::SubmitBsmsKeyRecordRequest>,
+    ) -> Result<Response<proto::SubmitBsmsKeyRecordResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let inner = request.into_inner();
+
+            let uuid = Uuid::from_str(&inner.session_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            // Two records completing a session at once would both create its
+            // wallet.
+            let lock = self.session_lock(uuid);
+            let _guard = lock.lock().unwrap();
+
+            let mut session = BsmsSession::from_db(&mut connection, uuid)
+                .map_err(|_| Status::internal("failed to enumerate sessions"))?
+                .ok_or_else(|| Status::not_found("session could not be found"))?;
+
+            if session.is_complete() {
+                return Err(Status::failed_precondition(
+                    "session has already been completed",
+                ));
+            }
+
+            session
+                .submit_key_record(
+                    &mut connection,
+                    self.backends.as_ref(),
+                    &inner.key_record,
+                    &KeyOptions {
+                        xpub: self.signer_key(session.network())?,
+                        encryption_passphrase: self
+                            .config
+                            .key_encryption_passphrase
+                            .as_ref()
+                            .map(|passphrase| passphrase.expose().as_str()),
+                        ..Default::default()
+                    },
+                )
+                .map_err(|err| Status::invalid_argument(format!("invalid key record: {}", err)))?;
+
+            let descriptor_record = session
+                .descriptor_record(&mut connection, self.backends.as_ref())
+                .map_err(|_| Status::internal("failed to create descriptor record"))?;
+
+            let mut session: proto::BsmsSession = session.into();
+            session.descriptor_record = descriptor_record;
+
+            Ok(Response::new(proto::SubmitBsmsKeyRecordResponse {
+                session: Some(session),
+            }))
+        })
+    }
 
-        Ok(Response::new(proto::ForgetPsbtResponse { psbt_id }))
+    async fn get_bsms_session(
+        &self,
+        request: Request<proto::GetBsmsSessionRequest>,
+    ) -> Result<Response<proto::GetBsmsSessionResponse>, Status> {
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let session_id = request.into_inner().session_id;
+
+            let uuid = Uuid::from_str(&session_id)
+                .map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+            let session = match BsmsSession::from_db(&mut connection, uuid)
+                .map_err(|_| Status::internal("failed to enumerate sessions"))?
+            {
+                Some(session) => {
+                    let descriptor_record = session
+                        .descriptor_record(&mut connection, self.backends.as_ref())
+                        .map_err(|_| Status::internal("failed to create descriptor record"))?;
+
+                    let mut session: proto::BsmsSession = session.into();
+                    session.descriptor_record = descriptor_record;
+                    Some(session)
+                }
+                None => None,
+            };
+
+            Ok(Response::new(proto::GetBsmsSessionResponse { session }))
+        })
+    }
+
+    async fn backup(
+        &self,
+        request: Request<proto::BackupRequest>,
+    ) -> Result<Response<proto::BackupResponse>, Status> {
+        if !self.config.allow_backup_rpc {
+            return Err(Status::permission_denied("backups over RPC are disabled"));
+        }
+        let admin_token = self
+            .config
+            .admin_token
+            .as_ref()
+            .map(|token| token.expose().as_str())
+            .ok_or_else(|| Status::permission_denied("backups over RPC require an admin token"))?;
+
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let authorized = is_authorized(&request, admin_token);
+            let remote_addr = request.remote_addr();
+            let inner = request.into_inner();
+
+            let actor = actor_of(inner.actor, remote_addr);
+            if !authorized {
+                Backup::record(&mut connection, &actor, "refused: unauthenticated")
+                    .map_err(|_| Status::internal("failed to record backup"))?;
+                return Err(Status::unauthenticated("a valid admin token is required"));
+            }
+
+            if inner.passphrase.is_empty() {
+                return Err(Status::invalid_argument("a passphrase is required"));
+            }
+
+            let archive = Backup::create(&mut connection, &actor)
+                .and_then(|backup| backup.encrypt(&inner.passphrase))
+                .map_err(|err| Status::internal(format!("backup failed: {}", err)))?;
+
+            Ok(Response::new(proto::BackupResponse { archive }))
+        })
+    }
+
+    async fn export_internal_key(
+        &self,
+        request: Request<proto::ExportInternalKeyRequest>,
+    ) -> Result<Response<proto::ExportInternalKeyResponse>, Status> {
+        let admin_token = self
+            .config
+            .admin_token
+            .as_ref()
+            .map(|token| token.expose().as_str())
+            .ok_or_else(|| Status::permission_denied("key exports are disabled"))?;
+
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let authorized = is_authorized(&request, admin_token);
+            let remote_addr = request.remote_addr();
+            let inner = request.into_inner();
+
+            let actor = actor_of(inner.actor, remote_addr);
+            let mut cosigner =
+                Self::admin_cosigner(&mut connection, authorized, &inner.wallet_id, &actor)?;
+
+            let encryption_passphrase = self
+                .config
+                .key_encryption_passphrase
+                .as_ref()
+                .map(|passphrase| passphrase.expose().as_str())
+                .ok_or_else(|| {
+                    Status::failed_precondition("no key encryption passphrase is configured")
+                })?;
+
+            let mnemonic = cosigner
+                .export_mnemonic(&mut connection, encryption_passphrase, &actor)
+                .map_err(|err| Status::failed_precondition(err.to_string()))?;
+
+            Ok(Response::new(proto::ExportInternalKeyResponse {
+                mnemonic: mnemonic.expose().clone(),
+                has_passphrase: cosigner.mnemonic_has_passphrase(),
+            }))
+        })
+    }
+
+    async fn split_internal_key(
+        &self,
+        request: Request<proto::SplitInternalKeyRequest>,
+    ) -> Result<Response<proto::SplitInternalKeyResponse>, Status> {
+        let admin_token = self
+            .config
+            .admin_token
+            .as_ref()
+            .map(|token| token.expose().as_str())
+            .ok_or_else(|| Status::permission_denied("key exports are disabled"))?;
+
+        block_in_place(|| {
+            let mut connection = self
+                .db_pool
+                .get()
+                .map_err(|_| Status::unavailable("no database connection available"))?;
+            let authorized = is_authorized(&request, admin_token);
+            let remote_addr = request.remote_addr();
+            let inner = request.into_inner();
+
+            let actor = actor_of(inner.actor, remote_addr);
+            let cosigner =
+                Self::admin_cosigner(&mut connection, authorized, &inner.wallet_id, &actor)?;
+
+            let threshold = u8::try_from(inner.threshold)
+                .map_err(|_| Status::invalid_argument("invalid threshold"))?;
+            let share_count = u8::try_from(inner.share_count)
+                .map_err(|_| Status::invalid_argument("invalid share count"))?;
+
+            let shares = cosigner
+                .split_key(
+                    &mut connection,
+                    threshold,
+                    share_count,
+                    inner.passphrase.as_deref().unwrap_or_default(),
+                    &actor,
+                )
+                .map_err(|err| Status::invalid_argument(err.to_string()))?;
+
+            Ok(Response::new(proto::SplitInternalKeyResponse { shares }))
+        })
     }
 }
 
 impl Servicer {
+    /// Builds the server, applying pending database migrations if `migrate`
+    /// is set and refusing to start on an outdated schema otherwise.
     pub fn new(
         config: Config,
+        migrate: bool,
     ) -> Result<
         Router<grpc_server::OhmApiServer<Servicer>, tonic::transport::server::Unimplemented>,
         Box<dyn std::error::Error>,
     > {
+        let db_pool = db::establish_pool(&config.database_url, config.db_pool_size)?;
+        db::migrate(&db_pool, migrate)?;
+
+        let backends = match config.offline {
+            true => None,
+            false => {
+                let pool = Pool::new(config.backends.clone());
+                pool.verify()?;
+                Wallet::resume_broadcasts(&mut db_pool.get()?, &pool)?;
+                Some(pool)
+            }
+        };
+
+        let signer = signer::from_config(&config.signer)?;
+
         Ok(
             Server::builder().add_service(grpc_server::OhmApiServer::new(Servicer {
-                db_connection: Mutex::new(db::establish_connection(
-                    &config.db_path.to_string_lossy(),
-                )),
+                db_pool,
                 config,
+                backends,
+                signer,
+                wallet_locks: Mutex::new(HashMap::new()),
+                session_locks: Mutex::new(HashMap::new()),
             })),
         )
     }
+
+    /// Permanently deletes the records archived more than
+    /// `archive_retention_days` ago, returning how many were deleted.
+    pub fn purge(config: &Config) -> Result<usize, Box<dyn std::error::Error>> {
+        let db_pool = db::establish_pool(&config.database_url, 1)?;
+        db::migrate(&db_pool, false)?;
+
+        let archived_before =
+            Utc::now().naive_local() - Duration::days(config.archive_retention_days.into());
+        db::purge(&mut db_pool.get()?, archived_before)
+    }
+
+    /// Writes an encrypted backup of the whole database.
+    pub fn backup(
+        config: &Config,
+        passphrase: &str,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let db_pool = db::establish_pool(&config.database_url, 1)?;
+        db::migrate(&db_pool, false)?;
+
+        Backup::create(&mut db_pool.get()?, "ohm-server")?.encrypt(passphrase)
+    }
+
+    /// Recreates the records of a backup in an empty database, creating its
+    /// schema if needed, then rescans the restored wallets on every network
+    /// with a backend. Returns the backup, the number of wallets rescanned and
+    /// why the others couldn't be; those are rescanned when next used.
+    pub fn restore(
+        config: &Config,
+        archive: &[u8],
+        passphrase: &str,
+    ) -> Result<(Backup, usize, Vec<String>), Box<dyn std::error::Error>> {
+        let backup = Backup::decrypt(archive, passphrase)?;
+
+        let db_pool = db::establish_pool(&config.database_url, 1)?;
+        db::migrate(&db_pool, true)?;
+        let mut connection = db_pool.get()?;
+        backup.restore(&mut connection)?;
+
+        // The records are in place by now, so failing to reach a backend
+        // doesn't fail the restore.
+        let mut rescanned = 0;
+        let mut failures = vec![];
+        if !config.offline {
+            let pool = Pool::new(config.backends.clone());
+            for (network, _) in config.backends.iter() {
+                let wallets = Wallet::find(
+                    &mut connection,
+                    None,
+                    None,
+                    None,
+                    Some(network),
+                    None,
+                    None,
+                    None,
+                    false,
+                )?;
+
+                for wallet in wallets {
+                    let uuid = Uuid::from_str(wallet.uuid().unwrap())?;
+                    let rescan = Wallet::from_db(&mut connection, Some(&pool), Some(uuid))
+                        .and_then(|wallet| wallet.ok_or_else(|| "wallet disappeared".into()))
+                        .and_then(|mut wallet| wallet.save(&mut connection));
+
+                    match rescan {
+                        Ok(()) => rescanned += 1,
+                        Err(err) => failures.push(format!("wallet {}: {}", uuid, err)),
+                    }
+                }
+            }
+        }
+
+        Ok((backup, rescanned, failures))
+    }
+
+    /// Looks up the internal cosigner of a wallet for an admin request,
+    /// recording a refusal if the request was not authorized.
+    #[allow(clippy::result_large_err)]
+    fn admin_cosigner(
+        connection: &mut db::DbConnection,
+        authorized: bool,
+        wallet_id: &str,
+        actor: &str,
+    ) -> Result<Cosigner, Status> {
+        let uuid =
+            Uuid::from_str(wallet_id).map_err(|_| Status::invalid_argument("invalid UUID"))?;
+
+        let cosigner = Cosigner::find(connection, None, None, None, Some(uuid), None, false)
+            .map_err(|_| Status::internal("failed to enumerate cosigners"))?
+            .pop()
+            .ok_or_else(|| Status::not_found("wallet could not be found"))?;
+
+        if !authorized {
+            cosigner
+                .record_key_export(connection, actor, "refused: unauthenticated")
+                .map_err(|_| Status::internal("failed to record key export"))?;
+            return Err(Status::unauthenticated("a valid admin token is required"));
+        }
+
+        Ok(cosigner)
+    }
+
+    /// Recreates a wallet from its exported descriptors and SLIP-39 shares of
+    /// its internal key, creating the database schema if needed.
+    pub fn recover(
+        config: &Config,
+        network: Network,
+        export: &str,
+        shares: &[String],
+        passphrase: &str,
+    ) -> Result<Wallet, Box<dyn std::error::Error>> {
+        let xprv = Cosigner::recover_key(network, shares, passphrase)?;
+
+        let db_pool = db::establish_pool(&config.database_url, 1)?;
+        db::migrate(&db_pool, true)?;
+        let pool = (!config.offline).then(|| Pool::new(config.backends.clone()));
+
+        db_pool.get()?.transaction(|connection| {
+            let mut wallet = Wallet::recover(
+                connection,
+                pool.as_ref(),
+                network,
+                export,
+                &KeyOptions {
+                    xprv: Some(xprv),
+                    ..Default::default()
+                },
+            )?;
+            wallet.save(connection)?;
+
+            Ok(wallet)
+        })
+    }
+
+    /// Public key of the internal cosigner of a new wallet if it's to be held
+    /// by the configured signer.
+    #[allow(clippy::result_large_err)]
+    fn signer_key(&self, network: Network) -> Result<Option<signer::SignerKey>, Status> {
+        self.signer
+            .key(network)
+            .map_err(|err| Status::failed_precondition(format!("signer is unavailable: {}", err)))
+    }
+
+    /// Serialises operations that modify a wallet, while operations on other
+    /// wallets keep running concurrently.
+    fn wallet_lock(&self, uuid: Uuid) -> Arc<Mutex<()>> {
+        self.wallet_locks
+            .lock()
+            .unwrap()
+            .entry(uuid)
+            .or_default()
+            .clone()
+    }
+
+    /// Serialises key record submissions to a BSMS session.
+    fn session_lock(&self, uuid: Uuid) -> Arc<Mutex<()>> {
+        self.session_locks
+            .lock()
+            .unwrap()
+            .entry(uuid)
+            .or_default()
+            .clone()
+    }
 }
 
 pub type Client = grpc_client::OhmApiClient<Channel>;
@@ -527,12 +2103,22 @@ pub enum OhmResponse {
     RegisterCosigner(Response<proto::RegisterCosignerResponse>),
     GetCosigner(Response<proto::GetCosignerResponse>),
     FindCosigner(Response<proto::FindCosignerResponse>),
+    UpdateCosigner(Response<proto::UpdateCosignerResponse>),
     ForgetCosigner(Response<proto::ForgetCosignerResponse>),
+    RestoreCosigner(Response<proto::RestoreCosignerResponse>),
     CreateWallet(Response<proto::CreateWalletResponse>),
     GetWallet(Response<proto::GetWalletResponse>),
     FindWallet(Response<proto::FindWalletResponse>),
+    UpdateWallet(Response<proto::UpdateWalletResponse>),
     ForgetWallet(Response<proto::ForgetWalletResponse>),
+    RestoreWallet(Response<proto::RestoreWalletResponse>),
+    ExportWallet(Response<proto::ExportWalletResponse>),
+    RotateWallet(Response<proto::RotateWalletResponse>),
+    ListTransactions(Response<proto::ListTransactionsResponse>),
+    ListUtxos(Response<proto::ListUtxosResponse>),
     GetNewReceiveAddress(Response<proto::GetNewReceiveAddressResponse>),
+    ListReceiveAddresses(Response<proto::ListReceiveAddressesResponse>),
+    VerifyAddress(Response<proto::VerifyAddressResponse>),
     CreatePsbt(Response<proto::CreatePsbtResponse>),
     RegisterPsbt(Response<proto::RegisterPsbtResponse>),
     GetPsbt(Response<proto::GetPsbtResponse>),
@@ -540,5 +2126,19 @@ pub enum OhmResponse {
     SignPsbt(Response<proto::SignPsbtResponse>),
     CombineWithOtherPsbt(Response<proto::CombineWithOtherPsbtResponse>),
     BroadcastPsbt(Response<proto::BroadcastPsbtResponse>),
+    DecodePsbt(Response<proto::DecodePsbtResponse>),
     ForgetPsbt(Response<proto::ForgetPsbtResponse>),
+    RestorePsbt(Response<proto::RestorePsbtResponse>),
+    SetLabel(Response<proto::SetLabelResponse>),
+    FindLabel(Response<proto::FindLabelResponse>),
+    ForgetLabel(Response<proto::ForgetLabelResponse>),
+    RestoreLabel(Response<proto::RestoreLabelResponse>),
+    ImportLabels(Response<proto::ImportLabelsResponse>),
+    ExportLabels(Response<proto::ExportLabelsResponse>),
+    CreateBsmsSession(Response<proto::CreateBsmsSessionResponse>),
+    SubmitBsmsKeyRecord(Response<proto::SubmitBsmsKeyRecordResponse>),
+    GetBsmsSession(Response<proto::GetBsmsSessionResponse>),
+    Backup(Response<proto::BackupResponse>),
+    ExportInternalKey(Response<proto::ExportInternalKeyResponse>),
+    SplitInternalKey(Response<proto::SplitInternalKeyResponse>),
 }
@@ -0,0 +1,293 @@
+use std::{error::Error, fmt, str::FromStr};
+
+use bdk::bitcoin::{util::bip32, Address, OutPoint, PublicKey, Txid};
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db;
+pub use db::LabelType;
+
+impl LabelType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LabelType::Tx => "tx",
+            LabelType::Addr => "addr",
+            LabelType::Pubkey => "pubkey",
+            LabelType::Input => "input",
+            LabelType::Output => "output",
+            LabelType::Xpub => "xpub",
+        }
+    }
+}
+
+impl fmt::Display for LabelType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for LabelType {
+    type Err = String;
+
+    fn from_str(type_: &str) -> Result<Self, Self::Err> {
+        match type_ {
+            "tx" => Ok(LabelType::Tx),
+            "addr" => Ok(LabelType::Addr),
+            "pubkey" => Ok(LabelType::Pubkey),
+            "input" => Ok(LabelType::Input),
+            "output" => Ok(LabelType::Output),
+            "xpub" => Ok(LabelType::Xpub),
+            _ => Err(format!("unsupported label type: {}", type_)),
+        }
+    }
+}
+
+/// A single line of a BIP329 label export.
+#[derive(Deserialize, Serialize)]
+struct Bip329Record {
+    #[serde(rename = "type")]
+    type_: String,
+    #[serde(rename = "ref")]
+    ref_: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    origin: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spendable: Option<bool>,
+}
+
+pub struct Label {
+    wallet: Uuid,
+    type_: LabelType,
+    ref_: String,
+    label: String,
+    origin: Option<String>,
+    spendable: Option<bool>,
+    archived_at: Option<NaiveDateTime>,
+    archived_by: Option<String>,
+}
+
+impl Label {
+    pub fn new(
+        wallet: Uuid,
+        type_: LabelType,
+        ref_: &str,
+        label: &str,
+        origin: Option<String>,
+        spendable: Option<bool>,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::validate(type_, ref_)?;
+
+        if spendable.is_some() && type_ != LabelType::Output {
+            return Err("only output labels can be marked as (un)spendable".into());
+        }
+
+        Ok(Self {
+            wallet,
+            type_,
+            ref_: ref_.to_string(),
+            label: label.to_string(),
+            origin,
+            spendable,
+            archived_at: None,
+            archived_by: None,
+        })
+    }
+
+    fn validate(type_: LabelType, ref_: &str) -> Result<(), Box<dyn Error>> {
+        match type_ {
+            LabelType::Tx => {
+                Txid::from_str(ref_)?;
+            }
+            LabelType::Addr => {
+                Address::from_str(ref_)?;
+            }
+            LabelType::Pubkey => {
+                PublicKey::from_str(ref_)?;
+            }
+            LabelType::Input | LabelType::Output => {
+                OutPoint::from_str(ref_)?;
+            }
+            LabelType::Xpub => {
+                bip32::ExtendedPubKey::from_str(ref_)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn find(
+        connection: &mut db::DbConnection,
+        wallet: Uuid,
+        type_: Option<LabelType>,
+        ref_: Option<&str>,
+        include_archived: bool,
+    ) -> Result<Vec<Self>, Box<dyn Error>> {
+        let records = db::Label::find(connection, &wallet, type_, ref_, include_archived)?;
+
+        let mut labels = vec![];
+        for record in records {
+            labels.push(Label {
+                wallet: Uuid::from_str(&record.wallet_uuid)?,
+                type_: record.type_,
+                ref_: record.ref_,
+                label: record.label,
+                origin: record.origin,
+                spendable: record.spendable,
+                archived_at: record.archived_at,
+                archived_by: record.archived_by,
+            });
+        }
+
+        Ok(labels)
+    }
+
+    pub fn import(
+        connection: &mut db::DbConnection,
+        wallet: Uuid,
+        jsonl: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut labels = vec![];
+        for (number, line) in jsonl.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record: Bip329Record = serde_json::from_str(line)
+                .map_err(|err| format!("line {}: {}", number + 1, err))?;
+
+            // BIP329 asks importers to skip types they don't know about.
+            let type_ = match LabelType::from_str(&record.type_) {
+                Ok(type_) => type_,
+                Err(_) => continue,
+            };
+
+            let label = match record.label {
+                Some(label) => label,
+                None => continue,
+            };
+
+            labels.push(
+                Self::new(
+                    wallet,
+                    type_,
+                    &record.ref_,
+                    &label,
+                    record.origin,
+                    record.spendable,
+                )
+                .map_err(|err| format!("line {}: {}", number + 1, err))?,
+            );
+        }
+
+        // All or nothing, so a failing line can be fixed and the file imported again.
+        connection.transaction(|connection| {
+            for label in &labels {
+                label.save(connection)?;
+            }
+
+            Ok(labels.len())
+        })
+    }
+
+    pub fn export(
+        connection: &mut db::DbConnection,
+        wallet: Uuid,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut lines = vec![];
+        for label in Self::find(connection, wallet, None, None, false)? {
+            lines.push(serde_json::to_string(&Bip329Record {
+                type_: label.type_.to_string(),
+                ref_: label.ref_,
+                label: Some(label.label),
+                origin: label.origin,
+                spendable: label.spendable,
+            })?);
+        }
+
+        Ok(lines.into_iter().map(|line| line + "\n").collect())
+    }
+
+    pub fn wallet(&self) -> &Uuid {
+        &self.wallet
+    }
+
+    pub fn type_(&self) -> LabelType {
+        self.type_
+    }
+
+    pub fn reference(&self) -> &str {
+        &self.ref_
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn origin(&self) -> Option<&str> {
+        self.origin.as_deref()
+    }
+
+    pub fn spendable(&self) -> Option<bool> {
+        self.spendable
+    }
+
+    pub fn archived_at(&self) -> Option<&NaiveDateTime> {
+        self.archived_at.as_ref()
+    }
+
+    pub fn archived_by(&self) -> Option<&str> {
+        self.archived_by.as_deref()
+    }
+
+    pub fn archive(
+        connection: &mut db::DbConnection,
+        wallet: Uuid,
+        type_: LabelType,
+        ref_: &str,
+        actor: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        db::Label::archive(
+            connection,
+            &wallet,
+            type_,
+            ref_,
+            Utc::now().naive_local(),
+            actor,
+        )
+    }
+
+    pub fn restore(
+        connection: &mut db::DbConnection,
+        wallet: Uuid,
+        type_: LabelType,
+        ref_: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        db::Label::restore(connection, &wallet, type_, ref_)
+    }
+
+    pub fn remove(
+        connection: &mut db::DbConnection,
+        wallet: Uuid,
+        type_: LabelType,
+        ref_: &str,
+    ) -> Result<usize, Box<dyn Error>> {
+        db::Label::remove(connection, &wallet, type_, ref_)
+    }
+
+    pub fn save(&self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
+        db::Label::new(
+            &self.wallet,
+            self.type_,
+            &self.ref_,
+            &self.label,
+            self.origin.as_deref(),
+            self.spendable,
+        )
+        .upsert(connection)?;
+
+        Ok(())
+    }
+}
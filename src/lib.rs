@@ -7,13 +7,38 @@ mod grpc;
 pub use grpc::{proto, Client, Response, Server};
 
 mod config;
-pub use config::Config;
+pub use config::{BackendConfig, Backends, Config, GapLimitPolicy, SignerConfig};
+
+mod backend;
+pub use backend::Pool;
 
 mod cosigner;
-pub use cosigner::{Cosigner, CosignerType};
+pub use cosigner::{Cosigner, CosignerType, KeyOptions};
 
 mod wallet;
-pub use wallet::{AddressType, Network, Wallet};
+pub use wallet::{AddressKey, AddressType, AddressVerification, Network, ReceiveAddress, Wallet};
+
+mod signer;
+pub use signer::Signer;
 
 mod psbt;
 pub use psbt::Psbt;
+
+mod export;
+pub use export::ExportFormat;
+
+mod bsms;
+pub use bsms::BsmsSession;
+
+mod label;
+pub use label::{Label, LabelType};
+
+mod backup;
+pub use backup::Backup;
+
+mod crypto;
+
+mod secret;
+pub use secret::Secret;
+
+mod slip39;
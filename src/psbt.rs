@@ -1,15 +1,27 @@
 use std::{error::Error, str::FromStr};
 
-use bdk::bitcoin::psbt::PartiallySignedTransaction;
-use diesel::SqliteConnection;
+use bdk::bitcoin::{psbt::PartiallySignedTransaction, OutPoint, Script};
+use chrono::{NaiveDateTime, Utc};
 use uuid::Uuid;
 
 use crate::db;
 
+pub struct PsbtInput {
+    pub outpoint: OutPoint,
+    pub value: Option<u64>,
+}
+
+pub struct PsbtOutput {
+    pub script_pubkey: Script,
+    pub value: u64,
+}
+
 pub struct Psbt {
     uuid: Option<String>,
     bdk_handle: PartiallySignedTransaction,
     wallet: Uuid,
+    archived_at: Option<NaiveDateTime>,
+    archived_by: Option<String>,
 }
 
 impl Psbt {
@@ -18,14 +30,16 @@ impl Psbt {
             uuid: None,
             bdk_handle,
             wallet,
+            archived_at: None,
+            archived_by: None,
         }
     }
 
     pub fn from_db(
-        connection: &mut SqliteConnection,
+        connection: &mut db::DbConnection,
         uuid: Option<Uuid>,
     ) -> Result<Option<Self>, Box<dyn Error>> {
-        let mut psbts = Self::find(connection, uuid, None)?;
+        let mut psbts = Self::find(connection, uuid, None, false)?;
 
         Ok(match !psbts.is_empty() {
             true => Some(psbts.remove(0)),
@@ -34,11 +48,12 @@ impl Psbt {
     }
 
     pub fn find(
-        connection: &mut SqliteConnection,
+        connection: &mut db::DbConnection,
         uuid: Option<Uuid>,
         wallet: Option<Uuid>,
+        include_archived: bool,
     ) -> Result<Vec<Self>, Box<dyn Error>> {
-        let records = db::Psbt::find(connection, uuid.as_ref(), wallet.as_ref())?;
+        let records = db::Psbt::find(connection, uuid.as_ref(), wallet.as_ref(), include_archived)?;
 
         let mut transactions = vec![];
         for record in records {
@@ -46,6 +61,8 @@ impl Psbt {
                 uuid: Some(record.uuid),
                 bdk_handle: PartiallySignedTransaction::from_str(&record.base64)?,
                 wallet: Uuid::from_str(&record.wallet_uuid)?,
+                archived_at: record.archived_at,
+                archived_by: record.archived_by,
             });
         }
 
@@ -56,6 +73,52 @@ impl Psbt {
         self.bdk_handle.to_string()
     }
 
+    pub fn inputs(&self) -> Vec<PsbtInput> {
+        let tx = &self.bdk_handle.unsigned_tx;
+
+        tx.input
+            .iter()
+            .zip(self.bdk_handle.inputs.iter())
+            .map(|(txin, input)| {
+                let value = match (&input.witness_utxo, &input.non_witness_utxo) {
+                    (Some(utxo), _) => Some(utxo.value),
+                    (None, Some(prev_tx)) => prev_tx
+                        .output
+                        .get(txin.previous_output.vout as usize)
+                        .map(|output| output.value),
+                    (None, None) => None,
+                };
+
+                PsbtInput {
+                    outpoint: txin.previous_output,
+                    value,
+                }
+            })
+            .collect()
+    }
+
+    pub fn outputs(&self) -> Vec<PsbtOutput> {
+        self.bdk_handle
+            .unsigned_tx
+            .output
+            .iter()
+            .map(|output| PsbtOutput {
+                script_pubkey: output.script_pubkey.clone(),
+                value: output.value,
+            })
+            .collect()
+    }
+
+    pub fn fee(&self) -> Option<u64> {
+        let mut input_value = 0;
+        for input in self.inputs() {
+            input_value += input.value?;
+        }
+
+        let output_value: u64 = self.outputs().iter().map(|output| output.value).sum();
+        input_value.checked_sub(output_value)
+    }
+
     pub fn inner(&mut self) -> &mut PartiallySignedTransaction {
         &mut self.bdk_handle
     }
@@ -68,7 +131,40 @@ impl Psbt {
         self.uuid.as_deref()
     }
 
-    pub fn remove(&mut self, connection: &mut SqliteConnection) -> Result<(), Box<dyn Error>> {
+    pub fn archived_at(&self) -> Option<&NaiveDateTime> {
+        self.archived_at.as_ref()
+    }
+
+    pub fn archived_by(&self) -> Option<&str> {
+        self.archived_by.as_deref()
+    }
+
+    pub fn archive(
+        &mut self,
+        connection: &mut db::DbConnection,
+        actor: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(uuid) = &self.uuid {
+            let archived_at = Utc::now().naive_local();
+            db::Psbt::archive(connection, uuid, archived_at, actor)?;
+            self.archived_at = Some(archived_at);
+            self.archived_by = Some(actor.to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn restore(&mut self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
+        if let Some(uuid) = &self.uuid {
+            db::Psbt::restore(connection, uuid)?;
+        }
+        self.archived_at = None;
+        self.archived_by = None;
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
         if let Some(uuid) = &self.uuid {
             db::Psbt::remove(connection, uuid)?;
         }
@@ -77,7 +173,7 @@ impl Psbt {
         Ok(())
     }
 
-    pub fn save(&mut self, connection: &mut SqliteConnection) -> Result<(), Box<dyn Error>> {
+    pub fn save(&mut self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
         let base64 = self.base64();
         let mut new_record = db::Psbt::new(&base64, &self.wallet);
 
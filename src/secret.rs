@@ -0,0 +1,107 @@
+use std::{ffi::CString, fmt, ptr, sync::atomic};
+
+use bdk::bitcoin::{secp256k1, util::bip32};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::Zeroize;
+
+/// Secret key material, wiped from memory when dropped and redacted from
+/// `Debug` output. Copies made while it's exposed are out of its reach.
+pub struct Secret<T: Wipe>(T);
+
+/// Overwrites a value in place.
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+impl<T: Wipe> Secret<T> {
+    pub fn new(secret: T) -> Self {
+        Self(secret)
+    }
+
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Wipe> From<T> for Secret<T> {
+    fn from(secret: T) -> Self {
+        Self(secret)
+    }
+}
+
+impl<T: Wipe + Clone> Clone for Secret<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Wipe + Default> Default for Secret<T> {
+    fn default() -> Self {
+        Self(T::default())
+    }
+}
+
+impl<T: Wipe> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+impl<T: Wipe> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<T: Wipe + Serialize> Serialize for Secret<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Wipe + Deserialize<'de>> Deserialize<'de> for Secret<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self)
+    }
+}
+
+impl Wipe for String {
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Wipe for Option<String> {
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Wipe for CString {
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Wipe for Vec<u8> {
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Wipe for bip32::ExtendedPrivKey {
+    fn wipe(&mut self) {
+        // This version of secp256k1 can't erase a key, so overwrite it with
+        // a valid dummy one.
+        let blank = bip32::ExtendedPrivKey {
+            network: self.network,
+            depth: 0,
+            parent_fingerprint: Default::default(),
+            child_number: bip32::ChildNumber::from(0),
+            private_key: secp256k1::ONE_KEY,
+            chain_code: bip32::ChainCode::from(&[0; 32][..]),
+        };
+        unsafe { ptr::write_volatile(self, blank) };
+        atomic::compiler_fence(atomic::Ordering::SeqCst);
+    }
+}
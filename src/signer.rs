@@ -0,0 +1,801 @@
+use std::{
+    error::Error,
+    ffi::CString,
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::{
+        raw::{c_char, c_int},
+        unix::net::UnixStream,
+    },
+    path::PathBuf,
+    ptr,
+    str::FromStr,
+    time::Duration,
+};
+
+use bdk::{
+    bitcoin::{
+        self,
+        consensus::encode::{deserialize, serialize},
+        psbt::PartiallySignedTransaction,
+        secp256k1,
+        util::{bip32, sighash::SighashCache},
+        EcdsaSig, EcdsaSighashType,
+    },
+    miniscript::descriptor::{DescriptorSecretKey, Wildcard},
+    signer::SignerError,
+};
+
+use super::{cosigner::parse_key, secret::Wipe, Cosigner, Network, Secret, SignerConfig};
+
+/// Public key of a signer along with its origin.
+pub type SignerKey = (bip32::ExtendedPubKey, bip32::KeySource);
+
+/// Signs PSBTs on behalf of the internal cosigner of a wallet.
+pub trait Signer: Send + Sync {
+    /// Key the internal cosigners of new wallets are children of, or `None`
+    /// if each of them gets a fresh key kept in the database.
+    fn key(&self, network: Network) -> Result<Option<SignerKey>, Box<dyn Error>>;
+
+    /// Adds the signatures of the internal cosigner to the PSBT.
+    fn sign(
+        &self,
+        psbt: &mut PartiallySignedTransaction,
+        cosigner: &Cosigner,
+    ) -> Result<(), Box<dyn Error>>;
+}
+
+pub fn from_config(config: &SignerConfig) -> Result<Box<dyn Signer>, Box<dyn Error>> {
+    Ok(match config {
+        SignerConfig::Database => Box::new(DatabaseSigner),
+        SignerConfig::KeyFile { path } => Box::new(KeyFileSigner { path: path.clone() }),
+        SignerConfig::Library {
+            path,
+            xpub,
+            slot,
+            pin,
+        } => Box::new(LibrarySigner::load(
+            path,
+            signer_key(parse_key(xpub)?)?,
+            *slot,
+            pin.as_ref()
+                .map(|pin| CString::new(pin.expose().as_str()))
+                .transpose()?
+                .map(Secret::new),
+        )?),
+        SignerConfig::Socket {
+            path,
+            xpub,
+            timeout,
+        } => Box::new(SocketSigner {
+            path: path.clone(),
+            key: signer_key(parse_key(xpub)?)?,
+            timeout: Duration::from_secs(*timeout),
+        }),
+    })
+}
+
+/// Takes the origin of a signer's key as configured, which a master key
+/// doesn't need.
+fn signer_key(
+    (xpub, origin): (bip32::ExtendedPubKey, Option<bip32::KeySource>),
+) -> Result<SignerKey, Box<dyn Error>> {
+    match origin {
+        Some(origin) => Ok((xpub, origin)),
+        None if xpub.depth == 0 => {
+            Ok((xpub, (xpub.fingerprint(), bip32::DerivationPath::master())))
+        }
+        None => Err("the signer's key requires its [fingerprint/path] origin".into()),
+    }
+}
+
+/// Path from the signer's key to the key of the internal cosigner, which is
+/// a child of it, or the key itself for wallets created before.
+pub fn derivation_path(
+    (xpub, (fingerprint, path)): &SignerKey,
+    cosigner: &Cosigner,
+) -> Result<bip32::DerivationPath, Box<dyn Error>> {
+    if cosigner.xpub() == xpub {
+        return Ok(bip32::DerivationPath::master());
+    }
+
+    let children = match cosigner.origin() {
+        Some((cosigner_fingerprint, cosigner_path)) if cosigner_fingerprint == *fingerprint => {
+            cosigner_path
+                .as_ref()
+                .strip_prefix(path.as_ref())
+                .map(bip32::DerivationPath::from)
+        }
+        _ => None,
+    };
+
+    match children {
+        Some(children)
+            if xpub.derive_pub(&secp256k1::Secp256k1::new(), &children)? == *cosigner.xpub() =>
+        {
+            Ok(children)
+        }
+        _ => Err("the signer doesn't hold the key of this wallet".into()),
+    }
+}
+
+/// Signs with the key stored along with the internal cosigner.
+pub struct DatabaseSigner;
+
+impl Signer for DatabaseSigner {
+    fn key(&self, _network: Network) -> Result<Option<SignerKey>, Box<dyn Error>> {
+        Ok(None)
+    }
+
+    fn sign(
+        &self,
+        psbt: &mut PartiallySignedTransaction,
+        cosigner: &Cosigner,
+    ) -> Result<(), Box<dyn Error>> {
+        let xprv = cosigner
+            .xprv()?
+            .ok_or("the key of the internal cosigner isn't kept in the database")?;
+
+        sign_with_xprv(psbt, xprv.expose(), cosigner.origin())
+    }
+}
+
+/// Signs with an xprv, optionally preceded by its origin, read from a file.
+struct KeyFileSigner {
+    path: PathBuf,
+}
+
+impl KeyFileSigner {
+    fn xprv(
+        &self,
+    ) -> Result<(Secret<bip32::ExtendedPrivKey>, Option<bip32::KeySource>), Box<dyn Error>> {
+        let contents = Secret::new(fs::read_to_string(&self.path).map_err(
+            |err| -> Box<dyn Error> {
+                format!("failed to read {}: {}", self.path.display(), err).into()
+            },
+        )?);
+
+        match DescriptorSecretKey::from_str(contents.expose().trim()) {
+            Ok(DescriptorSecretKey::XPrv(mut xkey))
+                if xkey.derivation_path.as_ref().is_empty() && xkey.wildcard == Wildcard::None =>
+            {
+                let xprv = Secret::new(xkey.xkey);
+                xkey.xkey.wipe();
+                Ok((xprv, xkey.origin))
+            }
+            _ => Err(format!(
+                "{} should hold an xprv, optionally preceded by its [fingerprint/path] origin",
+                self.path.display()
+            )
+            .into()),
+        }
+    }
+}
+
+impl Signer for KeyFileSigner {
+    fn key(&self, network: Network) -> Result<Option<SignerKey>, Box<dyn Error>> {
+        let (xprv, origin) = self.xprv()?;
+        check_network(xprv.expose().network, network)?;
+
+        let xpub = bip32::ExtendedPubKey::from_priv(&secp256k1::Secp256k1::new(), xprv.expose());
+        Ok(Some(signer_key((xpub, origin))?))
+    }
+
+    fn sign(
+        &self,
+        psbt: &mut PartiallySignedTransaction,
+        cosigner: &Cosigner,
+    ) -> Result<(), Box<dyn Error>> {
+        let (xprv, origin) = self.xprv()?;
+        let xpub = bip32::ExtendedPubKey::from_priv(&secp256k1::Secp256k1::new(), xprv.expose());
+        let path = derivation_path(&signer_key((xpub, origin))?, cosigner)?;
+        let xprv = Secret::new(
+            xprv.expose()
+                .derive_priv(&secp256k1::Secp256k1::new(), &path)?,
+        );
+
+        sign_with_xprv(psbt, xprv.expose(), cosigner.origin())
+    }
+}
+
+/// `int ohm_signer_sign(uint64_t slot, const char *pin, const uint8_t *psbt,
+/// size_t psbt_len, uint8_t **out, size_t *out_len)` signs the serialized
+/// PSBT, returning zero and the signed PSBT in a buffer the module allocated.
+type SignFn =
+    unsafe extern "C" fn(u64, *const c_char, *const u8, usize, *mut *mut u8, *mut usize) -> c_int;
+/// `void ohm_signer_free(uint8_t *out, size_t out_len)` releases that buffer.
+type FreeFn = unsafe extern "C" fn(*mut u8, usize);
+
+/// Hands PSBTs to a shared library implementing the two functions above, e.g.
+/// a bridge to an HSM. This is ohm's own ABI, not PKCS#11: the library gets
+/// the whole PSBT and finds the keys to sign with in it.
+struct LibrarySigner {
+    key: SignerKey,
+    slot: u64,
+    pin: Option<Secret<CString>>,
+    sign: SignFn,
+    free: FreeFn,
+    // Keeps the functions above loaded.
+    _library: libloading::Library,
+}
+
+impl LibrarySigner {
+    fn load(
+        path: &PathBuf,
+        key: SignerKey,
+        slot: u64,
+        pin: Option<Secret<CString>>,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Safety: the library is trusted to run no harmful initialisation and
+        // to implement the functions with the signatures above.
+        unsafe {
+            let library = libloading::Library::new(path).map_err(|err| -> Box<dyn Error> {
+                format!("failed to load {}: {}", path.display(), err).into()
+            })?;
+            let sign = *library.get::<SignFn>(b"ohm_signer_sign\0")?;
+            let free = *library.get::<FreeFn>(b"ohm_signer_free\0")?;
+
+            Ok(Self {
+                key,
+                slot,
+                pin,
+                sign,
+                free,
+                _library: library,
+            })
+        }
+    }
+}
+
+impl Signer for LibrarySigner {
+    fn key(&self, network: Network) -> Result<Option<SignerKey>, Box<dyn Error>> {
+        check_network(self.key.0.network, network)?;
+
+        Ok(Some(self.key.clone()))
+    }
+
+    fn sign(
+        &self,
+        psbt: &mut PartiallySignedTransaction,
+        _cosigner: &Cosigner,
+    ) -> Result<(), Box<dyn Error>> {
+        let unsigned = serialize(psbt);
+
+        // Safety: the library is still loaded, and trusted to leave `out`
+        // alone unless it succeeds.
+        let signed = unsafe {
+            let mut out = ptr::null_mut();
+            let mut out_len = 0;
+            let status = (self.sign)(
+                self.slot,
+                self.pin
+                    .as_ref()
+                    .map_or(ptr::null(), |pin| pin.expose().as_ptr()),
+                unsigned.as_ptr(),
+                unsigned.len(),
+                &mut out,
+                &mut out_len,
+            );
+            if status != 0 || out.is_null() {
+                return Err(format!("signer library failed with status {}", status).into());
+            }
+
+            let signed = std::slice::from_raw_parts(out, out_len).to_vec();
+            (self.free)(out, out_len);
+            signed
+        };
+
+        merge(psbt, deserialize(&signed)?)
+    }
+}
+
+/// Hands PSBTs to a signer process: ohm writes the base64 encoded PSBT on a
+/// line of its own and reads back the signed PSBT the same way, or a line
+/// starting with `error:` if the signer refused.
+struct SocketSigner {
+    path: PathBuf,
+    key: SignerKey,
+    timeout: Duration,
+}
+
+impl Signer for SocketSigner {
+    fn key(&self, network: Network) -> Result<Option<SignerKey>, Box<dyn Error>> {
+        check_network(self.key.0.network, network)?;
+
+        Ok(Some(self.key.clone()))
+    }
+
+    fn sign(
+        &self,
+        psbt: &mut PartiallySignedTransaction,
+        _cosigner: &Cosigner,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut stream = UnixStream::connect(&self.path).map_err(|err| -> Box<dyn Error> {
+            format!("failed to connect to {}: {}", self.path.display(), err).into()
+        })?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        writeln!(stream, "{}", psbt)?;
+
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        let line = line.trim();
+        if let Some(message) = line.strip_prefix("error:") {
+            return Err(format!("signer refused: {}", message.trim()).into());
+        }
+
+        merge(psbt, PartiallySignedTransaction::from_str(line)?)
+    }
+}
+
+/// Signs every input the key has a derivation for on either keychain, the
+/// way BDK's own software signers do, deriving each private key only for as
+/// long as its signature takes.
+fn sign_with_xprv(
+    psbt: &mut PartiallySignedTransaction,
+    xprv: &bip32::ExtendedPrivKey,
+    origin: Option<bip32::KeySource>,
+) -> Result<(), Box<dyn Error>> {
+    // Without the previous transaction a segwit input's amount can't be
+    // trusted, see BDK's `SignOptions::trust_witness_utxo`.
+    if psbt.inputs.iter().any(|input| {
+        input.final_script_sig.is_none()
+            && input.final_script_witness.is_none()
+            && input.non_witness_utxo.is_none()
+    }) {
+        return Err(SignerError::MissingNonWitnessUtxo.into());
+    }
+
+    // Other sighash types leave outputs unsigned for anyone to redirect, see
+    // BDK's `SignOptions::allow_all_sighashes`.
+    if psbt.inputs.iter().any(|input| {
+        input.sighash_type.is_some() && input.sighash_type != Some(EcdsaSighashType::All.into())
+    }) {
+        return Err(SignerError::NonStandardSighash.into());
+    }
+
+    let secp = secp256k1::Secp256k1::new();
+    let (fingerprint, path) =
+        origin.unwrap_or_else(|| (xprv.fingerprint(&secp), bip32::DerivationPath::master()));
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+
+    for (index, input) in psbt.inputs.iter_mut().enumerate() {
+        if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+            continue;
+        }
+
+        let keys: Vec<_> = input
+            .bip32_derivation
+            .iter()
+            .filter_map(|(public_key, (key_fingerprint, key_path))| {
+                let children = key_path.as_ref().strip_prefix(path.as_ref())?;
+                match children {
+                    [bip32::ChildNumber::Normal { index: 0..=1 }, bip32::ChildNumber::Normal { .. }]
+                        if *key_fingerprint == fingerprint =>
+                    {
+                        Some((*public_key, bip32::DerivationPath::from(children)))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        if keys.is_empty() {
+            continue;
+        }
+
+        let hash_ty = input.ecdsa_hash_ty()?;
+        // A previous transaction other than the one spent could lie about
+        // the amount, the very thing requiring it guards against.
+        let previous_output = psbt.unsigned_tx.input[index].previous_output;
+        let spent = match &input.non_witness_utxo {
+            Some(previous) if previous.txid() == previous_output.txid => previous
+                .output
+                .get(previous_output.vout as usize)
+                .ok_or(SignerError::InvalidNonWitnessUtxo)?,
+            Some(_) => return Err(SignerError::InvalidNonWitnessUtxo.into()),
+            None => return Err(SignerError::MissingNonWitnessUtxo.into()),
+        };
+        let sighash = match &input.witness_script {
+            Some(script) => cache.segwit_signature_hash(index, script, spent.value, hash_ty)?,
+            None => cache.legacy_signature_hash(
+                index,
+                input.redeem_script.as_ref().unwrap_or(&spent.script_pubkey),
+                hash_ty.to_u32(),
+            )?,
+        };
+        let message = secp256k1::Message::from_slice(&sighash)?;
+
+        for (public_key, children) in keys {
+            let key = Secret::new(xprv.derive_priv(&secp, &children)?);
+            if secp256k1::PublicKey::from_secret_key(&secp, &key.expose().private_key) != public_key
+            {
+                return Err(SignerError::InvalidKey.into());
+            }
+
+            let sig = secp.sign_ecdsa(&message, &key.expose().private_key);
+            input.partial_sigs.insert(
+                bitcoin::PublicKey::new(public_key),
+                EcdsaSig { sig, hash_ty },
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Takes over the signatures of a PSBT a signer returned, provided it still
+/// spends and pays the same.
+fn merge(
+    psbt: &mut PartiallySignedTransaction,
+    signed: PartiallySignedTransaction,
+) -> Result<(), Box<dyn Error>> {
+    if signed.unsigned_tx != psbt.unsigned_tx {
+        return Err("the signer returned a different transaction".into());
+    }
+
+    Ok(psbt.combine(signed)?)
+}
+
+fn check_network(key: bitcoin::Network, network: Network) -> Result<(), Box<dyn Error>> {
+    // Extended keys only tell mainnet from the test networks apart.
+    if (key == bitcoin::Network::Bitcoin) != matches!(network, Network::Mainnet) {
+        return Err("the signer's key is for another network".into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        os::unix::net::UnixListener,
+        path::Path,
+        process,
+        thread::{self, JoinHandle},
+    };
+
+    use bdk::{
+        bitcoin::{OutPoint, Script, Transaction, TxIn, TxOut, Witness},
+        descriptor::{Descriptor, DescriptorPublicKey},
+        miniscript::{descriptor::DescriptorXKey, DescriptorTrait, ForEachKey},
+        signer::{InputSigner, SignerContext, SignerWrapper},
+        SignOptions,
+    };
+
+    use super::*;
+    use crate::{cosigner::format_origin, CosignerType, KeyOptions};
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ohm-signer-{}-{}", process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn account(seed: u8) -> (bip32::ExtendedPrivKey, bip32::KeySource) {
+        let secp = secp256k1::Secp256k1::new();
+        let master =
+            bip32::ExtendedPrivKey::new_master(bitcoin::Network::Testnet, &[seed; 32]).unwrap();
+        let path = bip32::DerivationPath::from_str("m/48'/1'/0'/2'").unwrap();
+
+        (
+            master.derive_priv(&secp, &path).unwrap(),
+            (master.fingerprint(&secp), path),
+        )
+    }
+
+    /// A PSBT spending the first receive address of a 2-of-2 between the
+    /// cosigner and another key.
+    fn psbt(cosigner: &Cosigner) -> PartiallySignedTransaction {
+        let secp = secp256k1::Secp256k1::new();
+        let other = bip32::ExtendedPubKey::from_priv(&secp, &account(9).0);
+        let descriptor = format!(
+            "wsh(sortedmulti(2,{}{}/0/*,{}/0/*))",
+            cosigner
+                .origin()
+                .map(|origin| format!("[{}]", format_origin(&origin)))
+                .unwrap_or_default(),
+            cosigner.xpub(),
+            other
+        );
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str(&descriptor)
+            .unwrap()
+            .derive(0);
+        let script = descriptor.derived_descriptor(&secp, 0).unwrap();
+
+        let previous = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut {
+                value: 10_000,
+                script_pubkey: script.script_pubkey(),
+            }],
+        };
+        let transaction = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(previous.txid(), 0),
+                script_sig: Script::new(),
+                sequence: 0xffff_ffff,
+                witness: Witness::default(),
+            }],
+            output: vec![TxOut {
+                value: 9_000,
+                script_pubkey: Script::new(),
+            }],
+        };
+
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(transaction).unwrap();
+        let input = &mut psbt.inputs[0];
+        input.witness_utxo = Some(previous.output[0].clone());
+        input.non_witness_utxo = Some(previous);
+        input.witness_script = Some(script.explicit_script().unwrap());
+        descriptor.for_each_key(|key| {
+            let key = key.as_key();
+            input.bip32_derivation.insert(
+                key.derive_public_key(&secp).unwrap().inner,
+                (key.master_fingerprint(), key.full_derivation_path()),
+            );
+            true
+        });
+
+        psbt
+    }
+
+    /// Whether the PSBT holds a signature of the cosigner's first receive key.
+    fn signed_by(psbt: &PartiallySignedTransaction, cosigner: &Cosigner) -> bool {
+        let secp = secp256k1::Secp256k1::new();
+        let path = bip32::DerivationPath::from_str("m/0/0").unwrap();
+        let public_key = cosigner.xpub().derive_pub(&secp, &path).unwrap().to_pub();
+
+        psbt.inputs[0].partial_sigs.contains_key(&public_key)
+    }
+
+    fn key_file(name: &str, contents: &str) -> KeyFileSigner {
+        let path = temp_path(name);
+        fs::write(&path, contents).unwrap();
+
+        KeyFileSigner { path }
+    }
+
+    #[test]
+    fn database_signer() {
+        let cosigner = Cosigner::new_internal(Network::Regtest, &KeyOptions::default()).unwrap();
+        let mut psbt = psbt(&cosigner);
+
+        DatabaseSigner.sign(&mut psbt, &cosigner).unwrap();
+        assert!(signed_by(&psbt, &cosigner));
+    }
+
+    #[test]
+    fn non_standard_sighash() {
+        let cosigner = Cosigner::new_internal(Network::Regtest, &KeyOptions::default()).unwrap();
+
+        for sighash in [
+            EcdsaSighashType::None,
+            EcdsaSighashType::Single,
+            EcdsaSighashType::AllPlusAnyoneCanPay,
+        ] {
+            let mut psbt = psbt(&cosigner);
+            psbt.inputs[0].sighash_type = Some(sighash.into());
+
+            let err = DatabaseSigner.sign(&mut psbt, &cosigner).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<SignerError>(),
+                Some(SignerError::NonStandardSighash)
+            ));
+            assert!(psbt.inputs[0].partial_sigs.is_empty());
+        }
+
+        let mut psbt = psbt(&cosigner);
+        psbt.inputs[0].sighash_type = Some(EcdsaSighashType::All.into());
+        DatabaseSigner.sign(&mut psbt, &cosigner).unwrap();
+        assert!(signed_by(&psbt, &cosigner));
+    }
+
+    #[test]
+    fn mismatched_previous_transaction() {
+        let cosigner = Cosigner::new_internal(Network::Regtest, &KeyOptions::default()).unwrap();
+        let mut psbt = psbt(&cosigner);
+
+        // Claims the spent output holds less than it does, which would hide
+        // the difference in the fee.
+        let previous = psbt.inputs[0].non_witness_utxo.as_mut().unwrap();
+        previous.output[0].value = 9_500;
+
+        let err = DatabaseSigner.sign(&mut psbt, &cosigner).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SignerError>(),
+            Some(SignerError::InvalidNonWitnessUtxo)
+        ));
+        assert!(psbt.inputs[0].partial_sigs.is_empty());
+    }
+
+    #[test]
+    fn signs_like_bdk() {
+        let cosigner = Cosigner::new_internal(Network::Regtest, &KeyOptions::default()).unwrap();
+        let mut psbt = psbt(&cosigner);
+        let mut expected = psbt.clone();
+
+        DatabaseSigner.sign(&mut psbt, &cosigner).unwrap();
+
+        let key = DescriptorXKey {
+            origin: cosigner.origin(),
+            xkey: *cosigner.xprv().unwrap().unwrap().expose(),
+            derivation_path: bip32::DerivationPath::from_str("m/0").unwrap(),
+            wildcard: Wildcard::Unhardened,
+        };
+        SignerWrapper::new(key, SignerContext::Segwitv0)
+            .sign_input(
+                &mut expected,
+                0,
+                &SignOptions::default(),
+                &secp256k1::Secp256k1::new(),
+            )
+            .unwrap();
+
+        assert_eq!(psbt.inputs[0].partial_sigs, expected.inputs[0].partial_sigs);
+    }
+
+    #[test]
+    fn key_file_signer() {
+        let (xprv, origin) = account(1);
+        let signer = key_file("account", &format!("[{}]{}", format_origin(&origin), xprv));
+        let key = signer.key(Network::Regtest).unwrap().unwrap();
+        assert_eq!(key.1, origin);
+
+        // Every wallet gets a child of its own.
+        let options = KeyOptions {
+            xpub: Some(key.clone()),
+            ..Default::default()
+        };
+        let cosigner = Cosigner::new_internal(Network::Regtest, &options).unwrap();
+        let other = Cosigner::new_internal(Network::Regtest, &options).unwrap();
+        assert_ne!(cosigner.xpub(), other.xpub());
+        assert!(!cosigner.has_xprv());
+
+        let (fingerprint, path) = cosigner.origin().unwrap();
+        assert_eq!(fingerprint, origin.0);
+        assert_eq!(path.as_ref().len(), origin.1.as_ref().len() + 1);
+        assert_eq!(derivation_path(&key, &cosigner).unwrap().as_ref().len(), 1);
+
+        let mut psbt = psbt(&cosigner);
+        signer.sign(&mut psbt, &cosigner).unwrap();
+        assert!(signed_by(&psbt, &cosigner));
+    }
+
+    #[test]
+    fn key_file_signer_shared_key() {
+        // Wallets created before each got a child share the signer's key.
+        let (xprv, origin) = account(2);
+        let signer = key_file("shared", &format!("[{}]{}", format_origin(&origin), xprv));
+        let xpub = bip32::ExtendedPubKey::from_priv(&secp256k1::Secp256k1::new(), &xprv);
+        let cosigner = Cosigner::new(CosignerType::External, None, Some(xpub), None).unwrap();
+
+        let mut psbt = psbt(&cosigner);
+        signer.sign(&mut psbt, &cosigner).unwrap();
+        assert!(signed_by(&psbt, &cosigner));
+    }
+
+    #[test]
+    fn key_file_origin() {
+        let (xprv, _) = account(3);
+        let err = key_file("no-origin", &xprv.to_string())
+            .key(Network::Regtest)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("requires its [fingerprint/path] origin"));
+
+        let master =
+            bip32::ExtendedPrivKey::new_master(bitcoin::Network::Testnet, &[3; 32]).unwrap();
+        let (_, (fingerprint, path)) = key_file("master", &master.to_string())
+            .key(Network::Regtest)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            fingerprint,
+            master.fingerprint(&secp256k1::Secp256k1::new())
+        );
+        assert!(path.as_ref().is_empty());
+
+        let err = key_file("mainnet", &master.to_string())
+            .key(Network::Mainnet)
+            .unwrap_err();
+        assert!(err.to_string().contains("another network"));
+    }
+
+    #[test]
+    fn key_file_signer_other_key() {
+        let (xprv, origin) = account(4);
+        let signer = key_file("other", &format!("[{}]{}", format_origin(&origin), xprv));
+        let cosigner = Cosigner::new_internal(Network::Regtest, &KeyOptions::default()).unwrap();
+
+        let err = signer.sign(&mut psbt(&cosigner), &cosigner).unwrap_err();
+        assert!(err.to_string().contains("doesn't hold the key"));
+    }
+
+    /// A signer process answering a single request.
+    fn serve(
+        path: &Path,
+        respond: impl FnOnce(PartiallySignedTransaction) -> String + Send + 'static,
+    ) -> JoinHandle<()> {
+        let listener = UnixListener::bind(path).unwrap();
+
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let psbt = PartiallySignedTransaction::from_str(line.trim()).unwrap();
+            writeln!(&stream, "{}", respond(psbt)).unwrap();
+        })
+    }
+
+    fn socket_signer(name: &str) -> (SocketSigner, Cosigner, bip32::ExtendedPrivKey) {
+        let (xprv, origin) = account(5);
+        let xpub = bip32::ExtendedPubKey::from_priv(&secp256k1::Secp256k1::new(), &xprv);
+        let key = (xpub, origin);
+        let options = KeyOptions {
+            xpub: Some(key.clone()),
+            ..Default::default()
+        };
+        let cosigner = Cosigner::new_internal(Network::Regtest, &options).unwrap();
+        let path = derivation_path(&key, &cosigner).unwrap();
+        let child = xprv
+            .derive_priv(&secp256k1::Secp256k1::new(), &path)
+            .unwrap();
+
+        let signer = SocketSigner {
+            path: temp_path(name),
+            key,
+            timeout: Duration::from_secs(5),
+        };
+        (signer, cosigner, child)
+    }
+
+    #[test]
+    fn socket_signer_signs() {
+        let (signer, cosigner, child) = socket_signer("sign.sock");
+        let origin = cosigner.origin();
+        let server = serve(&signer.path, move |mut psbt| {
+            sign_with_xprv(&mut psbt, &child, origin).unwrap();
+            psbt.to_string()
+        });
+
+        let mut psbt = psbt(&cosigner);
+        signer.sign(&mut psbt, &cosigner).unwrap();
+        server.join().unwrap();
+        assert!(signed_by(&psbt, &cosigner));
+    }
+
+    #[test]
+    fn socket_signer_refused() {
+        let (signer, cosigner, _) = socket_signer("refuse.sock");
+        let server = serve(&signer.path, |_| String::from("error: policy violated"));
+
+        let err = signer.sign(&mut psbt(&cosigner), &cosigner).unwrap_err();
+        server.join().unwrap();
+        assert_eq!(err.to_string(), "signer refused: policy violated");
+    }
+
+    #[test]
+    fn socket_signer_different_transaction() {
+        let (signer, cosigner, _) = socket_signer("different.sock");
+        let server = serve(&signer.path, |mut psbt| {
+            psbt.unsigned_tx.output[0].value = 1_000;
+            psbt.to_string()
+        });
+
+        let err = signer.sign(&mut psbt(&cosigner), &cosigner).unwrap_err();
+        server.join().unwrap();
+        assert!(err.to_string().contains("different transaction"));
+    }
+
+    #[test]
+    fn socket_signer_unreachable() {
+        let (signer, cosigner, _) = socket_signer("missing.sock");
+
+        let err = signer.sign(&mut psbt(&cosigner), &cosigner).unwrap_err();
+        assert!(err.to_string().starts_with("failed to connect to"));
+    }
+}
@@ -0,0 +1,695 @@
+//! SLIP-39 Shamir secret sharing, for splitting a secret into mnemonic shares
+//! of which any `threshold` recover it.
+//!
+//! Shares are written as a single group, which any SLIP-39 implementation can
+//! read. Shares from several groups are read as well.
+
+use std::{collections::BTreeMap, error::Error, fmt, str::FromStr};
+
+use bdk::bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+
+const WORDLIST: &str = include_str!("wordlist.txt");
+
+const RADIX_BITS: usize = 10;
+const CHECKSUM_WORDS: usize = 3;
+const HEADER_WORDS: usize = 4;
+const MIN_SECRET_LENGTH: usize = 16;
+const MAX_SHARE_COUNT: u8 = 16;
+
+const ROUND_COUNT: u8 = 4;
+const BASE_ITERATION_COUNT: u32 = 10000;
+/// Passphrase strengthening for new shares, doubling the iterations per step.
+const ITERATION_EXPONENT: u8 = 1;
+
+const SECRET_INDEX: u8 = 255;
+const DIGEST_INDEX: u8 = 254;
+const DIGEST_LENGTH: usize = 4;
+
+const CHECKSUM_GENERATOR: [u32; 10] = [
+    0xE0E040, 0x1C1C080, 0x3838100, 0x7070200, 0xE0E0009, 0x1C0C2412, 0x38086C24, 0x3090FC48,
+    0x21B1F890, 0x3F3F120,
+];
+
+/// Values of a sharing polynomial, by x coordinate.
+type Points = Vec<(u8, Vec<u8>)>;
+
+/// One share of a secret as carried by a mnemonic.
+#[derive(Clone)]
+struct Share {
+    identifier: u16,
+    extendable: bool,
+    iteration_exponent: u8,
+    group_index: u8,
+    group_threshold: u8,
+    group_count: u8,
+    member_index: u8,
+    member_threshold: u8,
+    value: Vec<u8>,
+}
+
+impl Share {
+    fn customization(extendable: bool) -> &'static [u8] {
+        match extendable {
+            true => b"shamir_extendable",
+            false => b"shamir",
+        }
+    }
+
+    fn words(&self) -> Vec<u16> {
+        let mut words = vec![
+            self.identifier >> 5,
+            (self.identifier & 0x1F) << 5
+                | (self.extendable as u16) << 4
+                | self.iteration_exponent as u16,
+        ];
+
+        let parameters = [
+            self.group_index,
+            self.group_threshold - 1,
+            self.group_count - 1,
+            self.member_index,
+            self.member_threshold - 1,
+        ]
+        .iter()
+        .fold(0u32, |bits, nibble| bits << 4 | *nibble as u32);
+        words.push((parameters >> RADIX_BITS) as u16);
+        words.push((parameters & 0x3FF) as u16);
+
+        words.extend(bytes_to_words(&self.value));
+
+        let values = Self::customization(self.extendable)
+            .iter()
+            .map(|byte| *byte as u32)
+            .chain(words.iter().map(|word| *word as u32))
+            .chain([0; CHECKSUM_WORDS])
+            .collect::<Vec<_>>();
+        let checksum = polymod(&values) ^ 1;
+        words.extend(
+            (0..CHECKSUM_WORDS)
+                .map(|i| (checksum >> (RADIX_BITS * (CHECKSUM_WORDS - 1 - i)) & 0x3FF) as u16),
+        );
+
+        words
+    }
+}
+
+impl FromStr for Share {
+    type Err = Box<dyn Error>;
+
+    fn from_str(mnemonic: &str) -> Result<Self, Self::Err> {
+        let wordlist = WORDLIST.split_whitespace().collect::<Vec<_>>();
+        let words = mnemonic
+            .split_whitespace()
+            .map(|word| {
+                wordlist
+                    .binary_search(&word.to_lowercase().as_str())
+                    .map(|index| index as u16)
+                    .map_err(|_| format!("'{}' is not a SLIP-39 word", word))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let min_words =
+            HEADER_WORDS + (MIN_SECRET_LENGTH * 8 + RADIX_BITS - 1) / RADIX_BITS + CHECKSUM_WORDS;
+        if words.len() < min_words {
+            return Err(format!("a share needs at least {} words", min_words).into());
+        }
+
+        let extendable = words[1] >> 4 & 1 == 1;
+        let values = Self::customization(extendable)
+            .iter()
+            .map(|byte| *byte as u32)
+            .chain(words.iter().map(|word| *word as u32))
+            .collect::<Vec<_>>();
+        if polymod(&values) != 1 {
+            return Err("share checksum does not match, check the words".into());
+        }
+
+        let parameters = (words[2] as u32) << RADIX_BITS | words[3] as u32;
+        let nibble = |i: u32| (parameters >> (16 - 4 * i) & 0xF) as u8;
+        let share = Self {
+            identifier: words[0] << 5 | words[1] >> 5,
+            extendable,
+            iteration_exponent: (words[1] & 0xF) as u8,
+            group_index: nibble(0),
+            group_threshold: nibble(1) + 1,
+            group_count: nibble(2) + 1,
+            member_index: nibble(3),
+            member_threshold: nibble(4) + 1,
+            value: words_to_bytes(&words[HEADER_WORDS..words.len() - CHECKSUM_WORDS])?,
+        };
+
+        if share.group_threshold > share.group_count {
+            return Err("share has a group threshold above its group count".into());
+        }
+
+        Ok(share)
+    }
+}
+
+impl fmt::Display for Share {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let wordlist = WORDLIST.split_whitespace().collect::<Vec<_>>();
+        let words = self
+            .words()
+            .into_iter()
+            .map(|word| wordlist[word as usize])
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+/// Splits `secret` into `count` mnemonics, any `threshold` of which recover it
+/// along with `passphrase`.
+pub fn split(
+    secret: &[u8],
+    threshold: u8,
+    count: u8,
+    passphrase: &str,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(split_groups(secret, 1, &[(threshold, count)], passphrase)?.remove(0))
+}
+
+/// Splits `secret` into groups of mnemonics, each given by its threshold and
+/// share count. The secret is recovered from `group_threshold` groups with
+/// enough shares each.
+fn split_groups(
+    secret: &[u8],
+    group_threshold: u8,
+    groups: &[(u8, u8)],
+    passphrase: &str,
+) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    if secret.len() < MIN_SECRET_LENGTH || secret.len() % 2 != 0 {
+        return Err("the secret must be an even number of bytes, at least 16".into());
+    }
+    if group_threshold == 0 || group_threshold as usize > groups.len() || groups.len() > 16 {
+        return Err("the group threshold must be at least 1 and at most the group count".into());
+    }
+    for (threshold, count) in groups {
+        if *threshold == 0 || threshold > count || *count > MAX_SHARE_COUNT {
+            return Err(format!(
+                "the threshold must be at least 1 and at most the share count, which is at most {}",
+                MAX_SHARE_COUNT
+            )
+            .into());
+        }
+        if *threshold == 1 && *count > 1 {
+            return Err("a threshold of 1 allows only a single share".into());
+        }
+    }
+    check_passphrase(passphrase)?;
+
+    let mut identifier = [0u8; 2];
+    OsRng.fill_bytes(&mut identifier);
+    let identifier = u16::from_be_bytes(identifier) & 0x7FFF;
+
+    let encrypted = encrypt(secret, passphrase, ITERATION_EXPONENT, identifier, false);
+
+    split_secret(group_threshold, groups.len() as u8, &encrypted)?
+        .into_iter()
+        .zip(groups)
+        .map(|((group_index, group_secret), (threshold, count))| {
+            Ok(split_secret(*threshold, *count, &group_secret)?
+                .into_iter()
+                .map(|(member_index, value)| {
+                    Share {
+                        identifier,
+                        extendable: false,
+                        iteration_exponent: ITERATION_EXPONENT,
+                        group_index,
+                        group_threshold,
+                        group_count: groups.len() as u8,
+                        member_index,
+                        member_threshold: *threshold,
+                        value,
+                    }
+                    .to_string()
+                })
+                .collect())
+        })
+        .collect()
+}
+
+/// Recovers the secret from enough mnemonics of the same split.
+pub fn combine<S: AsRef<str>>(
+    mnemonics: &[S],
+    passphrase: &str,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    check_passphrase(passphrase)?;
+
+    let shares = mnemonics
+        .iter()
+        .map(|mnemonic| Share::from_str(mnemonic.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let first = shares.first().ok_or("no shares were given")?;
+
+    let mut groups = BTreeMap::<u8, Vec<&Share>>::new();
+    for share in &shares {
+        if (
+            share.identifier,
+            share.extendable,
+            share.iteration_exponent,
+            share.group_threshold,
+            share.group_count,
+            share.value.len(),
+        ) != (
+            first.identifier,
+            first.extendable,
+            first.iteration_exponent,
+            first.group_threshold,
+            first.group_count,
+            first.value.len(),
+        ) {
+            return Err("the shares do not all belong to the same secret".into());
+        }
+
+        let group = groups.entry(share.group_index).or_default();
+        if group
+            .iter()
+            .any(|other| other.member_threshold != share.member_threshold)
+        {
+            return Err("the shares of a group disagree on its threshold".into());
+        }
+        if group
+            .iter()
+            .any(|other| other.member_index == share.member_index)
+        {
+            return Err(format!("share {} was given twice", share.member_index + 1).into());
+        }
+        group.push(share);
+    }
+
+    let mut group_secrets = vec![];
+    for (group_index, members) in groups {
+        let threshold = members[0].member_threshold;
+        if members.len() < threshold as usize {
+            continue;
+        }
+
+        let members = members
+            .iter()
+            .take(threshold as usize)
+            .map(|share| (share.member_index, share.value.clone()))
+            .collect::<Vec<_>>();
+        group_secrets.push((group_index, recover_secret(threshold, &members)?));
+        if group_secrets.len() == first.group_threshold as usize {
+            break;
+        }
+    }
+
+    if group_secrets.len() < first.group_threshold as usize {
+        return Err(match first.group_count {
+            1 => format!(
+                "{} shares are needed to recover the secret",
+                first.member_threshold
+            ),
+            _ => format!(
+                "complete shares from {} groups are needed to recover the secret",
+                first.group_threshold
+            ),
+        }
+        .into());
+    }
+
+    let encrypted = recover_secret(first.group_threshold, &group_secrets)?;
+
+    Ok(decrypt(
+        &encrypted,
+        passphrase,
+        first.iteration_exponent,
+        first.identifier,
+        first.extendable,
+    ))
+}
+
+fn check_passphrase(passphrase: &str) -> Result<(), Box<dyn Error>> {
+    match passphrase.bytes().all(|byte| (32..=126).contains(&byte)) {
+        true => Ok(()),
+        false => Err("the passphrase may only hold printable ASCII characters".into()),
+    }
+}
+
+fn polymod(values: &[u32]) -> u32 {
+    values.iter().fold(1, |checksum, value| {
+        let top = checksum >> 20;
+        let mut checksum = (checksum & 0xFFFFF) << RADIX_BITS ^ value;
+        for (i, generator) in CHECKSUM_GENERATOR.iter().enumerate() {
+            if top >> i & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+        checksum
+    })
+}
+
+/// Big-endian bits of `bytes` in 10 bit words, zero padded at the front.
+fn bytes_to_words(bytes: &[u8]) -> Vec<u16> {
+    let bit_count = bytes.len() * 8;
+    let padding = (RADIX_BITS - bit_count % RADIX_BITS) % RADIX_BITS;
+    let bits = std::iter::repeat(false)
+        .take(padding)
+        .chain(
+            bytes
+                .iter()
+                .flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1 == 1)),
+        )
+        .collect::<Vec<_>>();
+
+    bits.chunks(RADIX_BITS)
+        .map(|chunk| chunk.iter().fold(0, |word, bit| word << 1 | *bit as u16))
+        .collect()
+}
+
+fn words_to_bytes(words: &[u16]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let bit_count = words.len() * RADIX_BITS;
+    let length = bit_count / 16 * 2;
+    let padding = bit_count - length * 8;
+    if padding > 8 {
+        return Err("share has an invalid length".into());
+    }
+
+    let bits = words
+        .iter()
+        .flat_map(|word| (0..RADIX_BITS).rev().map(move |i| word >> i & 1 == 1))
+        .collect::<Vec<_>>();
+    if bits[..padding].iter().any(|bit| *bit) {
+        return Err("share has invalid padding".into());
+    }
+
+    Ok(bits[padding..]
+        .chunks(8)
+        .map(|chunk| chunk.iter().fold(0, |byte, bit| byte << 1 | *bit as u8))
+        .collect())
+}
+
+/// Logarithm and exponent tables of GF(256) over x^8 + x^4 + x^3 + x + 1.
+struct Field {
+    exp: [u8; 255],
+    log: [u8; 256],
+}
+
+impl Field {
+    fn new() -> Self {
+        let mut field = Self {
+            exp: [0; 255],
+            log: [0; 256],
+        };
+
+        let mut power: u16 = 1;
+        for i in 0..255 {
+            field.exp[i] = power as u8;
+            field.log[power as usize] = i as u8;
+            // Multiply by the generator x + 1.
+            power ^= power << 1;
+            if power & 0x100 != 0 {
+                power ^= 0x11B;
+            }
+        }
+
+        field
+    }
+
+    /// Evaluates at `x` the polynomial running through all of `shares`.
+    fn interpolate(&self, shares: &Points, x: u8) -> Vec<u8> {
+        if let Some((_, value)) = shares.iter().find(|(index, _)| *index == x) {
+            return value.clone();
+        }
+
+        let log = |value: u8| self.log[value as usize] as usize;
+        let log_product = shares
+            .iter()
+            .map(|(index, _)| log(index ^ x))
+            .sum::<usize>()
+            % 255;
+
+        let mut result = vec![0; shares[0].1.len()];
+        for (index, value) in shares {
+            let log_denominator = (log(index ^ x)
+                + shares
+                    .iter()
+                    .filter(|(other, _)| other != index)
+                    .map(|(other, _)| log(index ^ other))
+                    .sum::<usize>())
+                % 255;
+            let log_basis = (log_product + 255 - log_denominator) % 255;
+
+            for (byte, share_byte) in result.iter_mut().zip(value) {
+                if *share_byte != 0 {
+                    *byte ^= self.exp[(log(*share_byte) + log_basis) % 255];
+                }
+            }
+        }
+
+        result
+    }
+}
+
+fn random_bytes(length: usize) -> Vec<u8> {
+    let mut bytes = vec![0; length];
+    OsRng.fill_bytes(&mut bytes);
+    bytes
+}
+
+fn digest(random: &[u8], secret: &[u8]) -> Vec<u8> {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(random);
+    engine.input(secret);
+    hmac::Hmac::<sha256::Hash>::from_engine(engine)[..DIGEST_LENGTH].to_vec()
+}
+
+fn split_secret(threshold: u8, count: u8, secret: &[u8]) -> Result<Points, Box<dyn Error>> {
+    if threshold == 1 {
+        return Ok((0..count).map(|index| (index, secret.to_vec())).collect());
+    }
+
+    let mut shares = (0..threshold - 2)
+        .map(|index| (index, random_bytes(secret.len())))
+        .collect::<Vec<_>>();
+
+    let random = random_bytes(secret.len() - DIGEST_LENGTH);
+    let mut digest_share = digest(&random, secret);
+    digest_share.extend(&random);
+
+    let mut base = shares.clone();
+    base.push((DIGEST_INDEX, digest_share));
+    base.push((SECRET_INDEX, secret.to_vec()));
+
+    let field = Field::new();
+    for index in threshold - 2..count {
+        shares.push((index, field.interpolate(&base, index)));
+    }
+
+    Ok(shares)
+}
+
+fn recover_secret(threshold: u8, shares: &Points) -> Result<Vec<u8>, Box<dyn Error>> {
+    if threshold == 1 {
+        return Ok(shares[0].1.clone());
+    }
+
+    let field = Field::new();
+    let secret = field.interpolate(shares, SECRET_INDEX);
+    let digest_share = field.interpolate(shares, DIGEST_INDEX);
+    if digest(&digest_share[DIGEST_LENGTH..], &secret) != digest_share[..DIGEST_LENGTH] {
+        return Err("the shares do not recover a valid secret".into());
+    }
+
+    Ok(secret)
+}
+
+/// One round of the Feistel network encrypting the secret under the passphrase.
+fn round_function(
+    round: u8,
+    passphrase: &str,
+    iteration_exponent: u8,
+    salt: &[u8],
+    right: &[u8],
+) -> Vec<u8> {
+    let mut password = vec![round];
+    password.extend(passphrase.as_bytes());
+    let mut salt = salt.to_vec();
+    salt.extend(right);
+
+    pbkdf2(
+        &password,
+        &salt,
+        (BASE_ITERATION_COUNT << iteration_exponent) / ROUND_COUNT as u32,
+        right.len(),
+    )
+}
+
+fn pbkdf2(password: &[u8], salt: &[u8], iterations: u32, length: usize) -> Vec<u8> {
+    let keyed = hmac::HmacEngine::<sha256::Hash>::new(password);
+    let mut output = vec![];
+    for block in 1u32.. {
+        if output.len() >= length {
+            break;
+        }
+
+        let mut engine = keyed.clone();
+        engine.input(salt);
+        engine.input(&block.to_be_bytes());
+        let mut u = hmac::Hmac::<sha256::Hash>::from_engine(engine).into_inner();
+        let mut t = u;
+        for _ in 1..iterations {
+            let mut engine = keyed.clone();
+            engine.input(&u);
+            u = hmac::Hmac::<sha256::Hash>::from_engine(engine).into_inner();
+            t.iter_mut().zip(u).for_each(|(t, u)| *t ^= u);
+        }
+        output.extend(t);
+    }
+    output.truncate(length);
+
+    output
+}
+
+fn salt(identifier: u16, extendable: bool) -> Vec<u8> {
+    match extendable {
+        true => vec![],
+        false => [b"shamir".as_slice(), &identifier.to_be_bytes()].concat(),
+    }
+}
+
+fn feistel(
+    input: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+    rounds: impl Iterator<Item = u8>,
+) -> Vec<u8> {
+    let salt = salt(identifier, extendable);
+    let (left, right) = input.split_at(input.len() / 2);
+    let (mut left, mut right) = (left.to_vec(), right.to_vec());
+    for round in rounds {
+        let f = round_function(round, passphrase, iteration_exponent, &salt, &right);
+        let next = left.iter().zip(f).map(|(l, f)| l ^ f).collect();
+        left = std::mem::replace(&mut right, next);
+    }
+
+    [right, left].concat()
+}
+
+fn encrypt(
+    secret: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+) -> Vec<u8> {
+    feistel(
+        secret,
+        passphrase,
+        iteration_exponent,
+        identifier,
+        extendable,
+        0..ROUND_COUNT,
+    )
+}
+
+fn decrypt(
+    encrypted: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+) -> Vec<u8> {
+    feistel(
+        encrypted,
+        passphrase,
+        iteration_exponent,
+        identifier,
+        extendable,
+        (0..ROUND_COUNT).rev(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bdk::bitcoin::hashes::hex::ToHex;
+
+    /// Vectors from the SLIP-39 reference implementation, as
+    /// `[description, mnemonics, master secret]`. An empty secret means the
+    /// mnemonics must be rejected.
+    const VECTORS: &str = include_str!("vectors.json");
+
+    #[test]
+    fn vectors() {
+        let vectors: Vec<(String, Vec<String>, String)> = serde_json::from_str(VECTORS).unwrap();
+        for (description, mnemonics, secret) in vectors {
+            match combine(&mnemonics, "TREZOR") {
+                Ok(recovered) => assert_eq!(recovered.to_hex(), secret, "{}", description),
+                Err(e) => assert!(secret.is_empty(), "{}: {}", description, e),
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_members() {
+        for length in [16, 32, 64] {
+            let secret = random_bytes(length);
+            for (threshold, count) in [(1, 1), (2, 3), (3, 5)] {
+                let mnemonics = split(&secret, threshold, count, "").unwrap();
+                assert_eq!(mnemonics.len(), count as usize);
+                assert_eq!(
+                    combine(&mnemonics[..threshold as usize], "").unwrap(),
+                    secret
+                );
+                assert_eq!(
+                    combine(&mnemonics[count as usize - threshold as usize..], "").unwrap(),
+                    secret
+                );
+                if threshold > 1 {
+                    assert!(combine(&mnemonics[..threshold as usize - 1], "").is_err());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_groups() {
+        let secret = random_bytes(16);
+        let groups = split_groups(&secret, 2, &[(1, 1), (2, 3), (3, 5)], "").unwrap();
+
+        let mut mnemonics = groups[0].clone();
+        mnemonics.extend_from_slice(&groups[2][2..]);
+        assert_eq!(combine(&mnemonics, "").unwrap(), secret);
+
+        let mut mnemonics = groups[1][1..].to_vec();
+        mnemonics.extend_from_slice(&groups[2][..3]);
+        assert_eq!(combine(&mnemonics, "").unwrap(), secret);
+
+        // One group alone, or a second group short of its threshold.
+        assert!(combine(&groups[2], "").is_err());
+        let mut mnemonics = groups[0].clone();
+        mnemonics.push(groups[1][0].clone());
+        assert!(combine(&mnemonics, "").is_err());
+    }
+
+    #[test]
+    fn passphrase() {
+        let secret = random_bytes(32);
+        let mnemonics = split(&secret, 2, 2, "TREZOR").unwrap();
+        assert_eq!(combine(&mnemonics, "TREZOR").unwrap(), secret);
+        assert_ne!(combine(&mnemonics, "").unwrap(), secret);
+    }
+
+    #[test]
+    fn mixed_shares() {
+        let first = split(&random_bytes(16), 2, 3, "").unwrap();
+        let second = split(&random_bytes(16), 2, 3, "").unwrap();
+        assert!(combine(&[&first[0], &second[1]], "").is_err());
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert!(split(&random_bytes(15), 1, 1, "").is_err());
+        assert!(split(&random_bytes(17), 1, 1, "").is_err());
+        assert!(split(&random_bytes(16), 3, 2, "").is_err());
+        assert!(split(&random_bytes(16), 1, 2, "").is_err());
+        assert!(split(&random_bytes(16), 2, 17, "").is_err());
+        assert!(split(&random_bytes(16), 1, 1, "é").is_err());
+    }
+}
@@ -1,37 +1,72 @@
-use std::{collections::HashMap, error::Error, str::FromStr};
+use std::{collections::HashMap, error::Error, str::FromStr, sync::Arc};
 
 use bdk::{
-    bitcoin::{psbt::PartiallySignedTransaction, util::bip32, Address, Txid},
-    blockchain::{Blockchain, ElectrumBlockchain},
+    bitcoin::{
+        consensus::encode::{deserialize, serialize_hex},
+        hashes::hex::FromHex,
+        psbt::PartiallySignedTransaction,
+        secp256k1,
+        util::bip32,
+        Address, PublicKey, Script, Transaction, Txid,
+    },
+    blockchain::{AnyBlockchain, Blockchain, GetHeight, GetTx},
     database::MemoryDatabase,
     descriptor,
-    electrum_client::Client,
-    keys::{IntoDescriptorKey, ScriptContext},
+    descriptor::{get_checksum, Descriptor, DescriptorPublicKey, DescriptorXKey},
+    miniscript::{
+        descriptor::{ShInner, Wildcard, WshInner},
+        DescriptorTrait, ForEachKey,
+    },
     wallet::AddressIndex,
-    Balance, FeeRate, SignOptions, SyncOptions,
+    Balance, FeeRate, KeychainKind, LocalUtxo, SignOptions, SyncOptions, TransactionDetails,
 };
-use diesel::SqliteConnection;
+use chrono::{NaiveDateTime, Utc};
 use rust_decimal::{prelude::ToPrimitive, Decimal};
-use url::Url;
 use uuid::Uuid;
 
-use super::{Cosigner, CosignerType, Psbt};
-use crate::{db, db::WalletDescriptors};
+use super::{
+    backend::Pool, Cosigner, CosignerType, ExportFormat, KeyOptions, Psbt, Secret, Signer,
+};
+use crate::{
+    db,
+    db::{SecretDescriptors, WalletDescriptors, WalletMetadata},
+    export::{ExportSigner, WalletExport},
+    signer::{self, DatabaseSigner},
+};
 pub use db::{AddressType, Network};
 
-#[derive(Debug)]
-enum ExtendedKey {
-    PrivKey((bip32::ExtendedPrivKey, bip32::DerivationPath)),
-    PubKey((bip32::ExtendedPubKey, bip32::DerivationPath)),
+/// A public key of a wallet along with its origin, if known, which hardware
+/// signers need to recognise it.
+type OriginKey = (bip32::ExtendedPubKey, Option<bip32::KeySource>);
+
+/// A BDK wallet along with the chain source it was synced through, if any.
+type SyncedHandle = (Option<Arc<AnyBlockchain>>, bdk::Wallet<MemoryDatabase>);
+
+/// Number of addresses per keychain derived up front when no backend syncs the wallet.
+const OFFLINE_ADDRESS_CACHE: u32 = 100;
+
+/// Number of blocks transactions should confirm within when the backend
+/// estimates their fee rate.
+const FEE_ESTIMATE_TARGET: usize = 6;
+
+pub struct ReceiveAddress {
+    pub index: u64,
+    pub address: Address,
+    pub used: bool,
 }
 
-impl<Ctx: ScriptContext> IntoDescriptorKey<Ctx> for ExtendedKey {
-    fn into_descriptor_key(self) -> Result<bdk::keys::DescriptorKey<Ctx>, bdk::keys::KeyError> {
-        match self {
-            ExtendedKey::PrivKey(xprv) => xprv.into_descriptor_key(),
-            ExtendedKey::PubKey(xpub) => xpub.into_descriptor_key(),
-        }
-    }
+pub struct AddressKey {
+    pub cosigner: Option<Uuid>,
+    pub fingerprint: bip32::Fingerprint,
+    pub derivation_path: bip32::DerivationPath,
+    pub xpub: bip32::ExtendedPubKey,
+    pub public_key: PublicKey,
+}
+
+pub struct AddressVerification {
+    pub keychain: KeychainKind,
+    pub index: u32,
+    pub keys: Vec<AddressKey>,
 }
 
 pub struct Wallet {
@@ -40,76 +75,213 @@ pub struct Wallet {
     network: Network,
     required_signatures: u64,
     descriptors: WalletDescriptors,
+    metadata: WalletMetadata,
     receive_address_index: u64,
     change_address_index: u64,
     partially_signed_txs: HashMap<String, Psbt>,
-    blockchain: ElectrumBlockchain,
+    blockchain: Option<Arc<AnyBlockchain>>,
+    stored_balance: Decimal,
     bdk_handle: bdk::Wallet<MemoryDatabase>,
     internal_cosigner: Cosigner,
+    cosigners: Vec<Uuid>,
+    archived_at: Option<NaiveDateTime>,
+    archived_by: Option<String>,
+    successor: Option<Uuid>,
 }
 
 impl Wallet {
     pub fn new(
-        connection: &mut SqliteConnection,
-        backend_url: &Url,
+        connection: &mut db::DbConnection,
+        pool: Option<&Pool>,
         address_type: AddressType,
         network: Network,
         required_signatures: u64,
         cosigners: Vec<Uuid>,
+        key: &KeyOptions,
     ) -> Result<Self, Box<dyn Error>> {
-        let cosigner = Cosigner::new(CosignerType::Internal, None, None, Some(network))?;
-        let xpubs = Self::get_xpubs(connection, cosigners)?;
-
-        let (receive_descriptor, receive_descriptor_watch_only) = Self::create_descriptor(
-            address_type,
-            required_signatures as usize,
-            bip32::DerivationPath::from_str("m/0").unwrap(),
-            cosigner.xprv(),
-            &xpubs,
-        )?;
-
-        let (change_descriptor, change_descriptor_watch_only) = Self::create_descriptor(
-            address_type,
-            required_signatures as usize,
-            bip32::DerivationPath::from_str("m/1").unwrap(),
-            cosigner.xprv(),
-            &xpubs,
-        )?;
+        let cosigner = Cosigner::new_internal(network, key)?;
+        if key.xpub.is_some()
+            && !Cosigner::find(
+                connection,
+                None,
+                None,
+                Some(*cosigner.xpub()),
+                None,
+                None,
+                true,
+            )?
+            .is_empty()
+        {
+            return Err("the signer's key picked for this wallet is already in use".into());
+        }
+        let mut keys = Self::get_keys(connection, cosigners.clone())?;
+        let internal_key = (*cosigner.xpub(), cosigner.origin());
+        match cosigner.has_xprv() {
+            true => keys.insert(0, internal_key),
+            // The key is held by an external signer.
+            false => keys.push(internal_key),
+        }
 
-        let blockchain = Self::get_blockchain(backend_url)?;
-        let bdk_handle = Self::initialize_bdk_handle(
-            &receive_descriptor,
-            &change_descriptor,
-            &blockchain,
-            network,
-        )?;
+        let descriptors = WalletDescriptors {
+            receive_descriptor_watch_only: Self::create_descriptor(
+                address_type,
+                required_signatures as usize,
+                bip32::DerivationPath::from_str("m/0").unwrap(),
+                &keys,
+            )?,
+            change_descriptor_watch_only: Self::create_descriptor(
+                address_type,
+                required_signatures as usize,
+                bip32::DerivationPath::from_str("m/1").unwrap(),
+                &keys,
+            )?,
+        };
+        let (blockchain, bdk_handle) = Self::initialize_bdk_handle(&descriptors, pool, network)?;
 
         Ok(Self {
             uuid: None,
             address_type,
             network,
             required_signatures,
-            descriptors: WalletDescriptors {
-                receive_descriptor, // TODO encrypt
-                receive_descriptor_watch_only,
-                change_descriptor, // TODO encrypt
-                change_descriptor_watch_only,
-            },
+            descriptors,
+            metadata: WalletMetadata::default(),
             receive_address_index: 0,
             change_address_index: 0,
             partially_signed_txs: HashMap::new(),
             blockchain,
+            stored_balance: Decimal::ZERO,
             internal_cosigner: cosigner,
+            cosigners,
             bdk_handle,
+            archived_at: None,
+            archived_by: None,
+            successor: None,
         })
     }
 
+    /// Recreates a wallet from its exported descriptors and the key of its
+    /// internal cosigner, e.g. one recovered from SLIP-39 shares. The other
+    /// keys of the descriptors are registered as external cosigners unless
+    /// they already are.
+    pub fn recover(
+        connection: &mut db::DbConnection,
+        pool: Option<&Pool>,
+        network: Network,
+        export: &str,
+        key: &KeyOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let xprv = key
+            .xprv
+            .as_ref()
+            .ok_or("the key of the internal cosigner is required")?;
+        let xpub = bip32::ExtendedPubKey::from_priv(&secp256k1::Secp256k1::new(), xprv.expose());
+
+        let mut descriptors = export
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let descriptor = line.trim().split('#').next().unwrap_or_default();
+                Descriptor::<DescriptorPublicKey>::from_str(descriptor)
+            });
+        let (receive, change) = match (descriptors.next(), descriptors.next()) {
+            (Some(receive), Some(change)) => (receive?, change?),
+            _ => return Err("expected a receive and a change descriptor".into()),
+        };
+
+        let (address_type, required_signatures, keys) = match &receive {
+            Descriptor::Sh(sh) => match sh.as_inner() {
+                ShInner::SortedMulti(multi) => (AddressType::P2sh, multi.k, multi.pks.clone()),
+                ShInner::Wsh(wsh) => match wsh.as_inner() {
+                    WshInner::SortedMulti(multi) => {
+                        (AddressType::P2shwsh, multi.k, multi.pks.clone())
+                    }
+                    _ => return Err("only sortedmulti descriptors are supported".into()),
+                },
+                _ => return Err("only sortedmulti descriptors are supported".into()),
+            },
+            Descriptor::Wsh(wsh) => match wsh.as_inner() {
+                WshInner::SortedMulti(multi) => (AddressType::P2wsh, multi.k, multi.pks.clone()),
+                _ => return Err("only sortedmulti descriptors are supported".into()),
+            },
+            _ => return Err("only sortedmulti descriptors are supported".into()),
+        };
+
+        if !Self::find(
+            connection,
+            None,
+            None,
+            None,
+            None,
+            Some(&receive.to_string()),
+            None,
+            None,
+            true,
+        )?
+        .is_empty()
+        {
+            return Err("the wallet already exists".into());
+        }
+
+        let mut internal = false;
+        let mut cosigners = vec![];
+        for key in keys {
+            let xkey = match key {
+                DescriptorPublicKey::XPub(xkey) => xkey,
+                DescriptorPublicKey::SinglePub(_) => {
+                    return Err("only extended keys are supported".into())
+                }
+            };
+            if xkey.xkey.public_key == xpub.public_key && xkey.xkey.chain_code == xpub.chain_code {
+                internal = true;
+                continue;
+            }
+
+            let cosigner =
+                match Cosigner::find(connection, None, None, Some(xkey.xkey), None, None, false)?
+                    .into_iter()
+                    .find(|cosigner| matches!(cosigner.type_(), CosignerType::External))
+                {
+                    Some(cosigner) => cosigner,
+                    None => {
+                        let mut cosigner =
+                            Cosigner::new(CosignerType::External, None, Some(xkey.xkey), None)?;
+                        cosigner.set_origin(xkey.origin)?;
+                        cosigner.save(connection)?;
+                        cosigner
+                    }
+                };
+            cosigners.push(Uuid::from_str(
+                cosigner.uuid().ok_or("cosigner was not saved")?,
+            )?);
+        }
+        if !internal {
+            return Err("the key does not belong to this wallet, check the passphrase".into());
+        }
+
+        let wallet = Self::new(
+            connection,
+            pool,
+            address_type,
+            network,
+            required_signatures as u64,
+            cosigners,
+            key,
+        )?;
+        if Descriptor::<DescriptorPublicKey>::from_str(wallet.receive_descriptor())? != receive
+            || Descriptor::<DescriptorPublicKey>::from_str(wallet.change_descriptor())? != change
+        {
+            return Err("the descriptors do not match those of an ohm wallet".into());
+        }
+
+        Ok(wallet)
+    }
+
     pub fn from_db(
-        connection: &mut SqliteConnection,
-        backend_url: &Url,
+        connection: &mut db::DbConnection,
+        pool: Option<&Pool>,
         uuid: Option<Uuid>,
     ) -> Result<Option<Self>, Box<dyn Error>> {
-        let mut wallets = Self::find(connection, backend_url, uuid, None, None, None)?;
+        let mut wallets = Self::find(connection, pool, uuid, None, None, None, None, None, false)?;
 
         Ok(match !wallets.is_empty() {
             true => Some(wallets.remove(0)),
@@ -117,13 +289,17 @@ impl Wallet {
         })
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn find(
-        connection: &mut SqliteConnection,
-        backend_url: &Url,
+        connection: &mut db::DbConnection,
+        pool: Option<&Pool>,
         uuid: Option<Uuid>,
         address_type: Option<AddressType>,
         network: Option<Network>,
         receive_descriptor: Option<&str>,
+        name: Option<&str>,
+        tag: Option<&str>,
+        include_archived: bool,
     ) -> Result<Vec<Self>, Box<dyn Error>> {
         let records = db::Wallet::find(
             connection,
@@ -131,38 +307,68 @@ impl Wallet {
             address_type,
             network,
             receive_descriptor,
+            name,
+            tag,
+            include_archived,
         )?;
 
         let mut wallets = vec![];
         for record in records {
-            let cosigner = Cosigner::from_db(connection, Some(Uuid::from_str(&record.uuid)?))?
-                .ok_or("associated internal cosigner could not be found")?;
-
-            let blockchain = Self::get_blockchain(backend_url)?;
-            let bdk_handle = Self::initialize_bdk_handle(
-                &record.receive_descriptor, // TODO decrypt
-                &record.change_descriptor,  // TODO decrypt
-                &blockchain,
-                record.network,
-            )?;
+            let cosigner = Cosigner::find(
+                connection,
+                None,
+                None,
+                None,
+                Some(Uuid::from_str(&record.uuid)?),
+                None,
+                true,
+            )?
+            .pop()
+            .ok_or("associated internal cosigner could not be found")?;
+
+            let descriptors = WalletDescriptors {
+                receive_descriptor_watch_only: record.receive_descriptor_watch_only,
+                change_descriptor_watch_only: record.change_descriptor_watch_only,
+            };
+            let (blockchain, bdk_handle) =
+                Self::initialize_bdk_handle(&descriptors, pool, record.network)?;
+
+            let tags = db::WalletTag::find(connection, &record.uuid)?
+                .into_iter()
+                .map(|record| record.tag)
+                .collect();
+
+            let cosigners = db::WalletCosigner::find(connection, Some(&record.uuid), None)?
+                .into_iter()
+                .map(|record| Uuid::from_str(&record.cosigner_uuid))
+                .collect::<Result<_, _>>()?;
 
             wallets.push(Wallet {
                 address_type: record.address_type,
                 network: record.network,
                 required_signatures: record.required_signatures as u64,
-                descriptors: WalletDescriptors {
-                    receive_descriptor: record.receive_descriptor,
-                    receive_descriptor_watch_only: record.receive_descriptor_watch_only,
-                    change_descriptor: record.change_descriptor,
-                    change_descriptor_watch_only: record.change_descriptor_watch_only,
+                descriptors,
+                metadata: WalletMetadata {
+                    name: record.name,
+                    description: record.description,
+                    tags,
                 },
                 receive_address_index: record.receive_address_index as u64,
                 change_address_index: record.change_address_index as u64,
                 partially_signed_txs: Self::get_psbts(connection, Uuid::from_str(&record.uuid)?)?,
                 blockchain,
+                stored_balance: record.balance.0,
                 uuid: Some(record.uuid),
                 internal_cosigner: cosigner,
+                cosigners,
                 bdk_handle,
+                archived_at: record.archived_at,
+                archived_by: record.archived_by,
+                successor: record
+                    .successor_uuid
+                    .as_deref()
+                    .map(Uuid::from_str)
+                    .transpose()?,
             });
         }
 
@@ -173,17 +379,19 @@ impl Wallet {
         address_type: AddressType,
         required_signers: usize,
         derivation_path: bip32::DerivationPath,
-        xprv: &Option<bip32::ExtendedPrivKey>,
-        xpubs: &Vec<bip32::ExtendedPubKey>,
-    ) -> Result<(String, String), Box<dyn Error>> {
-        let mut keys = vec![];
-        if let Some(xprv) = xprv {
-            keys.push(ExtendedKey::PrivKey((*xprv, derivation_path.clone())));
-        }
-
-        for xpub in xpubs {
-            keys.push(ExtendedKey::PubKey((*xpub, derivation_path.clone())));
-        }
+        xpubs: &[OriginKey],
+    ) -> Result<String, Box<dyn Error>> {
+        let keys: Vec<_> = xpubs
+            .iter()
+            .map(|(xpub, origin)| {
+                DescriptorPublicKey::XPub(DescriptorXKey {
+                    origin: origin.clone(),
+                    xkey: *xpub,
+                    derivation_path: derivation_path.clone(),
+                    wildcard: Wildcard::Unhardened,
+                })
+            })
+            .collect();
 
         let descriptor = match address_type {
             AddressType::P2sh => descriptor!(sh(sortedmulti_vec(required_signers, keys))),
@@ -193,34 +401,59 @@ impl Wallet {
             }
         }?;
 
-        Ok((
-            descriptor.0.to_string_with_secret(&descriptor.1),
-            descriptor.0.to_string(),
-        ))
+        Ok(descriptor.0.to_string())
     }
 
-    fn get_xpubs(
-        connection: &mut SqliteConnection,
+    /// The descriptors with the private key of the internal cosigner in place
+    /// of its public key, as kept in the database. Wallets whose key is held
+    /// by a signer have none to add.
+    fn secret_descriptors(&self) -> Result<SecretDescriptors, Box<dyn Error>> {
+        let xprv = self
+            .internal_cosigner
+            .xprv()?
+            .map(|xprv| Secret::new(xprv.expose().to_string()));
+        let xpub = self.internal_cosigner.xpub().to_string();
+
+        let with_secret = |descriptor: &str| -> Result<Secret<String>, Box<dyn Error>> {
+            let xprv = match &xprv {
+                Some(xprv) => xprv,
+                None => return Ok(Secret::new(descriptor.to_string())),
+            };
+
+            let descriptor = descriptor.split('#').next().unwrap();
+            let descriptor = Secret::new(descriptor.replace(&xpub, xprv.expose()));
+            let checksum = get_checksum(descriptor.expose())?;
+            Ok(Secret::new(format!("{}#{}", descriptor.expose(), checksum)))
+        };
+
+        Ok(SecretDescriptors {
+            receive_descriptor: with_secret(&self.descriptors.receive_descriptor_watch_only)?,
+            change_descriptor: with_secret(&self.descriptors.change_descriptor_watch_only)?,
+        })
+    }
+
+    fn get_keys(
+        connection: &mut db::DbConnection,
         cosigner_ids: Vec<Uuid>,
-    ) -> Result<Vec<bip32::ExtendedPubKey>, Box<dyn Error>> {
-        let mut xpubs = vec![];
+    ) -> Result<Vec<OriginKey>, Box<dyn Error>> {
+        let mut keys = vec![];
         for uuid in cosigner_ids {
-            let records = db::Cosigner::find(connection, Some(&uuid), None, None, None)?;
-            let cosigner = records.get(0).ok_or_else(|| -> Box<dyn Error> {
-                format!("cosigner could not be found: {}", uuid).into()
-            })?;
-            xpubs.push(bip32::ExtendedPubKey::from_str(cosigner.xpub.as_ref())?);
+            let cosigner =
+                Cosigner::from_db(connection, Some(uuid))?.ok_or_else(|| -> Box<dyn Error> {
+                    format!("cosigner could not be found: {}", uuid).into()
+                })?;
+            keys.push((*cosigner.xpub(), cosigner.origin()));
         }
 
-        Ok(xpubs)
+        Ok(keys)
     }
 
     fn get_psbts(
-        connection: &mut SqliteConnection,
+        connection: &mut db::DbConnection,
         wallet: Uuid,
     ) -> Result<HashMap<String, Psbt>, Box<dyn Error>> {
         let mut psbts = HashMap::new();
-        for psbt in Psbt::find(connection, None, Some(wallet))? {
+        for psbt in Psbt::find(connection, None, Some(wallet), false)? {
             let uuid = psbt.uuid().unwrap().to_string();
             psbts.insert(uuid, psbt);
         }
@@ -228,40 +461,125 @@ impl Wallet {
         Ok(psbts)
     }
 
-    fn get_blockchain(backend_url: &Url) -> Result<ElectrumBlockchain, Box<dyn Error>> {
-        Ok(ElectrumBlockchain::from(Client::new(backend_url.as_str())?))
-    }
-
+    /// Syncs a fresh, watch-only BDK wallet through the shared backend pool;
+    /// without a pool, i.e. in offline mode, the wallet is left unsynced.
+    /// Signing goes through a [`Signer`] instead.
     fn initialize_bdk_handle(
-        receive_descriptor: &str,
-        change_descriptor: &str,
-        blockchain: &ElectrumBlockchain,
+        descriptors: &WalletDescriptors,
+        pool: Option<&Pool>,
         network: Network,
-    ) -> Result<bdk::Wallet<MemoryDatabase>, Box<dyn Error>> {
+    ) -> Result<SyncedHandle, Box<dyn Error>> {
         let wallet = bdk::Wallet::new(
-            receive_descriptor,
-            Some(change_descriptor),
+            descriptors.receive_descriptor_watch_only.as_str(),
+            Some(descriptors.change_descriptor_watch_only.as_str()),
             network.into(),
             MemoryDatabase::default(),
         )?;
 
-        wallet.sync(blockchain, SyncOptions::default())?;
+        let pool = match pool {
+            Some(pool) => pool,
+            None => {
+                // Without a sync nothing derives the wallet's scripts, which signing
+                // and PSBT decoding rely on to recognise our own inputs and outputs.
+                wallet.ensure_addresses_cached(OFFLINE_ADDRESS_CACHE)?;
+                return Ok((None, wallet));
+            }
+        };
 
-        Ok(wallet)
+        let receive = &descriptors.receive_descriptor_watch_only;
+        let change = &descriptors.change_descriptor_watch_only;
+
+        let blockchain = pool.get(network, receive, change)?;
+        if wallet
+            .sync(blockchain.as_ref(), SyncOptions::default())
+            .is_ok()
+        {
+            return Ok((Some(blockchain), wallet));
+        }
+
+        // The shared connection may have gone stale; reconnect once, failing
+        // over to the next reachable URL if need be.
+        pool.invalidate(network, receive, change)?;
+        let blockchain = pool.get(network, receive, change)?;
+        wallet.sync(blockchain.as_ref(), SyncOptions::default())?;
+
+        Ok((Some(blockchain), wallet))
     }
 
     pub fn address_type(&self) -> AddressType {
         self.address_type
     }
 
+    pub fn is_online(&self) -> bool {
+        self.blockchain.is_some()
+    }
+
     pub fn balance(&self) -> Result<Balance, Box<dyn Error>> {
+        if !self.is_online() {
+            return Err("balance is unavailable in offline mode".into());
+        }
+
         Ok(self.bdk_handle.get_balance()?)
     }
 
+    /// The confirmed balance as of the last time the wallet was saved online.
+    pub fn stored_balance(&self) -> Decimal {
+        self.stored_balance
+    }
+
     pub fn network(&self) -> Network {
         self.network
     }
 
+    pub fn transactions(&self) -> Result<Vec<TransactionDetails>, Box<dyn Error>> {
+        if !self.is_online() {
+            return Err("transactions are unavailable in offline mode".into());
+        }
+
+        let mut transactions = self.bdk_handle.list_transactions(false)?;
+        transactions.sort_by_key(|tx| {
+            tx.confirmation_time
+                .as_ref()
+                .map_or(u32::MAX, |time| time.height)
+        });
+
+        Ok(transactions)
+    }
+
+    /// Height to scan the wallet from: that of its first confirmed transaction,
+    /// or the current tip if it has none yet. Offline, it is unknown and 0.
+    fn birth_height(&self) -> Result<u32, Box<dyn Error>> {
+        let blockchain = match &self.blockchain {
+            Some(blockchain) => blockchain,
+            None => return Ok(0),
+        };
+
+        let first_height = self
+            .bdk_handle
+            .list_transactions(false)?
+            .iter()
+            .filter_map(|tx| tx.confirmation_time.as_ref().map(|time| time.height))
+            .min();
+
+        match first_height {
+            Some(height) => Ok(height),
+            None => Ok(blockchain.get_height()?),
+        }
+    }
+
+    pub fn utxos(&self) -> Result<Vec<LocalUtxo>, Box<dyn Error>> {
+        if !self.is_online() {
+            return Err("UTXOs are unavailable in offline mode".into());
+        }
+
+        Ok(self.bdk_handle.list_unspent()?)
+    }
+
+    pub fn is_mine(&self, script: &Script) -> Result<bool, Box<dyn Error>> {
+        Ok(self.bdk_handle.is_mine(script)?)
+    }
+
+    #[allow(clippy::misnamed_getters)]
     pub fn receive_descriptor(&self) -> &str {
         &self.descriptors.receive_descriptor_watch_only
     }
@@ -277,6 +595,7 @@ impl Wallet {
             .address)
     }
 
+    #[allow(clippy::misnamed_getters)]
     pub fn change_descriptor(&self) -> &str {
         &self.descriptors.change_descriptor_watch_only
     }
@@ -292,9 +611,67 @@ impl Wallet {
             .address)
     }
 
-    pub fn new_receive_address(&mut self) -> Result<Address, Box<dyn Error>> {
-        self.receive_address_index += 1;
-        self.receive_address()
+    /// Hands out the next receive address, recording it together with the
+    /// wallet's new address index in one transaction.
+    pub fn new_receive_address(
+        &mut self,
+        connection: &mut db::DbConnection,
+    ) -> Result<ReceiveAddress, Box<dyn Error>> {
+        let uuid = self.uuid.as_ref().ok_or("please save this wallet first")?;
+
+        let index = self.receive_address_index + 1;
+        let address = self
+            .bdk_handle
+            .get_address(AddressIndex::Peek(index as u32))?
+            .address;
+
+        connection.transaction(|connection| {
+            db::WalletAddress::new(uuid, index as i64, address.to_string()).insert(connection)?;
+            db::Wallet::set_receive_address_index(connection, uuid, index as i64)
+        })?;
+        self.receive_address_index = index;
+
+        Ok(ReceiveAddress {
+            index: self.receive_address_index,
+            address,
+            used: false,
+        })
+    }
+
+    pub fn receive_addresses(
+        &self,
+        connection: &mut db::DbConnection,
+        used: Option<bool>,
+    ) -> Result<Vec<ReceiveAddress>, Box<dyn Error>> {
+        let uuid = self.uuid.as_ref().ok_or("please save this wallet first")?;
+
+        // Addresses are only ever marked as used, so a reorg or a spent coin
+        // never hands the same address out twice. Offline there are no
+        // transactions and the flags stay as they were last seen online.
+        let mut funded = vec![];
+        for tx in self.bdk_handle.list_transactions(true)? {
+            if let Some(transaction) = tx.transaction {
+                for output in transaction.output {
+                    if let Some(address) =
+                        Address::from_script(&output.script_pubkey, self.network.into())
+                    {
+                        funded.push(address.to_string());
+                    }
+                }
+            }
+        }
+        db::WalletAddress::mark_used(connection, uuid, &funded)?;
+
+        let mut addresses = vec![];
+        for record in db::WalletAddress::find(connection, uuid, used)? {
+            addresses.push(ReceiveAddress {
+                index: record.address_index as u64,
+                address: Address::from_str(&record.address)?,
+                used: record.used,
+            });
+        }
+
+        Ok(addresses)
     }
 
     pub fn new_change_address(&mut self) -> Result<Address, Box<dyn Error>> {
@@ -302,6 +679,87 @@ impl Wallet {
         self.change_address()
     }
 
+    pub fn verify_address(
+        &self,
+        connection: &mut db::DbConnection,
+        address: &Address,
+        lookahead: u64,
+    ) -> Result<Option<AddressVerification>, Box<dyn Error>> {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let script_pubkey = address.script_pubkey();
+
+        let keychains = [
+            (
+                KeychainKind::External,
+                self.receive_descriptor(),
+                self.receive_address_index,
+            ),
+            (
+                KeychainKind::Internal,
+                self.change_descriptor(),
+                self.change_address_index,
+            ),
+        ];
+
+        for (keychain, descriptor, address_index) in keychains {
+            let descriptor = Descriptor::<DescriptorPublicKey>::from_str(descriptor)?;
+
+            for index in 0..=(address_index + lookahead) as u32 {
+                let derived = descriptor.derive(index);
+                if derived.derived_descriptor(&secp, index)?.script_pubkey() != script_pubkey {
+                    continue;
+                }
+
+                let mut derived_keys = vec![];
+                derived.for_each_key(|key| {
+                    derived_keys.push(key.as_key().clone());
+                    true
+                });
+
+                let mut keys = vec![];
+                for key in derived_keys {
+                    let xkey = match &key {
+                        DescriptorPublicKey::XPub(xkey) => xkey,
+                        DescriptorPublicKey::SinglePub(_) => continue,
+                    };
+
+                    let cosigner =
+                        Cosigner::find(connection, None, None, Some(xkey.xkey), None, None, true)?
+                            .into_iter()
+                            .next();
+
+                    let (fingerprint, derivation_path) =
+                        match (&xkey.origin, cosigner.as_ref().and_then(Cosigner::origin)) {
+                            (None, Some((fingerprint, path))) => {
+                                (fingerprint, path.extend(&xkey.derivation_path))
+                            }
+                            _ => (key.master_fingerprint(), key.full_derivation_path()),
+                        };
+
+                    keys.push(AddressKey {
+                        cosigner: cosigner
+                            .as_ref()
+                            .and_then(Cosigner::uuid)
+                            .map(Uuid::from_str)
+                            .transpose()?,
+                        fingerprint,
+                        derivation_path,
+                        xpub: xkey.xkey,
+                        public_key: key.derive_public_key(&secp)?,
+                    });
+                }
+
+                return Ok(Some(AddressVerification {
+                    keychain,
+                    index,
+                    keys,
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
     pub fn required_signatures(&self) -> u64 {
         self.required_signatures
     }
@@ -314,12 +772,134 @@ impl Wallet {
         self.uuid.as_deref()
     }
 
+    pub fn name(&self) -> Option<&str> {
+        self.metadata.name.as_deref()
+    }
+
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.metadata.name = name.filter(|name| !name.is_empty());
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.metadata.description.as_deref()
+    }
+
+    pub fn set_description(&mut self, description: Option<String>) {
+        self.metadata.description = description.filter(|description| !description.is_empty());
+    }
+
+    pub fn tags(&self) -> &Vec<String> {
+        &self.metadata.tags
+    }
+
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        let mut tags: Vec<String> = tags
+            .into_iter()
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        tags.sort();
+        tags.dedup();
+
+        self.metadata.tags = tags;
+    }
+
+    pub fn export(
+        &self,
+        connection: &mut db::DbConnection,
+        format: ExportFormat,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut keys = vec![];
+        let mut signers = vec![];
+        for key in WalletExport::keys(self.receive_descriptor())? {
+            let xkey = match key {
+                DescriptorPublicKey::XPub(xkey) => xkey,
+                DescriptorPublicKey::SinglePub(_) => continue,
+            };
+            let cosigner =
+                Cosigner::find(connection, None, None, Some(xkey.xkey), None, None, true)?
+                    .into_iter()
+                    .next();
+
+            let label = cosigner
+                .as_ref()
+                .map(|cosigner| {
+                    match (cosigner.type_(), cosigner.name(), cosigner.email_address()) {
+                        (_, Some(name), _) => name.to_string(),
+                        (CosignerType::Internal, None, _) => String::from("ohm"),
+                        (CosignerType::External, None, Some(email)) => email.to_string(),
+                        (CosignerType::External, None, None) => {
+                            cosigner.uuid().unwrap().to_string()
+                        }
+                    }
+                })
+                .unwrap_or_else(|| xkey.xkey.fingerprint().to_string());
+
+            // Wallets created before origins were kept have none in their
+            // descriptors, but their cosigners may have been given one since.
+            let origin = xkey
+                .origin
+                .or_else(|| cosigner.as_ref().and_then(Cosigner::origin));
+
+            keys.push((xkey.xkey, origin.clone()));
+            signers.push(ExportSigner {
+                label,
+                origin,
+                xpub: xkey.xkey.to_string(),
+            });
+        }
+
+        let receive_descriptor = Self::create_descriptor(
+            self.address_type,
+            self.required_signatures as usize,
+            bip32::DerivationPath::from_str("m/0").unwrap(),
+            &keys,
+        )?;
+        let change_descriptor = Self::create_descriptor(
+            self.address_type,
+            self.required_signatures as usize,
+            bip32::DerivationPath::from_str("m/1").unwrap(),
+            &keys,
+        )?;
+
+        let uuid = self.uuid.as_ref().ok_or("please save this wallet first")?;
+        let name = match self.name() {
+            Some(name) => name.chars().take(20).collect(),
+            None => format!("ohm-{}", &uuid[..8]),
+        };
+
+        let export = WalletExport {
+            name,
+            address_type: self.address_type,
+            required_signatures: self.required_signatures,
+            receive_descriptor: &receive_descriptor,
+            change_descriptor: &change_descriptor,
+            first_address: self
+                .bdk_handle
+                .get_address(AddressIndex::Peek(0))?
+                .address
+                .to_string(),
+            birth_height: self.birth_height()?,
+            signers,
+        };
+
+        export.render(format)
+    }
+
+    /// Creates a PSBT paying the recipient at the given fee rate, or at the
+    /// one the backend estimates if none is given.
     pub fn create_psbt(
         &mut self,
-        connection: &mut SqliteConnection,
+        connection: &mut db::DbConnection,
         amount: Decimal,
         recipient: Address,
+        fee_rate: Option<FeeRate>,
     ) -> Result<&Psbt, Box<dyn Error>> {
+        if !self.is_online() {
+            return Err("creating PSBTs is unavailable in offline mode".into());
+        }
+
+        let fee_rate = self.fee_rate(fee_rate)?;
         let mut builder = self.bdk_handle.build_tx();
         builder
             .add_recipient(
@@ -327,15 +907,127 @@ impl Wallet {
                 amount.to_i64().ok_or("unable to convert amount to i64")? as u64,
             )
             .enable_rbf()
-            .fee_rate(FeeRate::from_sat_per_vb(1.0));
+            .fee_rate(fee_rate);
 
         let (psbt, _details) = builder.finish()?;
         self.import_psbt(connection, psbt)
     }
 
+    /// The fee rate given or, if none is, the one the backend estimates,
+    /// raised to the minimum relay fee rate.
+    fn fee_rate(&self, fee_rate: Option<FeeRate>) -> Result<FeeRate, Box<dyn Error>> {
+        let fee_rate = match fee_rate {
+            Some(fee_rate) => fee_rate,
+            None => self
+                .blockchain
+                .as_ref()
+                .ok_or("estimating fees is unavailable in offline mode")?
+                .estimate_fee(FEE_ESTIMATE_TARGET)?,
+        };
+
+        // Estimates may fall below what nodes relay.
+        if fee_rate < FeeRate::default_min_relay_fee() {
+            return Ok(FeeRate::default_min_relay_fee());
+        }
+
+        Ok(fee_rate)
+    }
+
+    /// Creates a wallet to take over from this one, with a new internal key and
+    /// the external cosigners swapped as given, and links the two. Unless this
+    /// wallet is empty, a PSBT sweeping all of its funds to the new wallet is
+    /// created too, at the given fee rate or the one the backend estimates,
+    /// and its identifier returned.
+    pub fn rotate(
+        &mut self,
+        connection: &mut db::DbConnection,
+        pool: Option<&Pool>,
+        swaps: &HashMap<Uuid, Uuid>,
+        key: &KeyOptions,
+        fee_rate: Option<FeeRate>,
+    ) -> Result<(Self, Option<String>), Box<dyn Error>> {
+        let uuid = self.uuid.clone().ok_or("please save this wallet first")?;
+        if !self.is_online() {
+            return Err("rotating wallets is unavailable in offline mode".into());
+        }
+        if let Some(successor) = &self.successor {
+            return Err(format!("the wallet was already rotated into {}", successor).into());
+        }
+        for (old, new) in swaps {
+            if !self.cosigners.contains(old) {
+                return Err(format!("cosigner {} is not part of this wallet", old).into());
+            }
+
+            let cosigner = Cosigner::from_db(connection, Some(*new))?
+                .ok_or_else(|| format!("cosigner {} could not be found", new))?;
+            if let CosignerType::Internal = cosigner.type_() {
+                return Err("only external cosigners can be swapped in".into());
+            }
+        }
+
+        let cosigners = self
+            .cosigners
+            .iter()
+            .map(|uuid| *swaps.get(uuid).unwrap_or(uuid))
+            .collect::<Vec<_>>();
+        if cosigners
+            .iter()
+            .enumerate()
+            .any(|(i, uuid)| cosigners[..i].contains(uuid))
+        {
+            return Err("a cosigner would appear twice in the new wallet".into());
+        }
+
+        let mut successor = Self::new(
+            connection,
+            pool,
+            self.address_type,
+            self.network,
+            self.required_signatures,
+            cosigners,
+            key,
+        )?;
+        successor.metadata = WalletMetadata {
+            name: self.metadata.name.clone(),
+            description: self.metadata.description.clone(),
+            tags: self.metadata.tags.clone(),
+        };
+        let fee_rate = self.fee_rate(fee_rate)?;
+        let sweep = !self.utxos()?.is_empty();
+
+        let psbt = connection.transaction(|connection| {
+            successor.save(connection)?;
+            let successor_uuid = successor.uuid.clone().unwrap();
+            db::Wallet::set_successor(connection, &uuid, &successor_uuid)?;
+
+            if !sweep {
+                return Ok(None);
+            }
+
+            let recipient = successor.new_receive_address(connection)?.address;
+            let mut builder = self.bdk_handle.build_tx();
+            builder
+                .drain_wallet()
+                .drain_to(recipient.script_pubkey())
+                .enable_rbf()
+                .fee_rate(fee_rate);
+            let (psbt, _details) = builder.finish()?;
+
+            Ok(Some(
+                self.import_psbt(connection, psbt)?
+                    .uuid()
+                    .unwrap()
+                    .to_string(),
+            ))
+        })?;
+        self.successor = Some(Uuid::from_str(successor.uuid().unwrap())?);
+
+        Ok((successor, psbt))
+    }
+
     pub fn import_psbt(
         &mut self,
-        connection: &mut SqliteConnection,
+        connection: &mut db::DbConnection,
         bdk_handle: PartiallySignedTransaction,
     ) -> Result<&Psbt, Box<dyn Error>> {
         let mut psbt = Psbt::new(
@@ -350,25 +1042,42 @@ impl Wallet {
         Ok(self.partially_signed_txs.get(&uuid).unwrap())
     }
 
+    /// Signs the PSBT on behalf of the internal cosigner and finalizes it if
+    /// that was the last signature missing. Wallets whose internal key is
+    /// kept in the database keep signing with it, whichever `signer` is
+    /// configured.
     pub fn sign_psbt(
         &mut self,
-        connection: &mut SqliteConnection,
+        connection: &mut db::DbConnection,
         uuid: Uuid,
+        signer: &dyn Signer,
     ) -> Result<&Psbt, Box<dyn Error>> {
+        let signer = match self.internal_cosigner.has_xprv() {
+            true => &DatabaseSigner,
+            false => signer,
+        };
+        if let Some(key) = signer.key(self.network)? {
+            signer::derivation_path(&key, &self.internal_cosigner)?;
+        }
+
         let psbt = self
             .partially_signed_txs
             .get_mut(&uuid.to_string())
             .ok_or("failed to find PSBT")?;
 
-        self.bdk_handle.sign(psbt.inner(), SignOptions::default())?;
+        signer.sign(psbt.inner(), &self.internal_cosigner)?;
+        self.bdk_handle
+            .finalize_psbt(psbt.inner(), SignOptions::default())?;
         psbt.save(connection)?;
 
         Ok(psbt)
     }
 
+    /// Takes over the signatures of a PSBT signed elsewhere, e.g. on a
+    /// hardware wallet, and finalizes it if none are missing anymore.
     pub fn combine_psbt(
         &mut self,
-        connection: &mut SqliteConnection,
+        connection: &mut db::DbConnection,
         uuid: Uuid,
         additional_psbt: PartiallySignedTransaction,
     ) -> Result<&Psbt, Box<dyn Error>> {
@@ -378,63 +1087,218 @@ impl Wallet {
             .ok_or("failed to find PSBT")?;
 
         psbt.inner().combine(additional_psbt)?;
+        self.bdk_handle
+            .finalize_psbt(psbt.inner(), SignOptions::default())?;
         psbt.save(connection)?;
 
         Ok(psbt)
     }
 
+    /// Broadcasts the PSBT's transaction through an outbox: the transaction
+    /// is recorded before it's sent and the PSBT only removed once a backend
+    /// took it, so that an interrupted broadcast can be retried or resumed.
     pub fn broadcast_psbt(
         &mut self,
-        connection: &mut SqliteConnection,
+        connection: &mut db::DbConnection,
         uuid: Uuid,
     ) -> Result<Txid, Box<dyn Error>> {
+        let wallet_uuid = self.uuid.as_ref().ok_or("please save this wallet first")?;
+        let blockchain = self
+            .blockchain
+            .as_ref()
+            .ok_or("broadcasting is unavailable in offline mode")?;
         let psbt = self
             .partially_signed_txs
             .get_mut(&uuid.to_string())
             .ok_or("failed to find PSBT")?;
 
         let raw_transaction = psbt.inner().clone().extract_tx();
-        let tx_id = raw_transaction.txid();
+        let record = db::Broadcast::new(
+            raw_transaction.txid().to_string(),
+            wallet_uuid,
+            &uuid.to_string(),
+            serialize_hex(&raw_transaction),
+        )
+        .insert(connection)?;
 
-        self.blockchain.broadcast(&raw_transaction)?;
-        psbt.remove(connection)?;
+        let tx_id = Self::send(connection, blockchain, &record)?;
+        self.partially_signed_txs.remove(&uuid.to_string());
 
         Ok(tx_id)
     }
 
-    pub fn remove(&mut self, connection: &mut SqliteConnection) -> Result<(), Box<dyn Error>> {
+    /// Sends a recorded transaction unless the backend already knows it, then
+    /// marks it as sent and removes its PSBT in one transaction.
+    fn send(
+        connection: &mut db::DbConnection,
+        blockchain: &AnyBlockchain,
+        record: &db::BroadcastRecord,
+    ) -> Result<Txid, Box<dyn Error>> {
+        let transaction: Transaction = deserialize(&Vec::<u8>::from_hex(&record.raw_transaction)?)?;
+        let tx_id = transaction.txid();
+
+        if !matches!(blockchain.get_tx(&tx_id), Ok(Some(_))) {
+            if let Err(err) = blockchain.broadcast(&transaction) {
+                db::Broadcast::record_failure(connection, &record.txid, &err.to_string())?;
+                return Err(err.into());
+            }
+        }
+
+        connection.transaction(|connection| {
+            db::Broadcast::mark_sent(connection, &record.txid)?;
+            db::Psbt::remove(connection, &record.psbt_uuid)
+        })?;
+
+        Ok(tx_id)
+    }
+
+    /// Sends the transactions whose broadcast didn't complete, e.g. because
+    /// the server went down in between. Broadcasts that fail again stay
+    /// pending; returns how many went through.
+    pub fn resume_broadcasts(
+        connection: &mut db::DbConnection,
+        pool: &Pool,
+    ) -> Result<usize, Box<dyn Error>> {
+        let mut resumed = 0;
+        for record in db::Broadcast::pending(connection)? {
+            let wallet = match db::Wallet::find(
+                connection,
+                Some(&Uuid::from_str(&record.wallet_uuid)?),
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+            )?
+            .pop()
+            {
+                Some(wallet) => wallet,
+                None => continue,
+            };
+
+            if !pool.is_configured(wallet.network) {
+                continue;
+            }
+
+            let blockchain = match pool.get(
+                wallet.network,
+                &wallet.receive_descriptor_watch_only,
+                &wallet.change_descriptor_watch_only,
+            ) {
+                Ok(blockchain) => blockchain,
+                Err(_) => continue,
+            };
+
+            if Self::send(connection, &blockchain, &record).is_ok() {
+                resumed += 1;
+            }
+        }
+
+        Ok(resumed)
+    }
+
+    /// Archives the wallet on behalf of `actor`, together with its internal
+    /// key and PSBTs.
+    pub fn archive(
+        &mut self,
+        connection: &mut db::DbConnection,
+        actor: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(uuid) = &self.uuid {
+            let archived_at = Utc::now().naive_local();
+            connection.transaction(|connection| {
+                db::Wallet::archive(connection, uuid, archived_at, actor)
+            })?;
+            self.archived_at = Some(archived_at);
+            self.archived_by = Some(actor.to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Restores the wallet together with the internal key and PSBTs that were
+    /// archived along with it.
+    pub fn restore(&mut self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
         if let Some(uuid) = &self.uuid {
-            db::Wallet::remove(connection, uuid)?;
+            connection.transaction(|connection| db::Wallet::restore(connection, uuid))?;
+        }
+        self.archived_at = None;
+        self.archived_by = None;
+
+        Ok(())
+    }
+
+    /// Removes the wallet together with its internal key, PSBTs, labels and
+    /// addresses in a single transaction.
+    pub fn remove(&mut self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
+        if let Some(uuid) = &self.uuid {
+            connection.transaction(|connection| db::Wallet::remove(connection, uuid))?;
         }
         self.uuid = None;
 
         Ok(())
     }
 
-    pub fn save(&mut self, connection: &mut SqliteConnection) -> Result<(), Box<dyn Error>> {
+    pub fn save(&mut self, connection: &mut db::DbConnection) -> Result<(), Box<dyn Error>> {
+        if self.is_online() {
+            self.stored_balance = Decimal::from(self.balance()?.confirmed);
+        }
+
+        let secret_descriptors = self.secret_descriptors()?;
         let mut new_record = db::Wallet::new(
             self.address_type,
             self.network,
             self.required_signatures as i16,
-            &self.balance()?,
-            &self.descriptors, // TODO encrypt
+            self.stored_balance,
+            &self.descriptors,
+            &secret_descriptors,
             self.receive_address_index as i64,
             self.change_address_index as i64,
+            &self.metadata,
         );
 
         if let Some(uuid) = &self.uuid {
             new_record.uuid = uuid.clone();
         };
 
-        let record = new_record.upsert(connection)?;
+        let uuid = connection.transaction(|connection| {
+            let record = new_record.upsert(connection)?;
+            db::WalletTag::replace(connection, &record.uuid, &self.metadata.tags)?;
 
-        if self.uuid.is_none() {
-            self.internal_cosigner
-                .set_wallet(Uuid::from_str(&record.uuid)?)?;
-            self.internal_cosigner.save(connection)?;
-            self.uuid = Some(record.uuid)
-        }
+            if self.uuid.is_none() {
+                self.internal_cosigner
+                    .set_wallet(Uuid::from_str(&record.uuid)?)?;
+                self.internal_cosigner.save(connection)?;
+
+                for cosigner in &self.cosigners {
+                    db::WalletCosigner::new(&record.uuid, &cosigner.to_string())
+                        .insert(connection)?;
+                }
+            }
+
+            Ok(record.uuid)
+        })?;
+        self.uuid = Some(uuid);
 
         Ok(())
     }
+
+    /// External cosigners the wallet was created with.
+    pub fn cosigners(&self) -> &Vec<Uuid> {
+        &self.cosigners
+    }
+
+    pub fn archived_at(&self) -> Option<&NaiveDateTime> {
+        self.archived_at.as_ref()
+    }
+
+    pub fn archived_by(&self) -> Option<&str> {
+        self.archived_by.as_deref()
+    }
+
+    /// The wallet that took over from this one when its key was rotated.
+    pub fn successor(&self) -> Option<&Uuid> {
+        self.successor.as_ref()
+    }
 }